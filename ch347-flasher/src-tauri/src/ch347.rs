@@ -27,6 +27,10 @@ pub const MAX_DATA_LEN: usize = PACKET_SIZE - 3;
 // Timeouts
 pub const USB_TIMEOUT: Duration = Duration::from_millis(1000);
 
+// Transient USB error retries (backoff doubles after each attempt)
+pub const DEFAULT_USB_RETRIES: u32 = 3;
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
 // SPI Commands (from flashrom ch347_spi.c)
 pub const CMD_SPI_SET_CFG: u8 = 0xC0;   // Configure SPI
 pub const CMD_SPI_CS_CTRL: u8 = 0xC1;   // CS control
@@ -39,10 +43,17 @@ pub const CMD_SPI_GET_CFG: u8 = 0xCA;   // Get SPI config
 pub const CS_ASSERT: u8 = 0x00;    // Assert CS (active low)
 pub const CS_DEASSERT: u8 = 0x40;  // Deassert CS
 pub const CS_CHANGE: u8 = 0x80;    // Change CS state
-pub const CS_IGNORE: u8 = 0x00;    // Ignore this CS
+
+// GPIO command (from the WCH CH347 library): one control byte per pin
+pub const CMD_GPIO: u8 = 0xCC;
+pub const GPIO_COUNT: u8 = 8;       // GPIO0-GPIO7
+pub const GPIO_CHANGE: u8 = 0xC0;   // Apply this pin's direction and level
+pub const GPIO_DIR_OUT: u8 = 0x30;  // Drive the pin as an output
+pub const GPIO_HIGH: u8 = 0x08;     // Output level high
+pub const GPIO_LEVEL: u8 = 0x40;    // Pin level in the reply
 
 // SPI Clock speeds (divisor values)
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum SpiClock {
     Clk60MHz = 0,
@@ -61,6 +72,91 @@ impl Default for SpiClock {
     }
 }
 
+impl SpiClock {
+    pub const ALL: [SpiClock; 8] = [
+        SpiClock::Clk60MHz,
+        SpiClock::Clk30MHz,
+        SpiClock::Clk15MHz,
+        SpiClock::Clk7_5MHz,
+        SpiClock::Clk3_75MHz,
+        SpiClock::Clk1_875MHz,
+        SpiClock::Clk937_5KHz,
+        SpiClock::Clk468_75KHz,
+    ];
+
+    /// SCK frequency in Hz (60MHz base clock halved per divisor step)
+    pub fn hz(self) -> u32 {
+        60_000_000 >> (self as u8)
+    }
+
+    /// Clock for a divisor value (bits 5:3 of the config byte)
+    pub fn from_divisor(divisor: u8) -> SpiClock {
+        Self::ALL[(divisor & 7) as usize]
+    }
+
+    /// Pick the divisor whose frequency is nearest to the requested speed
+    pub fn from_khz(speed_khz: u32) -> SpiClock {
+        let target = speed_khz as i64 * 1000;
+        Self::ALL
+            .into_iter()
+            .min_by_key(|clk| (clk.hz() as i64 - target).abs())
+            .unwrap_or_default()
+    }
+}
+
+// SPI modes (clock polarity and phase)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpiMode {
+    #[default]
+    Mode0,  // CPOL=0, CPHA=0
+    Mode1,  // CPOL=0, CPHA=1
+    Mode2,  // CPOL=1, CPHA=0
+    Mode3,  // CPOL=1, CPHA=1
+}
+
+impl SpiMode {
+    /// Mode from its number (0-3)
+    pub fn from_number(mode: u8) -> Option<SpiMode> {
+        match mode {
+            0 => Some(SpiMode::Mode0),
+            1 => Some(SpiMode::Mode1),
+            2 => Some(SpiMode::Mode2),
+            3 => Some(SpiMode::Mode3),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    /// Clock idles high
+    pub fn cpol(self) -> bool {
+        matches!(self, SpiMode::Mode2 | SpiMode::Mode3)
+    }
+
+    /// Data sampled on the second clock edge
+    pub fn cpha(self) -> bool {
+        matches!(self, SpiMode::Mode1 | SpiMode::Mode3)
+    }
+}
+
+// SPI bit order within each byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,  // What every SPI flash expects
+    LsbFirst,
+}
+
+/// SPI clock, mode and bit order, as requested or as reported by the CH347
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiSettings {
+    pub clock: SpiClock,
+    pub mode: SpiMode,
+    pub bit_order: BitOrder,
+}
+
 #[derive(Error, Debug)]
 pub enum Ch347Error {
     #[error("USB error: {0}")]
@@ -69,9 +165,20 @@ pub enum Ch347Error {
     #[error("Device not found")]
     DeviceNotFound,
 
+    #[error("No CH347 device with serial number {0}")]
+    SerialNotFound(String),
+
     #[error("Device busy or permission denied")]
     DeviceBusy,
 
+    #[error("Permission denied opening USB device {0:04x}:{1:04x}; add a udev rule such as \
+             SUBSYSTEM==\"usb\", ATTRS{{idVendor}}==\"{0:04x}\", ATTRS{{idProduct}}==\"{1:04x}\", MODE=\"0666\" \
+             or run as root")]
+    PermissionDenied(u16, u16),
+
+    #[error("WinUSB driver not installed on interface {0} ({1}); use Zadig to install WinUSB for the CH347 SPI interface")]
+    DriverNotInstalled(u8, rusb::Error),
+
     #[error("Invalid response from device")]
     InvalidResponse,
 
@@ -80,6 +187,39 @@ pub enum Ch347Error {
 
     #[error("SPI not initialized")]
     SpiNotInitialized,
+
+    #[error("SPI config not applied: {0}")]
+    SpiConfigMismatch(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("Timeout waiting for {0}")]
+    Timeout(String),
+
+    #[error("No response from chip (MISO held low)")]
+    MisoStuckLow,
+
+    #[error("Chip not responding (status stuck at 0x{0:02X})")]
+    ChipNotResponding(u8),
+
+    #[error("JEDEC ID unstable ({0} then {1}), check wiring")]
+    JedecUnstable(String, String),
+
+    #[error("Verification failed at 0x{0:06X}")]
+    VerifyFailed(u32),
+
+    #[error("Not erased at 0x{0:06X}: programming can only clear bits")]
+    NotErased(u32),
+
+    #[error("No acknowledge from I2C device 0x{0:02X}")]
+    I2cNack(u8),
+
+    #[error("Not supported: {0}")]
+    Unsupported(String),
+
+    #[error("Write protected: {0}")]
+    Locked(String),
 }
 
 pub type Result<T> = std::result::Result<T, Ch347Error>;
@@ -91,19 +231,70 @@ pub struct DeviceInfo {
     pub pid: u16,
     pub manufacturer: String,
     pub product: String,
+    pub serial: Option<String>,
+    pub interface: u8,
+    pub bus: u8,
+    pub address: u8,
     pub is_ch347t: bool,
 }
 
+impl DeviceInfo {
+    /// Chip variant name ("CH347T" or "CH347F")
+    pub fn variant(&self) -> &'static str {
+        if self.is_ch347t { "CH347T" } else { "CH347F" }
+    }
+}
+
 /// CH347 Device Handle
 pub struct Ch347Device {
     handle: DeviceHandle<Context>,
     interface: u8,
+    driver_detached: bool,  // Kernel driver to reattach on drop
     spi_initialized: bool,
+    usb_retries: u32,
+    timeout: Duration,
+}
+
+/// Which adapter `open_matching` should open
+#[derive(Clone, Copy)]
+enum Wanted<'a> {
+    Any,
+    Serial(&'a str),
+    Port(u8, u8),  // USB bus number and device address
 }
 
 impl Ch347Device {
     /// Find and open CH347 device
     pub fn open() -> Result<Self> {
+        Self::open_matching(Wanted::Any, None)
+    }
+
+    /// Open the CH347 device whose USB serial number matches `serial`
+    pub fn open_by_serial(serial: &str) -> Result<Self> {
+        Self::open_matching(Wanted::Serial(serial), None)
+    }
+
+    /// Open a CH347 claiming `interface` instead of the usual SPI interface
+    ///
+    /// For firmware or composite configurations that put the SPI function
+    /// somewhere other than interface 2 (CH347T) or 4 (CH347F).
+    pub fn open_with_interface(serial: Option<&str>, interface: u8) -> Result<Self> {
+        let wanted = match serial {
+            Some(serial) => Wanted::Serial(serial),
+            None => Wanted::Any,
+        };
+        Self::open_matching(wanted, Some(interface))
+    }
+
+    /// Open the CH347 device at a USB bus and address, as reported by `list_devices`
+    ///
+    /// Tells apart adapters that share a serial number (or have none).
+    pub fn open_at(bus: u8, address: u8) -> Result<Self> {
+        Self::open_matching(Wanted::Port(bus, address), None)
+    }
+
+    /// Open the first CH347 device accepted by `wanted`, on `interface` if given
+    fn open_matching(wanted: Wanted, interface: Option<u8>) -> Result<Self> {
         let context = Context::new()?;
 
         // Try CH347T first, then CH347F
@@ -112,6 +303,8 @@ impl Ch347Device {
             (CH347F_PID, CH347F_IFACE),
         ];
 
+        let mut last_error = None;
+
         for device in context.devices()?.iter() {
             let desc = match device.device_descriptor() {
                 Ok(d) => d,
@@ -123,38 +316,73 @@ impl Ch347Device {
             }
 
             let pid = desc.product_id();
-            for (target_pid, iface) in devices_to_try.iter() {
-                if pid == *target_pid {
-                    match Self::open_device(&device, *iface) {
-                        Ok(dev) => return Ok(dev),
-                        Err(_) => break, // Try next device
+            let iface = match devices_to_try.iter().find(|(target_pid, _)| *target_pid == pid) {
+                Some((_, iface)) => interface.unwrap_or(*iface),
+                None => continue,
+            };
+
+            let specific = match wanted {
+                Wanted::Any => false,
+                Wanted::Serial(serial) => {
+                    if read_serial(&device, &desc).as_deref() != Some(serial) {
+                        continue;
+                    }
+                    true
+                }
+                Wanted::Port(bus, address) => {
+                    if device.bus_number() != bus || device.address() != address {
+                        continue;
+                    }
+                    true
+                }
+            };
+
+            log::debug!("Opening {:04X}:{:04X} on interface {}", CH347_VID, pid, iface);
+            match Self::open_device(&device, pid, iface) {
+                Ok(dev) => return Ok(dev),
+                Err(e) => {
+                    log::warn!("Could not claim interface {} of {:04X}:{:04X}: {}", iface, CH347_VID, pid, e);
+                    // The requested device exists but can't be opened
+                    if specific {
+                        return Err(e);
                     }
+                    last_error = Some(e);
                 }
             }
         }
 
-        Err(Ch347Error::DeviceNotFound)
+        // A CH347 that was present but couldn't be claimed says more than "not found"
+        match (wanted, last_error) {
+            (Wanted::Serial(s), _) => Err(Ch347Error::SerialNotFound(s.into())),
+            (_, Some(e)) => Err(e),
+            _ => Err(Ch347Error::DeviceNotFound),
+        }
     }
 
     /// Open specific device with given interface
-    fn open_device(device: &Device<Context>, interface: u8) -> Result<Self> {
-        let handle = device.open()?;
-
-        // Detach kernel driver if needed (Linux/macOS)
-        #[cfg(any(target_os = "linux", target_os = "macos"))]
-        {
-            if handle.kernel_driver_active(interface).unwrap_or(false) {
-                let _ = handle.detach_kernel_driver(interface);
-            }
-        }
+    fn open_device(device: &Device<Context>, pid: u16, interface: u8) -> Result<Self> {
+        let handle = device.open().map_err(|e| open_error(pid, interface, e))?;
+
+        // Detach kernel driver if needed (Linux/macOS), remembering to give it back
+        let driver_detached = cfg!(any(target_os = "linux", target_os = "macos"))
+            && handle.kernel_driver_active(interface).unwrap_or(false)
+            && handle.detach_kernel_driver(interface).is_ok();
 
         // Claim interface
-        handle.claim_interface(interface)?;
+        if let Err(e) = handle.claim_interface(interface) {
+            if driver_detached {
+                let _ = handle.attach_kernel_driver(interface);
+            }
+            return Err(open_error(pid, interface, e));
+        }
 
         Ok(Self {
             handle,
             interface,
+            driver_detached,
             spi_initialized: false,
+            usb_retries: DEFAULT_USB_RETRIES,
+            timeout: USB_TIMEOUT,
         })
     }
 
@@ -169,19 +397,74 @@ impl Ch347Device {
         let product = self.handle
             .read_product_string_ascii(&desc)
             .unwrap_or_default();
+        let serial = self.handle
+            .read_serial_number_string_ascii(&desc)
+            .ok();
 
         Ok(DeviceInfo {
             vid: desc.vendor_id(),
             pid: desc.product_id(),
             manufacturer,
             product,
+            serial,
+            interface: self.interface,
+            bus: device.bus_number(),
+            address: device.address(),
             is_ch347t: desc.product_id() == CH347T_PID,
         })
     }
 
+    /// USB bus number and device address of the opened adapter
+    pub fn bus_address(&self) -> (u8, u8) {
+        let device = self.handle.device();
+        (device.bus_number(), device.address())
+    }
+
+    /// Set how many times a transient USB error is retried
+    pub fn set_usb_retries(&mut self, retries: u32) {
+        self.usb_retries = retries;
+    }
+
+    /// Set the timeout applied to each bulk transfer
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
     /// Configure SPI interface (based on flashrom ch347_spi_config)
+    ///
+    /// The CH347 SPI engine is single-lane (MOSI/MISO only) and the config
+    /// packet has no I/O width field, so there is no quad/dual mode to enable
+    /// here. Multi-I/O flash reads cannot be sampled by this adapter.
     pub fn spi_init(&mut self, clock: SpiClock) -> Result<()> {
-        // 29-byte config packet (from flashrom)
+        self.spi_init_mode(clock, SpiMode::Mode0, BitOrder::MsbFirst)
+    }
+
+    /// Initialize SPI with the given clock, SPI mode and bit order
+    pub fn spi_init_mode(&mut self, clock: SpiClock, mode: SpiMode, bit_order: BitOrder) -> Result<()> {
+        // Send config
+        let cmd = Self::spi_config_packet(clock, mode, bit_order);
+        self.write_bulk(&cmd)?;
+
+        // Read response
+        let mut resp = [0u8; 29];
+        self.read_bulk(&mut resp)?;
+
+        // Read the config back to make sure the device took it
+        let requested = SpiSettings { clock, mode, bit_order };
+        let actual = self.read_spi_config()?;
+        if actual != requested {
+            return Err(Ch347Error::SpiConfigMismatch(format!(
+                "requested {:?}, device reports {:?}",
+                requested, actual
+            )));
+        }
+
+        self.spi_initialized = true;
+        Ok(())
+    }
+
+    /// Build the 29-byte config packet for `spi_init_mode` (from flashrom)
+    fn spi_config_packet(clock: SpiClock, mode: SpiMode, bit_order: BitOrder) -> [u8; 29] {
         let mut cmd = [0u8; 29];
         cmd[0] = CMD_SPI_SET_CFG;
         cmd[1] = 26;  // Payload length low byte
@@ -191,11 +474,11 @@ impl Ch347Device {
         cmd[5] = 4;
         cmd[6] = 1;
 
-        // Clock polarity (CPOL): bit 1 = 0 for mode 0
-        cmd[9] = 0;
+        // Clock polarity (CPOL): bit 1, set for modes 2 and 3
+        cmd[9] = if mode.cpol() { 2 } else { 0 };
 
-        // Clock phase (CPHA): bit 0 = 0 for mode 0
-        cmd[11] = 0;
+        // Clock phase (CPHA): bit 0, set for modes 1 and 3
+        cmd[11] = if mode.cpha() { 1 } else { 0 };
 
         // Another mystery byte
         cmd[14] = 2;
@@ -204,7 +487,7 @@ impl Ch347Device {
         cmd[15] = (clock as u8) << 3;
 
         // Bit order: bit 7, 0=MSB first
-        cmd[17] = 0;
+        cmd[17] = if bit_order == BitOrder::LsbFirst { 0x80 } else { 0 };
 
         // Yet another mystery byte
         cmd[19] = 7;
@@ -212,38 +495,113 @@ impl Ch347Device {
         // CS polarity: bit 7 CS2, bit 6 CS1. 0 = active low
         cmd[24] = 0;
 
-        // Send config
+        cmd
+    }
+
+    /// Read back the SPI configuration the CH347 is using
+    ///
+    /// The reply carries the same 29-byte layout as the `spi_init` packet.
+    pub fn read_spi_config(&mut self) -> Result<SpiSettings> {
+        let cmd = [CMD_SPI_GET_CFG, 1, 0, 1];
         self.write_bulk(&cmd)?;
 
-        // Read response
         let mut resp = [0u8; 29];
-        self.read_bulk(&mut resp)?;
+        if self.read_bulk(&mut resp)? < resp.len() || resp[0] != CMD_SPI_GET_CFG {
+            return Err(Ch347Error::InvalidResponse);
+        }
 
-        self.spi_initialized = true;
-        Ok(())
+        Ok(Self::decode_spi_config(&resp))
+    }
+
+    /// Decode the settings from a config packet (same layout as `spi_config_packet`)
+    fn decode_spi_config(resp: &[u8; 29]) -> SpiSettings {
+        let mode = match (resp[9] & 2 != 0, resp[11] & 1 != 0) {
+            (false, false) => SpiMode::Mode0,
+            (false, true) => SpiMode::Mode1,
+            (true, false) => SpiMode::Mode2,
+            (true, true) => SpiMode::Mode3,
+        };
+
+        SpiSettings {
+            clock: SpiClock::from_divisor(resp[15] >> 3),
+            mode,
+            bit_order: if resp[17] & 0x80 != 0 { BitOrder::LsbFirst } else { BitOrder::MsbFirst },
+        }
     }
 
     /// Control CS (chip select) - based on flashrom ch347_cs_control
-    pub fn spi_cs(&mut self, assert: bool) -> Result<()> {
+    ///
+    /// `cs_line` is 1 or 2. The other line is always driven deasserted so two
+    /// flashes sharing the bus are never selected together.
+    pub fn spi_cs(&mut self, cs_line: u8, assert: bool) -> Result<()> {
+        let cmd = Self::cs_packet(cs_line, assert)?;
+        self.write_bulk(&cmd)?;
+        Ok(())
+    }
+
+    /// Build the CS control packet for `spi_cs`
+    fn cs_packet(cs_line: u8, assert: bool) -> Result<[u8; 13]> {
+        let (selected, other) = match cs_line {
+            1 => (3, 8),  // CS1 control at offset 3
+            2 => (8, 3),  // CS2 control at offset 8
+            _ => return Err(Ch347Error::Unsupported(format!("chip select CS{}", cs_line))),
+        };
+
         let mut cmd = [0u8; 13];
         cmd[0] = CMD_SPI_CS_CTRL;
         cmd[1] = 10;  // Payload length
         cmd[2] = 0;
 
-        // CS1 control at offset 3
         if assert {
-            cmd[3] = CS_ASSERT | CS_CHANGE;
+            cmd[selected] = CS_ASSERT | CS_CHANGE;
         } else {
-            cmd[3] = CS_DEASSERT | CS_CHANGE;
+            cmd[selected] = CS_DEASSERT | CS_CHANGE;
         }
 
-        // CS2 control at offset 8 - ignore
-        cmd[8] = CS_IGNORE;
+        cmd[other] = CS_DEASSERT | CS_CHANGE;
 
-        self.write_bulk(&cmd)?;
+        Ok(cmd)
+    }
+
+    /// Drive a GPIO pin as an output at the given level
+    pub fn gpio_set(&mut self, pin: u8, level: bool) -> Result<()> {
+        let mut control = GPIO_CHANGE | GPIO_DIR_OUT;
+        if level {
+            control |= GPIO_HIGH;
+        }
+
+        self.gpio_command(pin, control)?;
         Ok(())
     }
 
+    /// Read the current level of a GPIO pin
+    pub fn gpio_get(&mut self, pin: u8) -> Result<bool> {
+        let resp = self.gpio_command(pin, 0)?;
+        Ok(resp[3 + pin as usize] & GPIO_LEVEL != 0)
+    }
+
+    /// Send a GPIO packet with `control` for `pin` and return the pin states
+    fn gpio_command(&mut self, pin: u8, control: u8) -> Result<[u8; 11]> {
+        if pin >= GPIO_COUNT {
+            return Err(Ch347Error::Unsupported(format!("GPIO{} (pins 0-{})", pin, GPIO_COUNT - 1)));
+        }
+
+        let mut cmd = [0u8; 11];
+        cmd[0] = CMD_GPIO;
+        cmd[1] = GPIO_COUNT;  // Payload length
+        cmd[2] = 0;
+        cmd[3 + pin as usize] = control;
+
+        self.write_bulk(&cmd)?;
+
+        let mut resp = [0u8; 11];
+        if self.read_bulk(&mut resp)? < resp.len() || resp[0] != CMD_GPIO {
+            return Err(Ch347Error::InvalidResponse);
+        }
+
+        Ok(resp)
+    }
+
     /// SPI write only - based on flashrom ch347_write
     pub fn spi_write(&mut self, data: &[u8]) -> Result<()> {
         if !self.spi_initialized {
@@ -323,8 +681,8 @@ impl Ch347Device {
     }
 
     /// SPI write then read (with CS control) - main interface for flash operations
-    pub fn spi_transfer(&mut self, write_data: &[u8], read_data: &mut [u8]) -> Result<()> {
-        self.spi_cs(true)?;
+    pub fn spi_transfer(&mut self, cs_line: u8, write_data: &[u8], read_data: &mut [u8]) -> Result<()> {
+        self.spi_cs(cs_line, true)?;
 
         if !write_data.is_empty() {
             self.spi_write(write_data)?;
@@ -334,27 +692,87 @@ impl Ch347Device {
             self.spi_read(read_data)?;
         }
 
-        self.spi_cs(false)?;
+        self.spi_cs(cs_line, false)?;
 
         Ok(())
     }
 
     /// Write to bulk endpoint
-    fn write_bulk(&self, data: &[u8]) -> Result<usize> {
-        let written = self.handle.write_bulk(EP_OUT, data, USB_TIMEOUT)?;
-        Ok(written)
+    pub(crate) fn write_bulk(&self, data: &[u8]) -> Result<usize> {
+        self.with_retry(EP_OUT, || self.handle.write_bulk(EP_OUT, data, self.timeout))
     }
 
     /// Read from bulk endpoint
-    fn read_bulk(&self, data: &mut [u8]) -> Result<usize> {
-        let read = self.handle.read_bulk(EP_IN, data, USB_TIMEOUT)?;
-        Ok(read)
+    pub(crate) fn read_bulk(&self, data: &mut [u8]) -> Result<usize> {
+        self.with_retry(EP_IN, || self.handle.read_bulk(EP_IN, data, self.timeout))
+    }
+
+    /// Run a bulk transfer, retrying transient errors with exponential backoff
+    fn with_retry<F>(&self, endpoint: u8, mut transfer: F) -> Result<usize>
+    where
+        F: FnMut() -> rusb::Result<usize>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            match transfer() {
+                Ok(n) => return Ok(n),
+                Err(e) if is_transient(e) && attempt < self.usb_retries => {
+                    // A stalled endpoint stays stalled until the halt is cleared
+                    if e == rusb::Error::Pipe {
+                        let _ = self.handle.clear_halt(endpoint);
+                    }
+
+                    log::warn!("USB transfer on endpoint 0x{:02X} failed ({}), retrying", endpoint, e);
+                    std::thread::sleep(RETRY_BACKOFF * 2u32.pow(attempt));
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Explain open/claim failures caused by host setup rather than the adapter
+///
+/// On Windows these mean no WinUSB driver is bound; elsewhere `Access` means
+/// the user may not open the device node (no udev rule).
+fn open_error(pid: u16, interface: u8, e: rusb::Error) -> Ch347Error {
+    match e {
+        rusb::Error::Access | rusb::Error::NotSupported if cfg!(target_os = "windows") => {
+            Ch347Error::DriverNotInstalled(interface, e)
+        }
+        rusb::Error::Access => Ch347Error::PermissionDenied(CH347_VID, pid),
+        e => e.into(),
     }
 }
 
+/// SPI interface number used by a CH347 product ID
+pub fn interface_for_pid(pid: u16) -> u8 {
+    if pid == CH347T_PID { CH347T_IFACE } else { CH347F_IFACE }
+}
+
+/// Read a device's serial number string, if it has one
+fn read_serial(device: &Device<Context>, desc: &rusb::DeviceDescriptor) -> Option<String> {
+    let handle = device.open().ok()?;
+    handle.read_serial_number_string_ascii(desc).ok()
+}
+
+/// Errors worth retrying; NoDevice, Access and the like are fatal
+fn is_transient(e: rusb::Error) -> bool {
+    matches!(e, rusb::Error::Timeout | rusb::Error::Pipe | rusb::Error::Io)
+}
+
 impl Drop for Ch347Device {
     fn drop(&mut self) {
         let _ = self.handle.release_interface(self.interface);
+
+        // Hand the interface back so other tools can use the adapter
+        if self.driver_detached {
+            if let Err(e) = self.handle.attach_kernel_driver(self.interface) {
+                log::warn!("Could not reattach kernel driver to interface {}: {}", self.interface, e);
+            }
+        }
     }
 }
 
@@ -363,6 +781,7 @@ pub fn list_devices() -> Result<Vec<DeviceInfo>> {
     let context = Context::new()?;
     let pids = [CH347T_PID, CH347F_PID];
     let mut devices = Vec::new();
+    let mut denied = None;
 
     for device in context.devices()?.iter() {
         let desc = match device.device_descriptor() {
@@ -373,6 +792,10 @@ pub fn list_devices() -> Result<Vec<DeviceInfo>> {
         if desc.vendor_id() == CH347_VID && pids.contains(&desc.product_id()) {
             let handle = match device.open() {
                 Ok(h) => h,
+                Err(rusb::Error::Access) if !cfg!(target_os = "windows") => {
+                    denied = Some(Ch347Error::PermissionDenied(CH347_VID, desc.product_id()));
+                    continue;
+                }
                 Err(_) => continue,
             };
 
@@ -382,16 +805,71 @@ pub fn list_devices() -> Result<Vec<DeviceInfo>> {
             let product = handle
                 .read_product_string_ascii(&desc)
                 .unwrap_or_default();
+            let serial = handle
+                .read_serial_number_string_ascii(&desc)
+                .ok();
 
             devices.push(DeviceInfo {
                 vid: desc.vendor_id(),
                 pid: desc.product_id(),
                 manufacturer,
                 product,
+                serial,
+                interface: interface_for_pid(desc.product_id()),
+                bus: device.bus_number(),
+                address: device.address(),
                 is_ch347t: desc.product_id() == CH347T_PID,
             });
         }
     }
 
-    Ok(devices)
+    // Adapters are plugged in but none could be opened
+    match denied {
+        Some(e) if devices.is_empty() => Err(e),
+        _ => Ok(devices),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asserting_one_chip_select_deasserts_the_other() {
+        let cs1 = Ch347Device::cs_packet(1, true).unwrap();
+        assert_eq!(&cs1[..3], [CMD_SPI_CS_CTRL, 10, 0]);
+        assert_eq!(cs1[3], CS_ASSERT | CS_CHANGE);
+        assert_eq!(cs1[8], CS_DEASSERT | CS_CHANGE);
+
+        let cs2 = Ch347Device::cs_packet(2, true).unwrap();
+        assert_eq!(cs2[8], CS_ASSERT | CS_CHANGE);
+        assert_eq!(cs2[3], CS_DEASSERT | CS_CHANGE);
+
+        let release = Ch347Device::cs_packet(2, false).unwrap();
+        assert_eq!((release[3], release[8]), (CS_DEASSERT | CS_CHANGE, CS_DEASSERT | CS_CHANGE));
+
+        assert!(matches!(Ch347Device::cs_packet(3, true), Err(Ch347Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn spi_mode_sets_the_cpol_and_cpha_bytes() {
+        let expected = [
+            (SpiMode::Mode0, 0, 0),
+            (SpiMode::Mode1, 0, 1),
+            (SpiMode::Mode2, 2, 0),
+            (SpiMode::Mode3, 2, 1),
+        ];
+
+        for (mode, cpol, cpha) in expected {
+            let cmd = Ch347Device::spi_config_packet(SpiClock::Clk15MHz, mode, BitOrder::MsbFirst);
+            assert_eq!(&cmd[..3], [CMD_SPI_SET_CFG, 26, 0]);
+            assert_eq!((cmd[9], cmd[11]), (cpol, cpha), "{:?}", mode);
+
+            let settings = SpiSettings { clock: SpiClock::Clk15MHz, mode, bit_order: BitOrder::MsbFirst };
+            assert_eq!(Ch347Device::decode_spi_config(&cmd), settings);
+        }
+
+        let lsb = Ch347Device::spi_config_packet(SpiClock::Clk60MHz, SpiMode::Mode3, BitOrder::LsbFirst);
+        assert_eq!(Ch347Device::decode_spi_config(&lsb).bit_order, BitOrder::LsbFirst);
+    }
 }
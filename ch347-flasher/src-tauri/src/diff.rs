@@ -0,0 +1,59 @@
+//! Image Comparison
+//!
+//! Finds the byte ranges that differ between two images
+
+use serde::{Deserialize, Serialize};
+
+/// A run of differing bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffRange {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// Result of comparing two images
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageDiff {
+    pub ranges: Vec<DiffRange>,
+    pub bytes_differing: usize,
+}
+
+impl ImageDiff {
+    /// Compare equal-length chunks found at `offset`, continuing the ranges so far
+    ///
+    /// Chunks must be passed in address order for ranges to coalesce.
+    pub fn compare_chunk(&mut self, offset: usize, a: &[u8], b: &[u8]) {
+        for (i, (x, y)) in a.iter().zip(b).enumerate() {
+            if x != y {
+                self.mark(offset + i);
+            }
+        }
+    }
+
+    /// Record a differing byte, extending the last range if it is adjacent
+    fn mark(&mut self, offset: usize) {
+        self.bytes_differing += 1;
+        match self.ranges.last_mut() {
+            Some(r) if r.start + r.len == offset => r.len += 1,
+            _ => self.ranges.push(DiffRange { start: offset, len: 1 }),
+        }
+    }
+}
+
+/// Compare two images, treating the shorter one as padded with 0xFF
+///
+/// Adjacent differing bytes are coalesced into a single range.
+pub fn diff_images(a: &[u8], b: &[u8]) -> ImageDiff {
+    let len = a.len().max(b.len());
+    let byte = |data: &[u8], i: usize| data.get(i).copied().unwrap_or(0xFF);
+
+    let mut diff = ImageDiff::default();
+
+    for i in 0..len {
+        if byte(a, i) != byte(b, i) {
+            diff.mark(i);
+        }
+    }
+
+    diff
+}
@@ -0,0 +1,38 @@
+//! Hex Dump Formatting
+//!
+//! Renders bytes as an `xxd`-style listing: address, 16 bytes in 2-byte
+//! groups and an ASCII gutter
+
+// Bytes shown per line
+pub const BYTES_PER_LINE: usize = 16;
+
+// Width of the hex column: 2 digits per byte plus a space after each group
+const HEX_WIDTH: usize = BYTES_PER_LINE * 2 + BYTES_PER_LINE / 2;
+
+/// Format `data` read from address `base`
+///
+/// Non-printable bytes show as '.' and a short final line is padded so its
+/// ASCII gutter lines up with the full lines above.
+pub fn hex_dump(base: u32, data: &[u8]) -> String {
+    let mut out = String::new();
+
+    for (i, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let mut hex = String::with_capacity(HEX_WIDTH);
+        for (j, byte) in line.iter().enumerate() {
+            hex.push_str(&format!("{:02x}", byte));
+            if j % 2 == 1 {
+                hex.push(' ');
+            }
+        }
+
+        let ascii: String = line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+
+        let addr = base as usize + i * BYTES_PER_LINE;
+        out.push_str(&format!("{:08x}: {:<width$} {}\n", addr, hex, ascii, width = HEX_WIDTH));
+    }
+
+    out
+}
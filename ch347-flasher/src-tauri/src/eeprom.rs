@@ -0,0 +1,168 @@
+//! I2C EEPROM Programming
+//!
+//! Reads and writes 24Cxx serial EEPROMs through the CH347 I2C interface
+
+use crate::ch347::{Ch347Device, Ch347Error, Result};
+use crate::i2c::MAX_TRANSFER;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Usual 7-bit address of a 24Cxx with A0-A2 tied low
+pub const DEFAULT_ADDRESS: u8 = 0x50;
+
+// Bytes reachable with one address byte; larger parts spill into the device address
+const BLOCK_SIZE: usize = 256;
+
+// Internal write cycle (tWR is 5ms on most parts, 10ms on some)
+pub const WRITE_CYCLE_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// EEPROM geometry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EepromChip {
+    pub name: String,
+    pub size: usize,       // Total size in bytes
+    pub page_size: usize,  // Largest single write
+    pub addr_bytes: u8,    // Word address bytes (1 or 2)
+}
+
+impl EepromChip {
+    fn new(name: &str, size: usize, page_size: usize, addr_bytes: u8) -> Self {
+        Self {
+            name: name.into(),
+            size,
+            page_size,
+            addr_bytes,
+        }
+    }
+}
+
+/// EEPROM database
+pub fn get_eeprom_database() -> Vec<EepromChip> {
+    vec![
+        // One address byte; 24C04-24C16 put address bits 8-10 in the device address
+        EepromChip::new("24C01", 128, 8, 1),
+        EepromChip::new("24C02", 256, 8, 1),
+        EepromChip::new("24C04", 512, 16, 1),
+        EepromChip::new("24C08", 1024, 16, 1),
+        EepromChip::new("24C16", 2048, 16, 1),
+        // Two address bytes
+        EepromChip::new("24C32", 4 * 1024, 32, 2),
+        EepromChip::new("24C64", 8 * 1024, 32, 2),
+        EepromChip::new("24C128", 16 * 1024, 64, 2),
+        EepromChip::new("24C256", 32 * 1024, 64, 2),
+        EepromChip::new("24C512", 64 * 1024, 128, 2),
+    ]
+}
+
+/// 24Cxx EEPROM programmer on a borrowed CH347
+pub struct EepromProgrammer<'a> {
+    device: &'a mut Ch347Device,
+    chip: EepromChip,
+    address: u8,
+}
+
+impl<'a> EepromProgrammer<'a> {
+    pub fn new(device: &'a mut Ch347Device, chip: EepromChip, address: u8) -> Self {
+        Self {
+            device,
+            chip,
+            address,
+        }
+    }
+
+    /// Total size in bytes
+    pub fn size(&self) -> usize {
+        self.chip.size
+    }
+
+    /// Check that an EEPROM answers at the configured address
+    ///
+    /// 24Cxx parts have no ID to read, so the size comes from the chosen type.
+    pub fn detect(&mut self) -> Result<bool> {
+        self.device.i2c_probe(self.address)
+    }
+
+    /// Read `data.len()` bytes starting at `offset`
+    pub fn read(&mut self, offset: usize, data: &mut [u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        self.check_range(offset, data.len())?;
+
+        let total = data.len();
+        let mut done = 0;
+
+        while done < total {
+            // Stay inside one 256-byte block so the device address is fixed
+            let pos = offset + done;
+            let len = (BLOCK_SIZE - pos % BLOCK_SIZE).min(MAX_TRANSFER).min(total - done);
+            let (dev, word) = self.locate(pos);
+
+            self.device.i2c_read(dev, &word, &mut data[done..done + len])?;
+            done += len;
+
+            if let Some(cb) = progress {
+                cb(done, total);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write `data` starting at `offset`, one page per write cycle
+    pub fn write(&mut self, offset: usize, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        self.check_range(offset, data.len())?;
+
+        let page_size = self.chip.page_size;
+        let total = data.len();
+        let mut done = 0;
+
+        while done < total {
+            // Writes past the page end would wrap to its start
+            let pos = offset + done;
+            let len = (page_size - pos % page_size).min(total - done);
+            let (dev, mut packet) = self.locate(pos);
+            packet.extend_from_slice(&data[done..done + len]);
+
+            self.device.i2c_write(dev, &packet)?;
+            self.wait_write_cycle(dev)?;
+            done += len;
+
+            if let Some(cb) = progress {
+                cb(done, total);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Device address and word address bytes for a byte offset
+    fn locate(&self, offset: usize) -> (u8, Vec<u8>) {
+        if self.chip.addr_bytes == 1 {
+            let block = ((offset >> 8) & 0x07) as u8;
+            (self.address | block, vec![offset as u8])
+        } else {
+            (self.address, vec![(offset >> 8) as u8, offset as u8])
+        }
+    }
+
+    /// Poll for the address ACK that ends the internal write cycle
+    fn wait_write_cycle(&mut self, dev: u8) -> Result<()> {
+        let start = Instant::now();
+
+        while !self.device.i2c_probe(dev)? {
+            if start.elapsed() > WRITE_CYCLE_TIMEOUT {
+                return Err(Ch347Error::Timeout("EEPROM write cycle".into()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        if offset + len > self.chip.size {
+            return Err(Ch347Error::TransferFailed(format!(
+                "Range 0x{:X}+0x{:X} exceeds {} size ({})",
+                offset, len, self.chip.name, self.chip.size
+            )));
+        }
+        Ok(())
+    }
+}
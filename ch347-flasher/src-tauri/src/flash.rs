@@ -2,28 +2,424 @@
 //!
 //! Support for common SPI NOR flash chips used in BIOS
 
-use crate::ch347::{Ch347Device, Ch347Error, Result, SpiClock};
+use crate::ch347::{BitOrder, Ch347Device, Ch347Error, Result, SpiClock, SpiMode, SpiSettings, GPIO_COUNT, MAX_DATA_LEN};
+use crate::sfdp::{self, FlashParams};
 use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // Common SPI Flash Commands
 pub const CMD_READ_JEDEC_ID: u8 = 0x9F;
 pub const CMD_READ_STATUS: u8 = 0x05;
 pub const CMD_READ_STATUS2: u8 = 0x35;
+pub const CMD_READ_STATUS3: u8 = 0x15;
+pub const CMD_WRITE_STATUS: u8 = 0x01;
+pub const CMD_WRITE_STATUS2: u8 = 0x31;
+pub const CMD_WRITE_STATUS3: u8 = 0x11;
 pub const CMD_WRITE_ENABLE: u8 = 0x06;
+pub const CMD_VOLATILE_SR_WRITE_ENABLE: u8 = 0x50;  // EWSR on SST parts
+pub const CMD_GLOBAL_BLOCK_LOCK: u8 = 0x7E;
+pub const CMD_GLOBAL_BLOCK_UNLOCK: u8 = 0x98;
 pub const CMD_WRITE_DISABLE: u8 = 0x04;
 pub const CMD_PAGE_PROGRAM: u8 = 0x02;
+pub const CMD_PAGE_PROGRAM_4B: u8 = 0x12;
 pub const CMD_READ_DATA: u8 = 0x03;
 pub const CMD_FAST_READ: u8 = 0x0B;
+pub const CMD_DUAL_OUTPUT_READ: u8 = 0x3B;
+pub const CMD_QUAD_OUTPUT_READ: u8 = 0x6B;
+pub const CMD_READ_DATA_4B: u8 = 0x13;   // 4-byte address variants, no mode switch needed
+pub const CMD_FAST_READ_4B: u8 = 0x0C;
 pub const CMD_SECTOR_ERASE: u8 = 0x20;   // 4KB
 pub const CMD_BLOCK_ERASE_32K: u8 = 0x52;
 pub const CMD_BLOCK_ERASE_64K: u8 = 0xD8;
-pub const CMD_CHIP_ERASE: u8 = 0xC7;     // or 0x60
+pub const CMD_SECTOR_ERASE_4B: u8 = 0x21;
+pub const CMD_BLOCK_ERASE_32K_4B: u8 = 0x5C;
+pub const CMD_BLOCK_ERASE_64K_4B: u8 = 0xDC;
+pub const CMD_CHIP_ERASE: u8 = 0xC7;
+pub const CMD_CHIP_ERASE_ALT: u8 = 0x60;  // Only opcode some parts accept
 pub const CMD_POWER_DOWN: u8 = 0xB9;
 pub const CMD_RELEASE_PD: u8 = 0xAB;
+pub const CMD_READ_SFDP: u8 = 0x5A;
+pub const CMD_READ_UNIQUE_ID: u8 = 0x4B;
+pub const CMD_RESET_ENABLE: u8 = 0x66;
+pub const CMD_RESET: u8 = 0x99;
+pub const CMD_READ_SECURITY_REG: u8 = 0x48;
+pub const CMD_PROGRAM_SECURITY_REG: u8 = 0x42;
+pub const CMD_ERASE_SECURITY_REG: u8 = 0x44;
+pub const CMD_READ_NV_CONFIG: u8 = 0xB5;   // Micron nonvolatile configuration register
+pub const CMD_WRITE_NV_CONFIG: u8 = 0xB1;
 
 // Status register bits
 pub const STATUS_WIP: u8 = 0x01;  // Write In Progress
 pub const STATUS_WEL: u8 = 0x02;  // Write Enable Latch
+pub const STATUS_BP_MASK: u8 = 0x1C;    // BP0-BP2
+pub const STATUS_PROTECT_MASK: u8 = 0x7C;  // BP0-BP2, TB, SEC
+pub const STATUS_SRP0: u8 = 0x80;  // Status Register Protect 0
+
+// Status register 2 bits
+pub const STATUS2_SRP1: u8 = 0x01;  // Status Register Protect 1 (lock until power cycle / OTP)
+pub const STATUS2_LB1: u8 = 0x08;  // Security register lock bits LB1-LB3 (bits 3-5)
+
+// Status register 3 bits
+pub const STATUS3_WPS: u8 = 0x04;  // Write Protect Selection (individual block locks)
+
+// Nonvolatile configuration writes take up to 3s on Micron parts
+const CONFIG_WRITE_TIMEOUT_MS: u32 = 3000;
+
+// Security (OTP) registers
+pub const SECURITY_REG_COUNT: u8 = 3;
+pub const SECURITY_REG_SIZE: usize = 256;
+
+// Batched page programming: delay before the first WIP poll (typical tPP is
+// 0.4-0.7ms) and the give-up time per page
+const PAGE_PROGRAM_POLL_DELAY: Duration = Duration::from_micros(400);
+const PAGE_PROGRAM_TIMEOUT: Duration = Duration::from_millis(10);
+
+// How long the status may read back all ones before the chip is given up on
+pub const DEFAULT_STUCK_GRACE: Duration = Duration::from_secs(2);
+
+// How often a paused operation checks whether to carry on
+const PAUSE_POLL: Duration = Duration::from_millis(50);
+
+// Largest size reachable with 3-byte addresses
+pub const MAX_3BYTE_SIZE: usize = 16 * 1024 * 1024;
+
+// Page size assumed until a chip is detected
+const DEFAULT_PAGE_SIZE: usize = 256;
+
+// Chip erase can take very long (up to 200 seconds for large chips)
+pub const CHIP_ERASE_TIMEOUT_MS: u32 = 200_000;
+
+// JEP106 continuation code preceding manufacturer IDs in banks 2 and up
+pub const JEDEC_CONTINUATION: u8 = 0x7F;
+pub const JEDEC_MAX_CONTINUATIONS: usize = 15;
+
+// JEDEC ID reads compared by robust detection
+pub const JEDEC_ROBUST_READS: usize = 3;
+
+/// JEDEC ID with the manufacturer's JEP106 bank (0 = bank 1, no continuation codes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JedecId {
+    pub bank: u8,
+    pub mfr: u8,
+    pub dev: [u8; 2],
+}
+
+impl JedecId {
+    /// Decode a raw 0x9F response, counting leading continuation codes
+    pub fn parse(resp: &[u8]) -> Option<Self> {
+        let bank = resp.iter().take_while(|&&b| b == JEDEC_CONTINUATION).count();
+        let id = resp.get(bank..bank + 3)?;
+        Some(Self {
+            bank: bank as u8,
+            mfr: id[0],
+            dev: [id[1], id[2]],
+        })
+    }
+
+    /// Manufacturer and device bytes in the 3-byte form used by the chip database
+    pub fn bytes(&self) -> [u8; 3] {
+        [self.mfr, self.dev[0], self.dev[1]]
+    }
+
+    /// Manufacturer name; only bank 1 IDs are known
+    pub fn manufacturer(&self) -> &'static str {
+        if self.bank == 0 {
+            manufacturer_name(self.mfr)
+        } else {
+            "Unknown"
+        }
+    }
+}
+
+impl std::fmt::Display for JedecId {
+    /// Hex digits including continuation codes, e.g. "EF6018" or "7F9D6018"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for _ in 0..self.bank {
+            write!(f, "{:02X}", JEDEC_CONTINUATION)?;
+        }
+        write!(f, "{:02X}{:02X}{:02X}", self.mfr, self.dev[0], self.dev[1])
+    }
+}
+
+/// Options for chip detection
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DetectOptions {
+    pub reset_first: bool,  // Software reset before reading the JEDEC ID
+    pub robust: bool,       // Read the JEDEC ID JEDEC_ROBUST_READS times, all must agree
+}
+
+/// Erase granularities, smallest first
+///
+/// `Block256K` is the 0xD8 erase of uniform 256KB-sector parts such as the
+/// S25FL512S, which have no 4KB or 64KB erase; a chip lists it or
+/// `Block64K`, never both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EraseOp {
+    Sector4K,
+    Block32K,
+    Block64K,
+    Block256K,
+}
+
+impl EraseOp {
+    pub fn size(self) -> u32 {
+        match self {
+            EraseOp::Sector4K => 4 * 1024,
+            EraseOp::Block32K => 32 * 1024,
+            EraseOp::Block64K => 64 * 1024,
+            EraseOp::Block256K => 256 * 1024,
+        }
+    }
+
+    pub fn opcode(self) -> u8 {
+        match self {
+            EraseOp::Sector4K => CMD_SECTOR_ERASE,
+            EraseOp::Block32K => CMD_BLOCK_ERASE_32K,
+            EraseOp::Block64K | EraseOp::Block256K => CMD_BLOCK_ERASE_64K,
+        }
+    }
+
+    /// Worst-case erase time in ms when the chip doesn't specify one
+    pub fn timeout_ms(self) -> u32 {
+        match self {
+            EraseOp::Sector4K => 500,     // typically 50-400ms
+            EraseOp::Block32K => 2000,    // typically 120-1600ms
+            EraseOp::Block64K => 3000,    // typically 150-2000ms
+            EraseOp::Block256K => 3000,   // typically 520-2600ms
+        }
+    }
+
+    /// Typical erase time in ms, for estimates only
+    pub fn typical_ms(self) -> u32 {
+        match self {
+            EraseOp::Sector4K => 50,
+            EraseOp::Block32K => 120,
+            EraseOp::Block64K => 150,
+            EraseOp::Block256K => 520,
+        }
+    }
+}
+
+/// Block while `pause` is set, returning whether `cancel` is set
+///
+/// Cancelling also ends a pause, so a paused operation can still be stopped.
+pub fn wait_while_paused(pause: &AtomicBool, cancel: &AtomicBool) -> bool {
+    while pause.load(Ordering::SeqCst) && !cancel.load(Ordering::SeqCst) {
+        std::thread::sleep(PAUSE_POLL);
+    }
+    cancel.load(Ordering::SeqCst)
+}
+
+/// Plan the fewest erase operations covering `[address, address + len)`
+///
+/// The range is widened to the boundaries of the smallest erase in `ops`
+/// (4KB on nearly every part). Within it, the larger blocks in `ops` are
+/// used wherever they are aligned and fit; the unaligned edges fall back to
+/// the smallest erase.
+pub fn plan_erase(address: u32, len: usize, ops: &[EraseOp]) -> Vec<(EraseOp, u32)> {
+    let unit = ops.iter().copied().min_by_key(|op| op.size()).unwrap_or(EraseOp::Sector4K);
+    let sector = unit.size();
+    let mut addr = address - address % sector;
+    let end = (address as u64 + len as u64).div_ceil(sector as u64) * sector as u64;
+    let mut plan = Vec::new();
+
+    while (addr as u64) < end {
+        let op = [EraseOp::Block256K, EraseOp::Block64K, EraseOp::Block32K]
+            .into_iter()
+            .filter(|op| ops.contains(op) && op.size() > sector)
+            .find(|op| addr.is_multiple_of(op.size()) && addr as u64 + op.size() as u64 <= end)
+            .unwrap_or(unit);
+
+        plan.push((op, addr));
+        addr += op.size();
+    }
+
+    plan
+}
+
+// Typical page program time, for estimates only
+const PAGE_PROGRAM_TYPICAL_US: u32 = 700;
+
+/// A contiguous span that a write will erase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EraseRange {
+    pub start: usize,
+    pub len: usize,
+}
+
+/// What writing `len` bytes at an offset will do, without touching the chip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WritePlan {
+    pub erase_ranges: Vec<EraseRange>,
+    pub erase_ops: usize,       // Sector and block erase commands
+    pub erase_bytes: usize,     // Includes sector padding around the data
+    pub pages: usize,           // Page program commands
+    pub write_bytes: usize,
+    pub estimated_secs: f32,    // From typical erase/program times and the SPI clock
+}
+
+/// Plan a write of `len` bytes at `offset` the way `write_range` carries it out
+pub fn plan_write(chip: &FlashChip, clock: SpiClock, offset: usize, len: usize) -> WritePlan {
+    let erase = plan_erase(offset as u32, len, &chip.erase_ops);
+
+    let mut erase_ranges: Vec<EraseRange> = Vec::new();
+    for &(op, addr) in &erase {
+        match erase_ranges.last_mut() {
+            Some(r) if r.start + r.len == addr as usize => r.len += op.size() as usize,
+            _ => erase_ranges.push(EraseRange { start: addr as usize, len: op.size() as usize }),
+        }
+    }
+
+    let pages = if len == 0 {
+        0
+    } else {
+        (offset + len).div_ceil(chip.page_size) - offset / chip.page_size
+    };
+
+    let erase_ms: u32 = erase.iter().map(|(op, _)| op.typical_ms()).sum();
+    let program_secs = pages as f32 * PAGE_PROGRAM_TYPICAL_US as f32 / 1e6;
+    let transfer_secs = len as f32 * 8.0 / clock.hz() as f32;
+
+    WritePlan {
+        erase_ops: erase.len(),
+        erase_bytes: erase_ranges.iter().map(|r| r.len).sum(),
+        erase_ranges,
+        pages,
+        write_bytes: len,
+        estimated_secs: erase_ms as f32 / 1000.0 + program_secs + transfer_secs,
+    }
+}
+
+/// Configuration register layouts, recorded per chip in the database
+///
+/// Only Spansion/Cypress, Macronix and Micron are supported; other vendors
+/// keep their equivalent bits in status registers 2 and 3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConfigRegisterKind {
+    Spansion,  // CR1 (QUAD, latency code): read 0x35, written after SR1 with 0x01
+    Macronix,  // CR (4BYTE, dummy cycles): read 0x15, written after SR with 0x01
+    Micron,    // 16-bit NVCR (address mode, dummy cycles): read 0xB5, write 0xB1
+}
+
+impl ConfigRegisterKind {
+    /// Largest value the register holds
+    pub fn max_value(self) -> u16 {
+        match self {
+            ConfigRegisterKind::Micron => 0xFFFF,
+            _ => 0xFF,
+        }
+    }
+}
+
+/// A configuration register value and the layout it was read with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigRegister {
+    pub kind: ConfigRegisterKind,
+    pub value: u16,
+}
+
+/// Contents of status registers 1-3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusRegisters {
+    pub sr1: u8,
+    pub sr2: u8,
+    pub sr3: u8,
+}
+
+/// How much of the array `lock` protects through BP0-BP2
+///
+/// Fractions are the common Winbond/GigaDevice layout with TB=0 and SEC=0,
+/// counted from the top of the array. Some parts scale the partial levels
+/// with density, so check the datasheet before relying on an exact boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockLevel {
+    UpperSixtyFourth,  // BP = 001
+    UpperThirtySecond, // BP = 010
+    UpperSixteenth,    // BP = 011
+    UpperEighth,       // BP = 100
+    UpperQuarter,      // BP = 101
+    UpperHalf,         // BP = 110
+    All,               // BP = 111
+}
+
+impl LockLevel {
+    /// BP0-BP2 as they sit in status register 1
+    pub fn bp_bits(self) -> u8 {
+        let bp = match self {
+            LockLevel::UpperSixtyFourth => 1,
+            LockLevel::UpperThirtySecond => 2,
+            LockLevel::UpperSixteenth => 3,
+            LockLevel::UpperEighth => 4,
+            LockLevel::UpperQuarter => 5,
+            LockLevel::UpperHalf => 6,
+            LockLevel::All => 7,
+        };
+        (bp << 2) & STATUS_BP_MASK
+    }
+}
+
+/// SPI read modes (data lanes used for the data phase)
+///
+/// The CH347 SPI engine samples MISO only, so dual/quad output data can't be
+/// captured; the database lists `Single` alone even for parts that support
+/// more. The other modes are kept for custom chip files and future adapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadMode {
+    Single,      // 1-1-1 fast read
+    DualOutput,  // 1-1-2
+    QuadOutput,  // 1-1-4
+}
+
+impl ReadMode {
+    /// Read opcode for this mode
+    pub fn opcode(self) -> u8 {
+        match self {
+            ReadMode::Single => CMD_FAST_READ,
+            ReadMode::DualOutput => CMD_DUAL_OUTPUT_READ,
+            ReadMode::QuadOutput => CMD_QUAD_OUTPUT_READ,
+        }
+    }
+
+    /// Dummy bytes between address and data (8 dummy clocks for all modes)
+    pub fn dummy_bytes(self) -> usize {
+        1
+    }
+}
+
+fn default_read_modes() -> Vec<ReadMode> {
+    vec![ReadMode::Single]
+}
+
+fn default_chip_erase_opcode() -> u8 {
+    CMD_CHIP_ERASE
+}
+
+fn default_sector_erase_ms() -> u32 {
+    EraseOp::Sector4K.timeout_ms()
+}
+
+fn default_block_erase_ms() -> u32 {
+    EraseOp::Block64K.timeout_ms()
+}
+
+fn default_chip_erase_ms() -> u32 {
+    CHIP_ERASE_TIMEOUT_MS
+}
+
+fn default_erase_ops() -> Vec<EraseOp> {
+    vec![EraseOp::Sector4K, EraseOp::Block32K, EraseOp::Block64K]
+}
+
+/// Digest algorithms for flash contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DigestAlgo {
+    Crc32,
+    Md5,
+    Sha256,
+}
 
 /// Flash chip information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,19 +429,137 @@ pub struct FlashChip {
     pub jedec_id: [u8; 3],
     pub size: usize,           // Total size in bytes
     pub page_size: usize,      // Page size (usually 256)
-    pub sector_size: usize,    // Sector size (usually 4096)
+    pub sector_size: usize,    // Smallest erase, usually 4096
     pub block_size: usize,     // Block size (usually 65536)
+    #[serde(default = "default_read_modes")]
+    pub read_modes: Vec<ReadMode>,  // Fast read modes usable through the CH347
+    #[serde(default)]
+    pub has_unique_id: bool,   // Supports 0x4B Read Unique ID
+    #[serde(default = "default_chip_erase_opcode")]
+    pub chip_erase_opcode: u8,  // 0xC7 or 0x60
+    #[serde(default = "default_sector_erase_ms")]
+    pub sector_erase_ms: u32,  // Worst-case 4KB sector erase time
+    #[serde(default = "default_block_erase_ms")]
+    pub block_erase_ms: u32,   // Worst-case 32KB/64KB block erase time
+    #[serde(default = "default_chip_erase_ms")]
+    pub chip_erase_ms: u32,    // Worst-case chip erase time
+    #[serde(default = "default_erase_ops")]
+    pub erase_ops: Vec<EraseOp>,  // Sector/block erases the chip implements, smallest first
+    #[serde(default)]
+    pub config_register: Option<ConfigRegisterKind>,  // Layout of the configuration register, if any
 }
 
 impl FlashChip {
     pub fn size_str(&self) -> String {
-        if self.size >= 1024 * 1024 {
-            format!("{}MB", self.size / (1024 * 1024))
-        } else if self.size >= 1024 {
-            format!("{}KB", self.size / 1024)
-        } else {
-            format!("{}B", self.size)
+        format_size(self.size)
+    }
+
+    /// Whether `[offset, offset + len)` lies within the chip
+    pub fn contains_range(&self, offset: usize, len: usize) -> bool {
+        offset.checked_add(len).is_some_and(|end| end <= self.size)
+    }
+
+    /// Index of the sector holding `addr`
+    pub fn sector_of(&self, addr: usize) -> usize {
+        addr / self.sector_size
+    }
+
+    /// Index of the block holding `addr`
+    pub fn block_of(&self, addr: usize) -> usize {
+        addr / self.block_size
+    }
+
+    /// Start of the sector holding `addr`
+    pub fn align_down_sector(&self, addr: usize) -> usize {
+        addr - addr % self.sector_size
+    }
+
+    /// Whether addresses past 16MB exist and need 4-byte address opcodes
+    pub fn uses_4byte_addr(&self) -> bool {
+        self.size > MAX_3BYTE_SIZE
+    }
+
+    /// JEDEC ID as hex digits, e.g. "EF4018"
+    pub fn jedec_hex(&self) -> String {
+        format!("{:02X}{:02X}{:02X}", self.jedec_id[0], self.jedec_id[1], self.jedec_id[2])
+    }
+
+    /// One-line description for logs and the UI, e.g.
+    /// "W25Q128 (Winbond, EF4018) 16MB, 256B page, 4KB sector"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ({}, {}) {}, {} page, {} sector",
+            self.name,
+            self.manufacturer,
+            self.jedec_hex(),
+            self.size_str(),
+            format_size(self.page_size),
+            format_size(self.sector_size)
+        )
+    }
+
+    /// Check that the geometry is usable: sizes nonzero and powers of two
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let fields = [
+            ("size", self.size),
+            ("page_size", self.page_size),
+            ("sector_size", self.sector_size),
+            ("block_size", self.block_size),
+        ];
+
+        // DataFlash-style 264/528-byte pages put each page at a power-of-two
+        // stride with the extra bytes beyond it, and the parts use their own
+        // buffer/page opcodes rather than 0x02/0x20, so they can't be driven
+        // as linear NOR flash
+        if self.page_size > 0 && self.page_size.is_multiple_of(264) {
+            return Err(format!(
+                "{}: {}-byte pages are not supported; set the part to binary (power of two) page size",
+                self.name, self.page_size
+            ));
+        }
+
+        for (field, value) in fields {
+            if !value.is_power_of_two() {
+                return Err(format!("{}: {} must be a nonzero power of two (got {})", self.name, field, value));
+            }
+        }
+
+        if self.page_size > self.sector_size || self.sector_size > self.block_size {
+            return Err(format!("{}: expected page_size <= sector_size <= block_size", self.name));
         }
+
+        let smallest = self.erase_ops.iter().map(|op| op.size() as usize).min();
+        if smallest != Some(self.sector_size) {
+            return Err(format!("{}: the smallest of erase_ops must erase sector_size bytes", self.name));
+        }
+
+        // Both are opcode 0xD8
+        if self.erase_ops.contains(&EraseOp::Block64K) && self.erase_ops.contains(&EraseOp::Block256K) {
+            return Err(format!("{}: erase_ops can't hold both Block64K and Block256K", self.name));
+        }
+
+        if ![CMD_CHIP_ERASE, CMD_CHIP_ERASE_ALT].contains(&self.chip_erase_opcode) {
+            return Err(format!("{}: chip_erase_opcode must be 0xC7 or 0x60 (got 0x{:02X})", self.name, self.chip_erase_opcode));
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for FlashChip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.summary())
+    }
+}
+
+/// Size with the largest whole unit, e.g. "16MB", "4KB" or "256B"
+fn format_size(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{}MB", bytes / (1024 * 1024))
+    } else if bytes >= 1024 {
+        format!("{}KB", bytes / 1024)
+    } else {
+        format!("{}B", bytes)
     }
 }
 
@@ -61,6 +575,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "W25Q32".into(),
@@ -70,6 +592,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "W25Q64".into(),
@@ -79,6 +609,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "W25Q128".into(),
@@ -88,6 +626,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "W25Q256".into(),
@@ -97,6 +643,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: 400_000,  // tCE max 400s
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         // GigaDevice
         FlashChip {
@@ -107,6 +661,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "GD25Q32".into(),
@@ -116,6 +678,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "GD25Q64".into(),
@@ -125,6 +695,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "GD25Q128".into(),
@@ -134,6 +712,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         // Macronix
         FlashChip {
@@ -144,6 +730,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         FlashChip {
             name: "MX25L12835F".into(),
@@ -153,6 +747,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: Some(ConfigRegisterKind::Macronix),
         },
         FlashChip {
             name: "MX25L25635F".into(),
@@ -162,6 +764,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: Some(ConfigRegisterKind::Macronix),
         },
         // Spansion/Cypress
         FlashChip {
@@ -172,6 +782,32 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: Some(ConfigRegisterKind::Spansion),
+        },
+        FlashChip {
+            // 512-byte page buffer, uniform 256KB sectors and no 4KB erase
+            name: "S25FL512S".into(),
+            manufacturer: "Spansion".into(),
+            jedec_id: [0x01, 0x02, 0x20],
+            size: 64 * 1024 * 1024,
+            page_size: 512,
+            sector_size: 256 * 1024,
+            block_size: 256 * 1024,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: EraseOp::Block256K.timeout_ms(),
+            block_erase_ms: EraseOp::Block256K.timeout_ms(),
+            chip_erase_ms: 360_000,
+            erase_ops: vec![EraseOp::Block256K],
+            config_register: Some(ConfigRegisterKind::Spansion),
         },
         // ISSI
         FlashChip {
@@ -182,6 +818,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         // XMC
         FlashChip {
@@ -192,6 +836,14 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: true,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
         // ESMT
         FlashChip {
@@ -202,21 +854,133 @@ pub fn get_flash_database() -> Vec<FlashChip> {
             page_size: 256,
             sector_size: 4096,
             block_size: 65536,
+            read_modes: vec![ReadMode::Single],
+            has_unique_id: false,
+            chip_erase_opcode: CMD_CHIP_ERASE,
+            sector_erase_ms: default_sector_erase_ms(),
+            block_erase_ms: default_block_erase_ms(),
+            chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+            erase_ops: default_erase_ops(),
+            config_register: None,
         },
     ]
 }
 
+/// Manufacturer name for a JEDEC bank 0 manufacturer ID
+pub fn manufacturer_name(id: u8) -> &'static str {
+    match id {
+        0x01 => "Spansion",
+        0x04 => "Fujitsu",
+        0x0B => "XTX",
+        0x1C => "Eon",
+        0x1F => "Atmel",
+        0x20 => "Micron",
+        0x37 => "AMIC",
+        0x5E => "Zbit",
+        0x62 => "Sanyo",
+        0x68 => "Boya",
+        0x85 => "Puya",
+        0x89 => "Intel",
+        0x8C => "ESMT",
+        0x9D => "ISSI",
+        0xA1 => "Fudan",
+        0xBF => "SST",
+        0xC2 => "Macronix",
+        0xC8 => "GigaDevice",
+        0xEF => "Winbond",
+        _ => "Unknown",
+    }
+}
+
 /// Identify chip by JEDEC ID
-pub fn identify_chip(jedec_id: &[u8; 3]) -> Option<FlashChip> {
-    get_flash_database()
-        .into_iter()
+pub fn identify_chip(database: &[FlashChip], jedec_id: &[u8; 3]) -> Option<FlashChip> {
+    database
+        .iter()
         .find(|chip| &chip.jedec_id == jedec_id)
+        .cloned()
+}
+
+/// Names of database chips resembling `name`, for "did you mean" hints
+///
+/// Matches are case-insensitive substrings in either direction or names
+/// within two edits of the query.
+pub fn similar_chip_names(database: &[FlashChip], name: &str) -> Vec<String> {
+    const MAX_SUGGESTIONS: usize = 5;
+    let wanted = name.trim().to_ascii_lowercase();
+
+    database
+        .iter()
+        .filter(|chip| {
+            let candidate = chip.name.to_ascii_lowercase();
+            candidate.contains(&wanted)
+                || (!candidate.is_empty() && wanted.contains(&candidate))
+                || edit_distance(&candidate, &wanted) <= 2
+        })
+        .map(|chip| chip.name.clone())
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// Chips whose name, manufacturer or JEDEC ID hex contains `query`, ignoring case
+///
+/// An empty query matches everything.
+pub fn search_chips(database: &[FlashChip], query: &str) -> Vec<FlashChip> {
+    let wanted = query.trim().to_ascii_lowercase();
+
+    database
+        .iter()
+        .filter(|chip| {
+            chip.name.to_ascii_lowercase().contains(&wanted)
+                || chip.manufacturer.to_ascii_lowercase().contains(&wanted)
+                || chip.jedec_hex().to_ascii_lowercase().contains(&wanted)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Chips from the named manufacturer, ignoring case
+pub fn chips_by_manufacturer(database: &[FlashChip], name: &str) -> Vec<FlashChip> {
+    let wanted = name.trim();
+
+    database
+        .iter()
+        .filter(|chip| chip.manufacturer.eq_ignore_ascii_case(wanted))
+        .cloned()
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+/// Merge user chip definitions over a base database
+///
+/// A custom entry replaces any base entry with the same JEDEC ID.
+pub fn merge_chip_database(base: Vec<FlashChip>, custom: Vec<FlashChip>) -> Vec<FlashChip> {
+    let mut merged: Vec<FlashChip> = base
+        .into_iter()
+        .filter(|chip| !custom.iter().any(|c| c.jedec_id == chip.jedec_id))
+        .collect();
+    merged.extend(custom);
+    merged
 }
 
 /// Create unknown chip info
-pub fn unknown_chip(jedec_id: [u8; 3]) -> FlashChip {
+pub fn unknown_chip(id: JedecId) -> FlashChip {
     // Try to guess size from third byte
-    let size = match jedec_id[2] {
+    let size = match id.dev[1] {
         0x14 => 1 * 1024 * 1024,    // 1MB / 8Mbit
         0x15 => 2 * 1024 * 1024,    // 2MB / 16Mbit
         0x16 => 4 * 1024 * 1024,    // 4MB / 32Mbit
@@ -229,243 +993,1265 @@ pub fn unknown_chip(jedec_id: [u8; 3]) -> FlashChip {
         _ => 16 * 1024 * 1024,      // Default 16MB
     };
 
+    let manufacturer = id.manufacturer();
+    let name = format!("Unknown ({})", id);
+
     FlashChip {
-        name: format!("Unknown ({:02X}{:02X}{:02X})", jedec_id[0], jedec_id[1], jedec_id[2]),
-        manufacturer: "Unknown".into(),
-        jedec_id,
+        name: match manufacturer {
+            "Unknown" => name,
+            mfr => format!("{} {}", mfr, name),
+        },
+        manufacturer: manufacturer.into(),
+        jedec_id: id.bytes(),
         size,
         page_size: 256,
         sector_size: 4096,
         block_size: 65536,
+        read_modes: default_read_modes(),
+        has_unique_id: false,
+        chip_erase_opcode: CMD_CHIP_ERASE,
+        sector_erase_ms: default_sector_erase_ms(),
+        block_erase_ms: default_block_erase_ms(),
+        chip_erase_ms: CHIP_ERASE_TIMEOUT_MS,
+        erase_ops: default_erase_ops(),
+        config_register: None,
+    }
+}
+
+/// Create chip info from SFDP parameters
+pub fn sfdp_chip(id: JedecId, params: &FlashParams) -> FlashChip {
+    let sector = params.erase_types.first();
+    let block = params.erase_types.last();
+    let sector_size = sector.map(|e| e.size).unwrap_or(4096);
+    let block_size = block.map(|e| e.size).unwrap_or(65536);
+
+    FlashChip {
+        name: format!("SFDP ({})", id),
+        manufacturer: id.manufacturer().into(),
+        jedec_id: id.bytes(),
+        size: params.size,
+        page_size: params.page_size,
+        sector_size,
+        block_size,
+        read_modes: default_read_modes(),
+        has_unique_id: false,
+        chip_erase_opcode: CMD_CHIP_ERASE,
+        sector_erase_ms: sector.and_then(|e| e.max_ms).unwrap_or_else(default_sector_erase_ms),
+        block_erase_ms: block.and_then(|e| e.max_ms).unwrap_or_else(default_block_erase_ms),
+        chip_erase_ms: params.chip_erase_ms.unwrap_or(CHIP_ERASE_TIMEOUT_MS),
+        erase_ops: sfdp_erase_ops(params),
+        config_register: None,
+    }
+}
+
+/// Erase operations the SFDP erase-type table advertises
+///
+/// Sector erase is always kept since every write path works in 4KB sectors;
+/// 32KB and 64KB blocks are only used if listed with their standard opcodes.
+/// Without an erase table, all three are assumed as for database parts.
+fn sfdp_erase_ops(params: &FlashParams) -> Vec<EraseOp> {
+    if params.erase_types.is_empty() {
+        return default_erase_ops();
+    }
+
+    let mut ops = vec![EraseOp::Sector4K];
+    for op in [EraseOp::Block32K, EraseOp::Block64K] {
+        let listed = params
+            .erase_types
+            .iter()
+            .any(|e| e.size == op.size() as usize && e.opcode == op.opcode());
+        if listed {
+            ops.push(op);
+        }
+    }
+    ops
+}
+
+/// Opcode and address bytes of a read, page program or erase
+///
+/// With `four_byte` the 4-byte address variants (0x13/0x0C reads, 0x12
+/// program, 0x21/0x5C/0xDC erases) take a 32-bit address directly, avoiding
+/// the stateful enter-4-byte-mode switch. Opcodes without a variant here
+/// keep a 24-bit address.
+fn address_command(opcode: u8, address: u32, four_byte: bool) -> Vec<u8> {
+    let (opcode, address_bytes) = match (four_byte, opcode) {
+        (true, CMD_READ_DATA) => (CMD_READ_DATA_4B, 4),
+        (true, CMD_FAST_READ) => (CMD_FAST_READ_4B, 4),
+        (true, CMD_PAGE_PROGRAM) => (CMD_PAGE_PROGRAM_4B, 4),
+        (true, CMD_SECTOR_ERASE) => (CMD_SECTOR_ERASE_4B, 4),
+        (true, CMD_BLOCK_ERASE_32K) => (CMD_BLOCK_ERASE_32K_4B, 4),
+        (true, CMD_BLOCK_ERASE_64K) => (CMD_BLOCK_ERASE_64K_4B, 4),
+        _ => (opcode, 3),
+    };
+
+    let mut cmd = vec![opcode];
+    cmd.extend_from_slice(&address.to_be_bytes()[4 - address_bytes..]);
+    cmd
+}
+
+/// SPI transport a `FlashProgrammer` drives
+///
+/// Implemented by the CH347 and by `MockFlash`, so flash logic can run
+/// without hardware.
+pub trait SpiBackend {
+    /// Configure the SPI clock, mode and bit order
+    fn spi_init_mode(&mut self, clock: SpiClock, mode: SpiMode, bit_order: BitOrder) -> Result<()>;
+
+    /// Assert or release a chip select line
+    fn spi_cs(&mut self, cs_line: u8, assert: bool) -> Result<()>;
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<()>;
+
+    fn spi_read(&mut self, data: &mut [u8]) -> Result<()>;
+
+    fn gpio_set(&mut self, pin: u8, level: bool) -> Result<()>;
+}
+
+impl SpiBackend for Ch347Device {
+    fn spi_init_mode(&mut self, clock: SpiClock, mode: SpiMode, bit_order: BitOrder) -> Result<()> {
+        Ch347Device::spi_init_mode(self, clock, mode, bit_order)
+    }
+
+    fn spi_cs(&mut self, cs_line: u8, assert: bool) -> Result<()> {
+        Ch347Device::spi_cs(self, cs_line, assert)
+    }
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+        Ch347Device::spi_write(self, data)
+    }
+
+    fn spi_read(&mut self, data: &mut [u8]) -> Result<()> {
+        Ch347Device::spi_read(self, data)
+    }
+
+    fn gpio_set(&mut self, pin: u8, level: bool) -> Result<()> {
+        Ch347Device::gpio_set(self, pin, level)
     }
 }
 
 /// SPI Flash Programmer
-pub struct FlashProgrammer {
-    device: Ch347Device,
+pub struct FlashProgrammer<B: SpiBackend = Ch347Device> {
+    device: B,
     chip: Option<FlashChip>,
+    clock: SpiClock,
+    spi_mode: SpiMode,
+    bit_order: BitOrder,
+    cancel: Arc<AtomicBool>,
+    pause: Arc<AtomicBool>,
+    chip_select: u8,  // CS line the flash is on (1 or 2)
+    wp_gpio: Option<u8>,  // CH347 GPIO wired to the flash WP# pin
+    stuck_grace: Duration,
+    strict_erase_check: bool,  // Read each page before programming it
 }
 
 impl FlashProgrammer {
     /// Create new programmer
     pub fn new() -> Result<Self> {
-        let mut device = Ch347Device::open()?;
-
-        // Initialize SPI with 15MHz clock (default, safe for most chips)
-        device.spi_init(SpiClock::Clk15MHz)?;
-
-        Ok(Self {
-            device,
-            chip: None,
-        })
+        Self::with_device(Ch347Device::open()?)
     }
 
-    /// Detect and identify flash chip
-    pub fn detect(&mut self) -> Result<FlashChip> {
-        let jedec_id = self.read_jedec_id()?;
+    /// Create a programmer on the CH347 with the given USB serial number
+    pub fn open_by_serial(serial: &str) -> Result<Self> {
+        Self::with_device(Ch347Device::open_by_serial(serial)?)
+    }
 
-        let chip = identify_chip(&jedec_id)
-            .unwrap_or_else(|| unknown_chip(jedec_id));
+    /// Create a programmer claiming a nonstandard SPI interface number
+    pub fn open_with_interface(serial: Option<&str>, interface: u8) -> Result<Self> {
+        Self::with_device(Ch347Device::open_with_interface(serial, interface)?)
+    }
 
-        self.chip = Some(chip.clone());
-        Ok(chip)
+    /// Create a programmer on the CH347 at a USB bus and address
+    pub fn open_at(bus: u8, address: u8) -> Result<Self> {
+        Self::with_device(Ch347Device::open_at(bus, address)?)
     }
 
-    /// Read JEDEC ID
-    pub fn read_jedec_id(&mut self) -> Result<[u8; 3]> {
-        self.device.spi_cs(true)?;
+    fn with_device(mut device: Ch347Device) -> Result<Self> {
+        // Initialize SPI with 15MHz clock (default, safe for most chips)
+        device.spi_init(SpiClock::default())?;
 
-        let cmd = [CMD_READ_JEDEC_ID];
-        let mut resp = [0u8; 3];
+        Ok(Self::with_backend(device))
+    }
 
-        self.device.spi_write(&cmd)?;
-        self.device.spi_read(&mut resp)?;
+    /// Access the underlying CH347 device
+    pub fn device(&self) -> &Ch347Device {
+        &self.device
+    }
 
-        self.device.spi_cs(false)?;
+    /// Access the underlying CH347 device for transport settings
+    pub fn device_mut(&mut self) -> &mut Ch347Device {
+        &mut self.device
+    }
+}
 
-        // Validate - shouldn't be all 0xFF or 0x00
-        if (resp[0] == 0xFF && resp[1] == 0xFF && resp[2] == 0xFF) ||
-           (resp[0] == 0x00 && resp[1] == 0x00 && resp[2] == 0x00) {
-            return Err(Ch347Error::DeviceNotFound);
+impl<B: SpiBackend> FlashProgrammer<B> {
+    /// Create a programmer on a backend already set to the default SPI settings
+    pub fn with_backend(device: B) -> Self {
+        Self {
+            device,
+            chip: None,
+            clock: SpiClock::default(),
+            spi_mode: SpiMode::default(),
+            bit_order: BitOrder::default(),
+            cancel: Arc::new(AtomicBool::new(false)),
+            pause: Arc::new(AtomicBool::new(false)),
+            chip_select: 1,
+            wp_gpio: None,
+            stuck_grace: DEFAULT_STUCK_GRACE,
+            strict_erase_check: false,
         }
+    }
 
-        Ok(resp)
+    /// Set how long `wait_ready` tolerates a status of 0xFF before failing
+    ///
+    /// A floating or pulled-up MISO reads as all ones, which looks like a
+    /// write in progress forever; a real status register never has every
+    /// bit set while busy.
+    pub fn set_stuck_grace(&mut self, grace: Duration) {
+        self.stuck_grace = grace;
     }
 
-    /// Read status register
-    pub fn read_status(&mut self) -> Result<u8> {
-        self.device.spi_cs(true)?;
+    /// Refuse page programs over bytes that would need bits set back to 1
+    pub fn set_strict_erase_check(&mut self, enabled: bool) {
+        self.strict_erase_check = enabled;
+    }
 
-        let cmd = [CMD_READ_STATUS];
-        let mut status = [0u8; 1];
+    /// Share a cancel flag so long waits can be interrupted from outside
+    pub fn set_cancel_flag(&mut self, cancel: Arc<AtomicBool>) {
+        self.cancel = cancel;
+    }
 
-        self.device.spi_write(&cmd)?;
-        self.device.spi_read(&mut status)?;
+    /// Share a pause flag checked between pages and chunks of reads and writes
+    pub fn set_pause_flag(&mut self, pause: Arc<AtomicBool>) {
+        self.pause = pause;
+    }
 
-        self.device.spi_cs(false)?;
+    /// Hold here while paused, then fail if the operation was cancelled
+    fn checkpoint(&self) -> Result<()> {
+        if wait_while_paused(&self.pause, &self.cancel) {
+            return Err(Ch347Error::Cancelled);
+        }
+        Ok(())
+    }
 
-        Ok(status[0])
+    /// Access the SPI backend, e.g. to inspect a `MockFlash`
+    pub fn backend(&self) -> &B {
+        &self.device
     }
 
-    /// Wait for write to complete
-    pub fn wait_ready(&mut self, timeout_ms: u32) -> Result<()> {
-        let start = std::time::Instant::now();
-        let timeout = std::time::Duration::from_millis(timeout_ms as u64);
+    /// Current SPI clock
+    pub fn clock(&self) -> SpiClock {
+        self.clock
+    }
 
-        loop {
-            let status = self.read_status()?;
-            if (status & STATUS_WIP) == 0 {
-                return Ok(());
-            }
+    /// Change the SPI clock, re-running the CH347 SPI configuration
+    pub fn set_clock(&mut self, clock: SpiClock) -> Result<()> {
+        self.configure_spi(clock, self.spi_mode)
+    }
 
-            if start.elapsed() > timeout {
-                return Err(Ch347Error::TransferFailed("Timeout waiting for ready".into()));
-            }
+    /// Change the SPI clock and mode with a single re-initialization
+    pub fn configure_spi(&mut self, clock: SpiClock, mode: SpiMode) -> Result<()> {
+        self.device.spi_init_mode(clock, mode, self.bit_order)?;
+        self.clock = clock;
+        self.spi_mode = mode;
+        Ok(())
+    }
 
-            std::thread::sleep(std::time::Duration::from_millis(1));
+    /// SPI clock, mode and bit order currently requested
+    pub fn spi_settings(&self) -> SpiSettings {
+        SpiSettings {
+            clock: self.clock,
+            mode: self.spi_mode,
+            bit_order: self.bit_order,
         }
     }
 
-    /// Enable write
-    pub fn write_enable(&mut self) -> Result<()> {
-        self.device.spi_cs(true)?;
-        self.device.spi_write(&[CMD_WRITE_ENABLE])?;
-        self.device.spi_cs(false)?;
+    /// Change the SPI mode, keeping the current clock
+    pub fn set_spi_mode(&mut self, mode: SpiMode) -> Result<()> {
+        self.configure_spi(self.clock, mode)
+    }
+
+    /// Change the SPI bit order, keeping the current clock and mode
+    ///
+    /// Flash commands assume MSB first; LSB first is for other SPI devices.
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) -> Result<()> {
+        self.device.spi_init_mode(self.clock, self.spi_mode, bit_order)?;
+        self.bit_order = bit_order;
+        Ok(())
+    }
+
+    /// Route the flash WP# and HOLD# pins through CH347 GPIOs
+    ///
+    /// HOLD# is driven high (inactive) straight away. WP# is released by
+    /// `unlock()` and asserted again by `lock_all()`; without a WP GPIO both
+    /// leave the pin alone.
+    pub fn set_control_pins(&mut self, wp_gpio: Option<u8>, hold_gpio: Option<u8>) -> Result<()> {
+        if let Some(pin) = wp_gpio.filter(|&pin| pin >= GPIO_COUNT) {
+            return Err(Ch347Error::Unsupported(format!("GPIO{} (pins 0-{})", pin, GPIO_COUNT - 1)));
+        }
+
+        if let Some(pin) = hold_gpio {
+            self.device.gpio_set(pin, true)?;
+        }
+
+        self.wp_gpio = wp_gpio;
+        Ok(())
+    }
+
+    /// Drive WP# low (`asserted`) or high, if it is wired to a GPIO
+    fn set_wp(&mut self, asserted: bool) -> Result<()> {
+        match self.wp_gpio {
+            Some(pin) => self.device.gpio_set(pin, !asserted),
+            None => Ok(()),
+        }
+    }
+
+    /// CS line the flash is on (1 or 2)
+    pub fn chip_select(&self) -> u8 {
+        self.chip_select
+    }
+
+    /// Operate on the flash wired to CS1 or CS2
+    ///
+    /// The previously detected chip is forgotten; run detection again.
+    pub fn set_chip_select(&mut self, line: u8) -> Result<()> {
+        if line != 1 && line != 2 {
+            return Err(Ch347Error::Unsupported(format!("chip select CS{}", line)));
+        }
+
+        self.chip_select = line;
+        self.chip = None;
+        Ok(())
+    }
+
+    /// Detect and identify flash chip
+    pub fn detect(&mut self, database: &[FlashChip], options: DetectOptions) -> Result<FlashChip> {
+        // Recovers chips left in continuous-read or other odd modes
+        if options.reset_first {
+            self.reset()?;
+        }
+
+        let id = if options.robust {
+            self.read_jedec_id_stable(JEDEC_ROBUST_READS)?
+        } else {
+            self.read_jedec_id()?
+        };
+
+        // Database entries are all bank 1 parts
+        let known = if id.bank == 0 {
+            identify_chip(database, &id.bytes())
+        } else {
+            None
+        };
+
+        // Database first, then SFDP, then a guess from the ID
+        let chip = match known {
+            Some(chip) => chip,
+            None => self
+                .read_sfdp()
+                .ok()
+                .and_then(|table| sfdp::parse_sfdp(&table))
+                .map(|params| sfdp_chip(id, &params))
+                .unwrap_or_else(|| unknown_chip(id)),
+        };
+
+        self.chip = Some(chip.clone());
+        Ok(chip)
+    }
+
+    /// Software reset (0x66 reset enable, then 0x99 reset)
+    ///
+    /// Each opcode needs its own CS cycle. The chip ignores commands for
+    /// tRST afterwards (30us on Winbond, longer if an erase was interrupted),
+    /// so wait 1ms before talking to it again.
+    pub fn reset(&mut self) -> Result<()> {
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[CMD_RESET_ENABLE])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[CMD_RESET])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        std::thread::sleep(std::time::Duration::from_millis(1));
+
+        Ok(())
+    }
+
+    /// Get the bus back to a known state after a wedged operation
+    ///
+    /// Releases chip select, re-applies the SPI settings and software-resets
+    /// the flash, then reads the JEDEC ID to show the chip answers again.
+    pub fn recover(&mut self) -> Result<JedecId> {
+        self.device.spi_cs(self.chip_select, false)?;
+        self.device.spi_init_mode(self.clock, self.spi_mode, self.bit_order)?;
+        self.reset()?;
+        self.read_jedec_id()
+    }
+
+    /// Enter deep power-down (0xB9); only 0xAB and reset are accepted afterwards
+    pub fn power_down(&mut self) -> Result<()> {
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[CMD_POWER_DOWN])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // tDP: 3us before the chip is actually in power-down
+        std::thread::sleep(std::time::Duration::from_micros(3));
+
+        Ok(())
+    }
+
+    /// Release from deep power-down (0xAB) and return the electronic signature
+    pub fn release_power_down(&mut self) -> Result<u8> {
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // Opcode followed by 3 dummy bytes, then the signature byte
+        let cmd = [CMD_RELEASE_PD, 0, 0, 0];
+        let mut signature = [0u8; 1];
+
+        self.device.spi_write(&cmd)?;
+        self.device.spi_read(&mut signature)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // tRES2: about 3us before other commands are accepted
+        std::thread::sleep(std::time::Duration::from_micros(3));
+
+        Ok(signature[0])
+    }
+
+    /// Read the legacy one-byte electronic signature (0xAB)
+    ///
+    /// Clones often copy the JEDEC ID but not this byte, so comparing the two
+    /// helps spot counterfeit parts. An all-zero reply means nothing drove MISO.
+    pub fn read_electronic_signature(&mut self) -> Result<u8> {
+        match self.release_power_down()? {
+            0x00 => Err(Ch347Error::MisoStuckLow),
+            signature => Ok(signature),
+        }
+    }
+
+    /// Read the JEDEC ID `reads` times and fail unless every read agrees
+    ///
+    /// Marginal wiring can corrupt a single read, which would otherwise
+    /// identify the wrong chip.
+    pub fn read_jedec_id_stable(&mut self, reads: usize) -> Result<JedecId> {
+        let first = self.read_jedec_id()?;
+
+        for _ in 1..reads {
+            let id = self.read_jedec_id()?;
+            if id != first {
+                return Err(Ch347Error::JedecUnstable(first.to_string(), id.to_string()));
+            }
+        }
+
+        Ok(first)
+    }
+
+    /// Read JEDEC ID
+    ///
+    /// Manufacturers outside JEP106 bank 1 prefix their ID with 0x7F
+    /// continuation codes; when present, the extra bytes are read in the same
+    /// transaction.
+    pub fn read_jedec_id(&mut self) -> Result<JedecId> {
+        self.device.spi_cs(self.chip_select, true)?;
+
+        let cmd = [CMD_READ_JEDEC_ID];
+        let mut resp = vec![0u8; 3];
+
+        self.device.spi_write(&cmd)?;
+        self.device.spi_read(&mut resp)?;
+
+        if resp[0] == JEDEC_CONTINUATION {
+            let mut more = [0u8; JEDEC_MAX_CONTINUATIONS];
+            self.device.spi_read(&mut more)?;
+            resp.extend_from_slice(&more);
+        }
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // Validate - shouldn't be all 0xFF or 0x00
+        if (resp[0] == 0xFF && resp[1] == 0xFF && resp[2] == 0xFF) ||
+           (resp[0] == 0x00 && resp[1] == 0x00 && resp[2] == 0x00) {
+            return Err(Ch347Error::DeviceNotFound);
+        }
+
+        JedecId::parse(&resp).ok_or(Ch347Error::InvalidResponse)
+    }
+
+    /// Read raw SFDP bytes starting at the given SFDP address
+    fn read_sfdp_bytes(&mut self, address: u32, data: &mut [u8]) -> Result<()> {
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // 24-bit address followed by one dummy byte
+        let cmd = [
+            CMD_READ_SFDP,
+            ((address >> 16) & 0xFF) as u8,
+            ((address >> 8) & 0xFF) as u8,
+            (address & 0xFF) as u8,
+            0,
+        ];
+        self.device.spi_write(&cmd)?;
+        self.device.spi_read(data)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        Ok(())
+    }
+
+    /// Read the SFDP region (header, parameter headers and all tables)
+    pub fn read_sfdp(&mut self) -> Result<Vec<u8>> {
+        let mut header = [0u8; sfdp::HEADER_LEN];
+        self.read_sfdp_bytes(0, &mut header)?;
+
+        if u32::from_le_bytes([header[0], header[1], header[2], header[3]]) != sfdp::SFDP_SIGNATURE {
+            return Err(Ch347Error::Unsupported("No SFDP".into()));
+        }
+
+        let count = header[6] as usize + 1;
+        let mut table = vec![0u8; sfdp::HEADER_LEN + count * sfdp::PARAM_HEADER_LEN];
+        self.read_sfdp_bytes(0, &mut table)?;
+
+        let headers = sfdp::parse_headers(&table).ok_or(Ch347Error::InvalidResponse)?;
+        table.resize(sfdp::table_extent(&headers), 0);
+        self.read_sfdp_bytes(0, &mut table)?;
+
+        Ok(table)
+    }
+
+    /// Read the 64-bit factory unique ID (0x4B)
+    pub fn read_unique_id(&mut self) -> Result<[u8; 8]> {
+        if let Some(chip) = &self.chip {
+            if !chip.has_unique_id {
+                return Err(Ch347Error::Unsupported(format!("{} has no unique ID", chip.name)));
+            }
+        }
+
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // Opcode followed by 4 dummy bytes
+        let cmd = [CMD_READ_UNIQUE_ID, 0, 0, 0, 0];
+        let mut id = [0u8; 8];
+
+        self.device.spi_write(&cmd)?;
+        self.device.spi_read(&mut id)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // A chip ignoring the opcode leaves the bus idle
+        if id.iter().all(|&b| b == 0xFF) || id.iter().all(|&b| b == 0x00) {
+            return Err(Ch347Error::Unsupported("Chip did not return a unique ID".into()));
+        }
+
+        Ok(id)
+    }
+
+    /// Read status register
+    pub fn read_status(&mut self) -> Result<u8> {
+        self.read_status_reg(CMD_READ_STATUS)
+    }
+
+    /// Read status register 2
+    pub fn read_status2(&mut self) -> Result<u8> {
+        self.read_status_reg(CMD_READ_STATUS2)
+    }
+
+    /// Read status register 3
+    pub fn read_status3(&mut self) -> Result<u8> {
+        self.read_status_reg(CMD_READ_STATUS3)
+    }
+
+    /// Read all three status registers
+    pub fn read_status_registers(&mut self) -> Result<StatusRegisters> {
+        Ok(StatusRegisters {
+            sr1: self.read_status()?,
+            sr2: self.read_status2()?,
+            sr3: self.read_status3()?,
+        })
+    }
+
+    fn read_status_reg(&mut self, opcode: u8) -> Result<u8> {
+        self.device.spi_cs(self.chip_select, true)?;
+
+        let cmd = [opcode];
+        let mut status = [0u8; 1];
+
+        self.device.spi_write(&cmd)?;
+        self.device.spi_read(&mut status)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        Ok(status[0])
+    }
+
+    /// Write status register 1, 2 or 3
+    ///
+    /// SR1 is written with the 0x01 two-byte form (SR1 + current SR2) so parts
+    /// that clear SR2 on a one-byte write keep their QE bit. SR2 and SR3 use
+    /// the dedicated 0x31/0x11 single-register writes.
+    ///
+    /// With `volatile` the 0x50 write enable (SST EWSR) is used instead of
+    /// 0x06, so on parts with volatile status bits the change only lasts
+    /// until the next power cycle.
+    pub fn write_status(&mut self, reg: u8, value: u8, volatile: bool) -> Result<()> {
+        let cmd = match reg {
+            1 => vec![CMD_WRITE_STATUS, value, self.read_status2()?],
+            2 => vec![CMD_WRITE_STATUS2, value],
+            3 => vec![CMD_WRITE_STATUS3, value],
+            _ => return Err(Ch347Error::TransferFailed(format!("Invalid status register {}", reg))),
+        };
+
+        if volatile {
+            // 0x50 does not set WEL, so it can't be checked like write_enable
+            self.device.spi_cs(self.chip_select, true)?;
+            self.device.spi_write(&[CMD_VOLATILE_SR_WRITE_ENABLE])?;
+            self.device.spi_cs(self.chip_select, false)?;
+        } else {
+            self.write_enable()?;
+        }
+
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&cmd)?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // Non-volatile status writes take up to 15ms
+        self.wait_ready(50)?;
+
+        Ok(())
+    }
+
+    /// Configuration register layout of the detected chip
+    fn config_register_kind(&self) -> Result<ConfigRegisterKind> {
+        let chip = self.chip.as_ref().ok_or_else(|| Ch347Error::Unsupported("no chip detected".into()))?;
+        chip.config_register.ok_or_else(|| {
+            Ch347Error::Unsupported(format!("{} has no known configuration register", chip.name))
+        })
+    }
+
+    /// Read the configuration register (Spansion, Macronix or Micron parts)
+    pub fn read_config_register(&mut self) -> Result<ConfigRegister> {
+        let kind = self.config_register_kind()?;
+
+        let value = match kind {
+            ConfigRegisterKind::Spansion => self.read_status_reg(CMD_READ_STATUS2)? as u16,
+            ConfigRegisterKind::Macronix => self.read_status_reg(CMD_READ_STATUS3)? as u16,
+            ConfigRegisterKind::Micron => {
+                let mut nvcr = [0u8; 2];
+                self.device.spi_cs(self.chip_select, true)?;
+                self.device.spi_write(&[CMD_READ_NV_CONFIG])?;
+                self.device.spi_read(&mut nvcr)?;
+                self.device.spi_cs(self.chip_select, false)?;
+                u16::from_le_bytes(nvcr)
+            }
+        };
+
+        Ok(ConfigRegister { kind, value })
+    }
+
+    /// Write the configuration register (Spansion, Macronix or Micron parts)
+    ///
+    /// Spansion and Macronix take it as the second byte of a 0x01 status
+    /// write, so SR1 is read first and written back unchanged. Micron's NVCR
+    /// is sent least significant byte first and applies from the next power
+    /// cycle.
+    pub fn write_config_register(&mut self, value: u16) -> Result<()> {
+        let kind = self.config_register_kind()?;
+        if value > kind.max_value() {
+            return Err(Ch347Error::Unsupported(format!(
+                "{:?} configuration register takes at most 0x{:X} (got 0x{:X})",
+                kind,
+                kind.max_value(),
+                value
+            )));
+        }
+
+        let cmd = match kind {
+            ConfigRegisterKind::Spansion | ConfigRegisterKind::Macronix => {
+                vec![CMD_WRITE_STATUS, self.read_status()?, value as u8]
+            }
+            ConfigRegisterKind::Micron => {
+                let [lo, hi] = value.to_le_bytes();
+                vec![CMD_WRITE_NV_CONFIG, lo, hi]
+            }
+        };
+
+        self.write_enable()?;
+
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&cmd)?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        self.wait_ready(CONFIG_WRITE_TIMEOUT_MS)?;
+
+        Ok(())
+    }
+
+    /// Clear the block protection bits (BP0-BP2, TB, SEC)
+    ///
+    /// Falls back to the 0x50 write enable for parts that ignore 0x06 before
+    /// WRSR, and reports SRP/WP# protection instead of failing silently.
+    /// With `volatile` only 0x50 is used, so the protection comes back at the
+    /// next power cycle and the non-volatile register is not rewritten.
+    pub fn unlock(&mut self, volatile: bool) -> Result<()> {
+        // WP# low would make the status register read-only (SRP0=1)
+        self.set_wp(false)?;
+
+        // With WPS=1 the individual block locks apply instead of the BP bits
+        if self.wps_enabled()? {
+            self.simple_command(CMD_GLOBAL_BLOCK_UNLOCK)?;
+        }
 
-        // Verify WEL bit is set
         let status = self.read_status()?;
-        if (status & STATUS_WEL) == 0 {
-            return Err(Ch347Error::TransferFailed("Write enable failed".into()));
+        if status & STATUS_PROTECT_MASK == 0 {
+            return Ok(());
         }
 
+        self.set_protection(status & !STATUS_PROTECT_MASK, volatile)
+    }
+
+    /// Whether any part of the array may refuse erase/program
+    ///
+    /// True when BP0-BP2 are set or individual block locks are in use (WPS=1).
+    pub fn is_write_protected(&mut self) -> Result<bool> {
+        if self.wps_enabled()? {
+            return Ok(true);
+        }
+
+        Ok(self.read_status()? & STATUS_BP_MASK != 0)
+    }
+
+    /// Protect the whole array by setting BP0-BP2, then assert WP#
+    pub fn lock_all(&mut self) -> Result<()> {
+        self.set_wp(false)?;
+
+        if self.wps_enabled()? {
+            self.simple_command(CMD_GLOBAL_BLOCK_LOCK)?;
+        } else {
+            let status = self.read_status()?;
+            self.set_protection((status & !STATUS_PROTECT_MASK) | STATUS_BP_MASK, false)?;
+        }
+
+        self.set_wp(true)
+    }
+
+    /// Protect part of the array (or all of it) by setting BP0-BP2, then assert WP#
+    ///
+    /// Partial levels need the BP bits to be in control, so they are refused
+    /// when individual block locks are in use (WPS=1).
+    pub fn lock(&mut self, level: LockLevel) -> Result<()> {
+        if level == LockLevel::All {
+            return self.lock_all();
+        }
+
+        self.set_wp(false)?;
+
+        if self.wps_enabled()? {
+            return Err(Ch347Error::Unsupported(
+                "Partial BP locks need WPS=0; individual block locks are enabled".into(),
+            ));
+        }
+
+        let status = self.read_status()?;
+        self.set_protection((status & !STATUS_PROTECT_MASK) | level.bp_bits(), false)?;
+
+        self.set_wp(true)
+    }
+
+    /// Whether individual block locks are in use (SR3 WPS=1)
+    ///
+    /// Parts with a configuration register have no SR3: on Macronix 0x15
+    /// reads the CR, whose bit 2 (ODS2) is set by default.
+    fn wps_enabled(&mut self) -> Result<bool> {
+        if self.chip.as_ref().is_some_and(|c| c.config_register.is_some()) {
+            return Ok(false);
+        }
+
+        Ok(self.read_status3()? & STATUS3_WPS != 0)
+    }
+
+    /// Write SR1 protection bits and confirm they took effect
+    fn set_protection(&mut self, value: u8, volatile: bool) -> Result<()> {
+        let attempts: &[bool] = if volatile { &[true] } else { &[false, true] };
+
+        for &volatile in attempts {
+            self.write_status(1, value, volatile)?;
+            if self.read_status()? & STATUS_PROTECT_MASK == value & STATUS_PROTECT_MASK {
+                return Ok(());
+            }
+        }
+
+        Err(self.protection_error()?)
+    }
+
+    /// Explain why the status register refused a write
+    fn protection_error(&mut self) -> Result<Ch347Error> {
+        let srp0 = self.read_status()? & STATUS_SRP0 != 0;
+        let srp1 = self.read_status2()? & STATUS2_SRP1 != 0;
+
+        let reason = match (srp1, srp0) {
+            (true, true) => "status register is permanently locked (SRP1=1, SRP0=1)",
+            (true, false) => "status register is locked until the next power cycle (SRP1=1)",
+            (false, true) => "status register is hardware protected (SRP0=1, WP# pin is low)",
+            (false, false) => "status register write was ignored",
+        };
+
+        Ok(Ch347Error::Locked(reason.into()))
+    }
+
+    /// Issue a single-byte command that requires write enable
+    fn simple_command(&mut self, opcode: u8) -> Result<()> {
+        self.write_enable()?;
+
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[opcode])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        self.wait_ready(50)?;
+
         Ok(())
     }
 
-    /// Read data from flash
-    pub fn read(&mut self, address: u32, data: &mut [u8]) -> Result<()> {
-        self.device.spi_cs(true)?;
+    /// Address of security register `reg` (0-based): registers 1-3 live at 0x1000/0x2000/0x3000
+    fn security_register_address(reg: u8) -> Result<u32> {
+        if reg >= SECURITY_REG_COUNT {
+            return Err(Ch347Error::TransferFailed(format!("Invalid security register {}", reg)));
+        }
+        Ok(((reg as u32) + 1) << 12)
+    }
 
-        // Send read command with 24-bit address
+    /// Check whether the lock bit (LB1-LB3 in status register 2) of a security register is set
+    pub fn is_security_register_locked(&mut self, reg: u8) -> Result<bool> {
+        Self::security_register_address(reg)?;
+        let status2 = self.read_status2()?;
+        Ok(status2 & (STATUS2_LB1 << reg) != 0)
+    }
+
+    /// Read a 256-byte security register (0x48)
+    pub fn read_security_register(&mut self, reg: u8) -> Result<Vec<u8>> {
+        let address = Self::security_register_address(reg)?;
+
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // Opcode, 24-bit address, one dummy byte
         let cmd = [
-            CMD_READ_DATA,
+            CMD_READ_SECURITY_REG,
             ((address >> 16) & 0xFF) as u8,
             ((address >> 8) & 0xFF) as u8,
             (address & 0xFF) as u8,
+            0,
         ];
+        let mut data = vec![0u8; SECURITY_REG_SIZE];
+
         self.device.spi_write(&cmd)?;
+        self.device.spi_read(&mut data)?;
 
-        // Read data in chunks
-        const CHUNK_SIZE: usize = 256;
-        for chunk in data.chunks_mut(CHUNK_SIZE) {
-            self.device.spi_read(chunk)?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        Ok(data)
+    }
+
+    /// Erase a security register (0x44)
+    pub fn erase_security_register(&mut self, reg: u8) -> Result<()> {
+        let address = Self::security_register_address(reg)?;
+        if self.is_security_register_locked(reg)? {
+            return Err(Ch347Error::Locked(format!("security register {} is locked", reg)));
         }
 
-        self.device.spi_cs(false)?;
+        self.write_enable()?;
+
+        self.device.spi_cs(self.chip_select, true)?;
+
+        let cmd = [
+            CMD_ERASE_SECURITY_REG,
+            ((address >> 16) & 0xFF) as u8,
+            ((address >> 8) & 0xFF) as u8,
+            (address & 0xFF) as u8,
+        ];
+        self.device.spi_write(&cmd)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // Same timing as a sector erase
+        let timeout_ms = self.erase_timeout_ms(EraseOp::Sector4K);
+        self.wait_ready(timeout_ms)?;
 
         Ok(())
     }
 
-    /// Erase sector (4KB)
-    pub fn erase_sector(&mut self, address: u32) -> Result<()> {
+    /// Program a security register (0x42), starting at byte 0
+    pub fn program_security_register(&mut self, reg: u8, data: &[u8]) -> Result<()> {
+        let address = Self::security_register_address(reg)?;
+        if data.is_empty() || data.len() > SECURITY_REG_SIZE {
+            return Err(Ch347Error::TransferFailed("Invalid security register data size".into()));
+        }
+        if self.is_security_register_locked(reg)? {
+            return Err(Ch347Error::Locked(format!("security register {} is locked", reg)));
+        }
+
         self.write_enable()?;
 
-        self.device.spi_cs(true)?;
+        self.device.spi_cs(self.chip_select, true)?;
 
         let cmd = [
-            CMD_SECTOR_ERASE,
+            CMD_PROGRAM_SECURITY_REG,
             ((address >> 16) & 0xFF) as u8,
             ((address >> 8) & 0xFF) as u8,
             (address & 0xFF) as u8,
         ];
         self.device.spi_write(&cmd)?;
+        self.device.spi_write(data)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        self.wait_ready(10)?;
+
+        Ok(())
+    }
+
+    /// Wait for write to complete
+    pub fn wait_ready(&mut self, timeout_ms: u32) -> Result<()> {
+        let start = std::time::Instant::now();
+        let timeout = std::time::Duration::from_millis(timeout_ms as u64);
+
+        let mut stuck_since: Option<Instant> = None;
+
+        loop {
+            let status = self.read_status()?;
+            if (status & STATUS_WIP) == 0 {
+                return Ok(());
+            }
+
+            if self.cancel.load(Ordering::SeqCst) {
+                return Err(Ch347Error::Cancelled);
+            }
+
+            // All ones means nothing is driving MISO; don't wait out a chip erase for it
+            if status == 0xFF {
+                let since = *stuck_since.get_or_insert_with(Instant::now);
+                if since.elapsed() > self.stuck_grace {
+                    return Err(Ch347Error::ChipNotResponding(status));
+                }
+            } else {
+                stuck_since = None;
+            }
+
+            if start.elapsed() > timeout {
+                return Err(Ch347Error::Timeout("ready".into()));
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    /// Enable write
+    pub fn write_enable(&mut self) -> Result<()> {
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[CMD_WRITE_ENABLE])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        // Verify WEL bit is set
+        let status = self.read_status()?;
+        if (status & STATUS_WEL) == 0 {
+            return Err(Ch347Error::TransferFailed("Write enable failed".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Whether addressed commands use the 4-byte opcodes (chips over 16MB)
+    fn uses_4byte_addr(&self) -> bool {
+        self.chip.as_ref().is_some_and(|c| c.uses_4byte_addr())
+    }
+
+    /// Read data from flash
+    pub fn read(&mut self, address: u32, data: &mut [u8]) -> Result<()> {
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // Send read command with 24-bit address, or 32-bit past 16MB
+        let cmd = address_command(CMD_READ_DATA, address, self.uses_4byte_addr());
+        self.device.spi_write(&cmd)?;
 
-        self.device.spi_cs(false)?;
+        // CS stays asserted, so each request can fill a whole USB packet
+        for chunk in data.chunks_mut(MAX_DATA_LEN) {
+            self.device.spi_read(chunk)?;
+        }
 
-        // Sector erase typically takes 50-400ms
-        self.wait_ready(500)?;
+        self.device.spi_cs(self.chip_select, false)?;
 
         Ok(())
     }
 
-    /// Erase block (64KB)
+    /// Read data using a fast read opcode with dummy cycles
+    ///
+    /// Only `ReadMode::Single` can be serviced: the CH347 samples MISO alone,
+    /// so dual/quad output data would come back garbled. Its configuration
+    /// packet has no setting that changes this.
+    pub fn read_fast(&mut self, address: u32, data: &mut [u8], mode: ReadMode) -> Result<()> {
+        if let Some(chip) = &self.chip {
+            if !chip.read_modes.contains(&mode) {
+                return Err(Ch347Error::Unsupported(format!("{} does not support {:?} read", chip.name, mode)));
+            }
+        }
+
+        if mode != ReadMode::Single {
+            return Err(Ch347Error::Unsupported(format!("CH347 cannot sample {:?} read data", mode)));
+        }
+
+        self.device.spi_cs(self.chip_select, true)?;
+
+        // Opcode, 24-bit (or 32-bit) address, then dummy bytes
+        let mut cmd = address_command(mode.opcode(), address, self.uses_4byte_addr());
+        cmd.resize(cmd.len() + mode.dummy_bytes(), 0);
+        self.device.spi_write(&cmd)?;
+
+        for chunk in data.chunks_mut(MAX_DATA_LEN) {
+            self.device.spi_read(chunk)?;
+        }
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        Ok(())
+    }
+
+    /// Erase one sector, the chip's smallest erase (4KB on most parts)
+    pub fn erase_sector(&mut self, address: u32) -> Result<()> {
+        let op = self.erase_ops().into_iter().min_by_key(|op| op.size()).unwrap_or(EraseOp::Sector4K);
+        self.erase(op, address)
+    }
+
+    /// Erase block (the largest the chip supports, 64KB on most parts)
     pub fn erase_block(&mut self, address: u32) -> Result<()> {
+        let op = self.erase_ops().into_iter().max_by_key(|op| op.size()).unwrap_or(EraseOp::Block64K);
+        self.erase(op, address)
+    }
+
+    /// Erase one sector or block at an address aligned to its size
+    pub fn erase(&mut self, op: EraseOp, address: u32) -> Result<()> {
         self.write_enable()?;
 
-        self.device.spi_cs(true)?;
+        self.device.spi_cs(self.chip_select, true)?;
+
+        let cmd = address_command(op.opcode(), address, self.uses_4byte_addr());
+        self.device.spi_write(&cmd)?;
+
+        self.device.spi_cs(self.chip_select, false)?;
+
+        let timeout_ms = self.erase_timeout_ms(op);
+        self.wait_ready(timeout_ms)?;
+
+        Ok(())
+    }
+
+    /// Worst-case time for an erase, from the chip if known
+    fn erase_timeout_ms(&self, op: EraseOp) -> u32 {
+        match &self.chip {
+            Some(chip) if op.size() as usize == chip.sector_size => chip.sector_erase_ms,
+            Some(chip) => chip.block_erase_ms,
+            None => op.timeout_ms(),
+        }
+    }
+
+    /// Erase operations the current chip implements (all of them if unknown)
+    pub fn erase_ops(&self) -> Vec<EraseOp> {
+        self.chip.as_ref().map(|c| c.erase_ops.clone()).unwrap_or_else(default_erase_ops)
+    }
+
+    /// Erase `[address, address + len)` using the fewest sector/block erases
+    ///
+    /// The range is widened to 4KB boundaries. Progress counts erase operations.
+    pub fn erase_range(&mut self, address: u32, len: usize, progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        let plan = plan_erase(address, len, &self.erase_ops());
+        let total = plan.len();
+
+        for (i, (op, addr)) in plan.into_iter().enumerate() {
+            self.checkpoint()?;
+
+            self.erase(op, addr)?;
+
+            if let Some(cb) = progress {
+                cb(i + 1, total);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Erase entire chip, returning the opcode that was accepted
+    ///
+    /// Uses the chip's `chip_erase_opcode`. A part that ignores it finishes
+    /// "instantly" with WEL still set; with `retry_alternate` the other of
+    /// 0xC7/0x60 is then tried before giving up.
+    pub fn erase_chip(&mut self, retry_alternate: bool) -> Result<u8> {
+        let opcode = self.chip.as_ref().map(|c| c.chip_erase_opcode).unwrap_or(CMD_CHIP_ERASE);
+        let alternate = if opcode == CMD_CHIP_ERASE { CMD_CHIP_ERASE_ALT } else { CMD_CHIP_ERASE };
+
+        if self.chip_erase_with(opcode)? {
+            return Ok(opcode);
+        }
+
+        if retry_alternate {
+            log::warn!("Chip erase 0x{:02X} ignored, retrying with 0x{:02X}", opcode, alternate);
+            if self.chip_erase_with(alternate)? {
+                return Ok(alternate);
+            }
+        }
+
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[CMD_WRITE_DISABLE])?;
+        self.device.spi_cs(self.chip_select, false)?;
+
+        let tried = if retry_alternate {
+            format!("0x{:02X} and 0x{:02X}", opcode, alternate)
+        } else {
+            format!("0x{:02X}", opcode)
+        };
+        Err(Ch347Error::TransferFailed(format!("Chip erase ignored (tried {})", tried)))
+    }
+
+    /// Issue one chip erase opcode and wait; false if the chip ignored it
+    fn chip_erase_with(&mut self, opcode: u8) -> Result<bool> {
+        self.write_enable()?;
 
-        let cmd = [
-            CMD_BLOCK_ERASE_64K,
-            ((address >> 16) & 0xFF) as u8,
-            ((address >> 8) & 0xFF) as u8,
-            (address & 0xFF) as u8,
-        ];
-        self.device.spi_write(&cmd)?;
+        self.device.spi_cs(self.chip_select, true)?;
+        self.device.spi_write(&[opcode])?;
+        self.device.spi_cs(self.chip_select, false)?;
 
-        self.device.spi_cs(false)?;
+        // Can take minutes; wait_ready still honours the cancel flag
+        let timeout_ms = self.chip.as_ref().map(|c| c.chip_erase_ms).unwrap_or(CHIP_ERASE_TIMEOUT_MS);
+        self.wait_ready(timeout_ms)?;
 
-        // Block erase typically takes 150-2000ms
-        self.wait_ready(3000)?;
+        // A completed erase clears WEL; an ignored opcode leaves it set
+        Ok(self.read_status()? & STATUS_WEL == 0)
+    }
 
-        Ok(())
+    /// Page size of the current chip, e.g. 256 or 512 bytes
+    fn page_size(&self) -> usize {
+        self.chip.as_ref().map(|c| c.page_size).unwrap_or(DEFAULT_PAGE_SIZE)
     }
 
-    /// Erase entire chip
-    pub fn erase_chip(&mut self) -> Result<()> {
-        self.write_enable()?;
+    /// Program page (up to one page of the current chip)
+    pub fn program_page(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        let page_size = self.page_size();
+
+        // Bytes past the end of the page would wrap around to its start
+        if data.is_empty() || address as usize % page_size + data.len() > page_size {
+            return Err(Ch347Error::TransferFailed("Data crosses a page boundary".into()));
+        }
 
-        self.device.spi_cs(true)?;
-        self.device.spi_write(&[CMD_CHIP_ERASE])?;
-        self.device.spi_cs(false)?;
+        self.start_page_program(address, data)?;
 
-        // Chip erase can take very long (up to 200 seconds for large chips)
-        self.wait_ready(200000)?;
+        // Page program typically takes 0.7-3ms
+        self.wait_ready(10)?;
 
         Ok(())
     }
 
-    /// Program page (up to 256 bytes)
-    pub fn program_page(&mut self, address: u32, data: &[u8]) -> Result<()> {
-        if data.is_empty() || data.len() > 256 {
-            return Err(Ch347Error::TransferFailed("Invalid page size".into()));
+    /// Issue write enable and the page program command without waiting
+    fn start_page_program(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        if self.strict_erase_check {
+            self.check_erased(address, data)?;
         }
 
         self.write_enable()?;
 
-        self.device.spi_cs(true)?;
+        self.device.spi_cs(self.chip_select, true)?;
 
-        // Send program command with address
-        let cmd = [
-            CMD_PAGE_PROGRAM,
-            ((address >> 16) & 0xFF) as u8,
-            ((address >> 8) & 0xFF) as u8,
-            (address & 0xFF) as u8,
-        ];
+        // Send program command with 24-bit address, or 32-bit past 16MB
+        let cmd = address_command(CMD_PAGE_PROGRAM, address, self.uses_4byte_addr());
         self.device.spi_write(&cmd)?;
 
         // Write data
         self.device.spi_write(data)?;
 
-        self.device.spi_cs(false)?;
+        self.device.spi_cs(self.chip_select, false)
+    }
 
-        // Page program typically takes 0.7-3ms
-        self.wait_ready(10)?;
+    /// Fail if programming `data` at `address` would need any bit set to 1
+    ///
+    /// A page program ANDs the data into the array, so only bytes erased
+    /// (or already holding the right ones) come out as written.
+    fn check_erased(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        let mut current = vec![0u8; data.len()];
+        self.read(address, &mut current)?;
+
+        match current.iter().zip(data).position(|(&have, &want)| have & want != want) {
+            Some(i) => Err(Ch347Error::NotErased(address + i as u32)),
+            None => Ok(()),
+        }
+    }
 
-        Ok(())
+    /// Wait for a page program, sleeping `delay` before the first WIP poll
+    ///
+    /// Polls back to back after the delay instead of sleeping between them;
+    /// each status read is already a USB round trip. Returns the time from
+    /// the end of the program command until WIP cleared.
+    fn wait_page_programmed(&mut self, delay: Duration) -> Result<Duration> {
+        let start = Instant::now();
+        std::thread::sleep(delay);
+
+        loop {
+            if (self.read_status()? & STATUS_WIP) == 0 {
+                return Ok(start.elapsed());
+            }
+
+            if self.cancel.load(Ordering::SeqCst) {
+                return Err(Ch347Error::Cancelled);
+            }
+
+            if start.elapsed() > PAGE_PROGRAM_TIMEOUT {
+                return Err(Ch347Error::Timeout("ready".into()));
+            }
+        }
     }
 
     /// Write data with automatic page handling
     pub fn write(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
-        let page_size = self.chip.as_ref().map(|c| c.page_size).unwrap_or(256);
+        self.program_pages_batched(address, data, progress)
+    }
+
+    /// Program consecutive pages, keeping WIP polls to a minimum
+    ///
+    /// The first poll after each page waits out most of the chip's program
+    /// time: the delay starts at `PAGE_PROGRAM_POLL_DELAY` and follows the
+    /// fastest page seen so far, so a typical page is ready on the first or
+    /// second status read rather than after a fixed 1ms sleep.
+    pub fn program_pages_batched(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        self.write_pages(address, data, false, progress)
+    }
+
+    /// Write data, reading each page back right after programming it
+    ///
+    /// Stops at the first mismatch with `Ch347Error::VerifyFailed`.
+    pub fn write_verify_pages(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        self.write_pages(address, data, true, progress)
+    }
+
+    fn write_pages(&mut self, address: u32, data: &[u8], verify: bool, progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        let page_size = self.page_size();
         let total = data.len();
         let mut offset = 0;
         let mut addr = address;
+        let mut readback = vec![0u8; if verify { page_size } else { 0 }];
+        let mut poll_delay = PAGE_PROGRAM_POLL_DELAY;
 
         while offset < total {
+            self.checkpoint()?;
+
             // Calculate bytes to write in this page
             let page_offset = (addr as usize) % page_size;
             let chunk_size = std::cmp::min(page_size - page_offset, total - offset);
+            let page = &data[offset..offset + chunk_size];
+
+            self.start_page_program(addr, page)?;
+            let busy = self.wait_page_programmed(poll_delay)?;
+            poll_delay = poll_delay.min(busy * 3 / 4);
+
+            if verify {
+                self.read(addr, &mut readback[..chunk_size])?;
 
-            self.program_page(addr, &data[offset..offset + chunk_size])?;
+                if let Some(i) = readback[..chunk_size].iter().zip(page).position(|(a, b)| a != b) {
+                    return Err(Ch347Error::VerifyFailed(addr + i as u32));
+                }
+            }
 
             offset += chunk_size;
             addr += chunk_size as u32;
@@ -478,6 +2264,102 @@ impl FlashProgrammer {
         Ok(())
     }
 
+    /// Write data without disturbing the rest of each touched sector
+    ///
+    /// Every affected sector is read, patched with the new bytes, erased and
+    /// reprogrammed, so bytes outside `[address, address + data.len())` survive.
+    pub fn write_preserving(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<()> {
+        let sector_size = self.chip.as_ref().map(|c| c.sector_size).unwrap_or(4096);
+        let start = address as usize;
+        let end = start + data.len();
+        let mut sector_buf = vec![0u8; sector_size];
+        let mut sector_addr = start - start % sector_size;
+
+        while sector_addr < end {
+            self.checkpoint()?;
+
+            self.read(sector_addr as u32, &mut sector_buf)?;
+
+            // Patch in the overlapping part of the new data
+            let patch_start = std::cmp::max(start, sector_addr);
+            let patch_end = std::cmp::min(end, sector_addr + sector_size);
+            sector_buf[patch_start - sector_addr..patch_end - sector_addr]
+                .copy_from_slice(&data[patch_start - start..patch_end - start]);
+
+            self.erase_sector(sector_addr as u32)?;
+
+            // Erased pages are already 0xFF, only program the rest
+            let page_size = self.page_size();
+            for (i, page) in sector_buf.chunks(page_size).enumerate() {
+                if page.iter().any(|&b| b != 0xFF) {
+                    self.program_page((sector_addr + i * page_size) as u32, page)?;
+                }
+            }
+
+            sector_addr += sector_size;
+
+            if let Some(cb) = progress {
+                cb(std::cmp::min(sector_addr, end) - start, data.len());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write only what differs, returning the addresses of the sectors that were rewritten
+    ///
+    /// Sectors already matching are skipped. When a change only clears bits the
+    /// sector is programmed in place; otherwise it is erased first. Pages that
+    /// already hold the right bytes are never reprogrammed, and bytes outside the
+    /// data range are preserved.
+    pub fn write_diff(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<Vec<u32>> {
+        let sector_size = self.chip.as_ref().map(|c| c.sector_size).unwrap_or(4096);
+        let page_size = self.page_size();
+        let start = address as usize;
+        let end = start + data.len();
+        let mut current = vec![0u8; sector_size];
+        let mut rewritten = Vec::new();
+        let mut sector_addr = start - start % sector_size;
+
+        while sector_addr < end {
+            self.checkpoint()?;
+
+            self.read(sector_addr as u32, &mut current)?;
+
+            let mut target = current.clone();
+            let patch_start = std::cmp::max(start, sector_addr);
+            let patch_end = std::cmp::min(end, sector_addr + sector_size);
+            target[patch_start - sector_addr..patch_end - sector_addr]
+                .copy_from_slice(&data[patch_start - start..patch_end - start]);
+
+            if target != current {
+                // Programming can only clear bits; anything needing a 0 -> 1 needs an erase
+                let needs_erase = current.iter().zip(&target).any(|(&c, &t)| c & t != t);
+                if needs_erase {
+                    self.erase_sector(sector_addr as u32)?;
+                    current.fill(0xFF);
+                }
+
+                for (i, page) in target.chunks(page_size).enumerate() {
+                    let page_start = i * page_size;
+                    if page != &current[page_start..page_start + page.len()] {
+                        self.program_page((sector_addr + page_start) as u32, page)?;
+                    }
+                }
+
+                rewritten.push(sector_addr as u32);
+            }
+
+            sector_addr += sector_size;
+
+            if let Some(cb) = progress {
+                cb(std::cmp::min(sector_addr, end) - start, data.len());
+            }
+        }
+
+        Ok(rewritten)
+    }
+
     /// Verify data
     pub fn verify(&mut self, address: u32, data: &[u8], progress: Option<&dyn Fn(usize, usize)>) -> Result<bool> {
         const CHUNK_SIZE: usize = 4096;
@@ -506,8 +2388,700 @@ impl FlashProgrammer {
         Ok(true)
     }
 
+    /// Stream `len` bytes from `address` in 64KB chunks, stopping early when `f` returns false
+    fn read_chunks<F>(&mut self, address: u32, len: usize, progress: Option<&dyn Fn(usize, usize)>, mut f: F) -> Result<()>
+    where
+        F: FnMut(u32, &[u8]) -> bool,
+    {
+        const CHUNK_SIZE: usize = 65536;
+        let mut buf = vec![0u8; std::cmp::min(CHUNK_SIZE, len)];
+        let mut offset = 0;
+
+        while offset < len {
+            self.checkpoint()?;
+
+            let chunk_size = std::cmp::min(CHUNK_SIZE, len - offset);
+            let addr = address + offset as u32;
+
+            self.read(addr, &mut buf[..chunk_size])?;
+            offset += chunk_size;
+
+            if !f(addr, &buf[..chunk_size]) {
+                break;
+            }
+
+            if let Some(cb) = progress {
+                cb(offset, len);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that a range is erased, returning the address of the first non-0xFF byte
+    pub fn is_blank(&mut self, address: u32, len: usize) -> Result<Option<u32>> {
+        let mut dirty = None;
+
+        self.read_chunks(address, len, None, |addr, chunk| {
+            match chunk.iter().position(|&b| b != 0xFF) {
+                Some(i) => {
+                    dirty = Some(addr + i as u32);
+                    false
+                }
+                None => true,
+            }
+        })?;
+
+        Ok(dirty)
+    }
+
+    /// Compute the CRC32 of a flash range without buffering it
+    pub fn checksum_crc32(&mut self, address: u32, len: usize, progress: Option<&dyn Fn(usize, usize)>) -> Result<u32> {
+        let mut hasher = crc32fast::Hasher::new();
+
+        self.read_chunks(address, len, progress, |_, chunk| {
+            hasher.update(chunk);
+            true
+        })?;
+
+        Ok(hasher.finalize())
+    }
+
+    /// Hash a flash range with the given algorithm, returning a lowercase hex digest
+    ///
+    /// Output matches `crc32`/`md5sum`/`sha256sum` run on the same bytes.
+    pub fn digest(&mut self, address: u32, len: usize, algo: DigestAlgo, progress: Option<&dyn Fn(usize, usize)>) -> Result<String> {
+        match algo {
+            DigestAlgo::Crc32 => Ok(format!("{:08x}", self.checksum_crc32(address, len, progress)?)),
+            DigestAlgo::Md5 => self.hash_range::<md5::Md5>(address, len, progress),
+            DigestAlgo::Sha256 => self.hash_range::<sha2::Sha256>(address, len, progress),
+        }
+    }
+
+    fn hash_range<D: Digest>(&mut self, address: u32, len: usize, progress: Option<&dyn Fn(usize, usize)>) -> Result<String> {
+        let mut hasher = D::new();
+
+        self.read_chunks(address, len, progress, |_, chunk| {
+            hasher.update(chunk);
+            true
+        })?;
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Use the given chip geometry instead of the detected one
+    pub fn set_chip(&mut self, chip: FlashChip) {
+        self.chip = Some(chip);
+    }
+
     /// Get detected chip info
     pub fn get_chip(&self) -> Option<&FlashChip> {
         self.chip.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockFlash;
+
+    fn chip(name: &str) -> FlashChip {
+        get_flash_database()
+            .into_iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("{} not in the database", name))
+    }
+
+    /// Programmer on a blank simulated `chip`, with its geometry already known
+    fn programmer(chip: &FlashChip) -> FlashProgrammer<MockFlash> {
+        let mut programmer = FlashProgrammer::with_backend(MockFlash::new(chip));
+        programmer.set_chip(chip.clone());
+        programmer
+    }
+
+    /// Passes traffic through to a `MockFlash`, keeping the bytes written
+    /// in each chip select cycle and counting read transfers
+    struct Recorder {
+        flash: MockFlash,
+        commands: Vec<Vec<u8>>,
+        reads: usize,
+        reg_0x15: Option<u8>,  // Reply to 0x15 in place of the mock's 0xFF
+    }
+
+    impl SpiBackend for Recorder {
+        fn spi_init_mode(&mut self, clock: SpiClock, mode: SpiMode, bit_order: BitOrder) -> Result<()> {
+            self.flash.spi_init_mode(clock, mode, bit_order)
+        }
+
+        fn spi_cs(&mut self, cs_line: u8, assert: bool) -> Result<()> {
+            if assert {
+                self.commands.push(Vec::new());
+            }
+            self.flash.spi_cs(cs_line, assert)
+        }
+
+        fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+            if let Some(command) = self.commands.last_mut() {
+                command.extend_from_slice(data);
+            }
+            self.flash.spi_write(data)
+        }
+
+        fn spi_read(&mut self, data: &mut [u8]) -> Result<()> {
+            self.reads += 1;
+            if let (Some(value), Some([CMD_READ_STATUS3])) = (self.reg_0x15, self.commands.last().map(Vec::as_slice)) {
+                data.fill(value);
+                return Ok(());
+            }
+            self.flash.spi_read(data)
+        }
+
+        fn gpio_set(&mut self, pin: u8, level: bool) -> Result<()> {
+            self.flash.gpio_set(pin, level)
+        }
+    }
+
+    fn recording(chip: &FlashChip) -> FlashProgrammer<Recorder> {
+        let mut programmer = FlashProgrammer::with_backend(Recorder {
+            flash: MockFlash::new(chip),
+            commands: Vec::new(),
+            reads: 0,
+            reg_0x15: None,
+        });
+        programmer.set_chip(chip.clone());
+        programmer
+    }
+
+    /// Commands sent so far that start with one of `opcodes`
+    fn sent(programmer: &FlashProgrammer<Recorder>, opcodes: &[u8]) -> Vec<Vec<u8>> {
+        programmer
+            .backend()
+            .commands
+            .iter()
+            .filter(|c| c.first().is_some_and(|op| opcodes.contains(op)))
+            .cloned()
+            .collect()
+    }
+
+    /// Answers each chip select cycle with the next canned reply (the last
+    /// one repeats), padding with 0xFF; for exercising ID decoding
+    struct Replies {
+        replies: Vec<Vec<u8>>,
+        cycle: usize,
+        pos: usize,
+    }
+
+    impl Replies {
+        fn new(replies: &[&[u8]]) -> FlashProgrammer<Replies> {
+            FlashProgrammer::with_backend(Replies {
+                replies: replies.iter().map(|r| r.to_vec()).collect(),
+                cycle: 0,
+                pos: 0,
+            })
+        }
+    }
+
+    impl SpiBackend for Replies {
+        fn spi_init_mode(&mut self, _clock: SpiClock, _mode: SpiMode, _bit_order: BitOrder) -> Result<()> {
+            Ok(())
+        }
+
+        fn spi_cs(&mut self, _cs_line: u8, assert: bool) -> Result<()> {
+            if assert {
+                self.pos = 0;
+            } else {
+                self.cycle += 1;
+            }
+            Ok(())
+        }
+
+        fn spi_write(&mut self, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn spi_read(&mut self, data: &mut [u8]) -> Result<()> {
+            let reply = &self.replies[self.cycle.min(self.replies.len() - 1)];
+            for byte in data.iter_mut() {
+                *byte = reply.get(self.pos).copied().unwrap_or(0xFF);
+                self.pos += 1;
+            }
+            Ok(())
+        }
+
+        fn gpio_set(&mut self, _pin: u8, _level: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8 ^ 0x5A).collect()
+    }
+
+    #[test]
+    fn detect_identifies_the_simulated_chip() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = FlashProgrammer::with_backend(MockFlash::new(&w25q16));
+
+        let detected = programmer.detect(&get_flash_database(), DetectOptions::default()).unwrap();
+        assert_eq!(detected.name, "W25Q16");
+    }
+
+    #[test]
+    fn erase_write_verify_round_trip() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        let data = pattern(10_000);
+
+        programmer.erase_range(0x1000, data.len(), None).unwrap();
+        programmer.write(0x1000, &data, None).unwrap();
+
+        assert!(programmer.verify(0x1000, &data, None).unwrap());
+
+        let mut readback = vec![0u8; data.len()];
+        programmer.read(0x1000, &mut readback).unwrap();
+        assert_eq!(readback, data);
+    }
+
+    #[test]
+    fn erase_range_blanks_whole_sectors_only() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        let data = pattern(0x4000);
+        programmer.write(0, &data, None).unwrap();
+
+        // Widened to the sectors at 0x1000 and 0x2000
+        programmer.erase_range(0x1800, 0x1000, None).unwrap();
+
+        assert_eq!(programmer.is_blank(0x1000, 0x2000).unwrap(), None);
+        let mut outside = vec![0u8; 0x1000];
+        programmer.read(0, &mut outside).unwrap();
+        assert_eq!(outside, data[..0x1000]);
+        programmer.read(0x3000, &mut outside).unwrap();
+        assert_eq!(outside, data[0x3000..]);
+    }
+
+    #[test]
+    fn verify_fails_after_writing_over_unerased_data() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        programmer.write(0, &[0x0F; 256], None).unwrap();
+
+        // Without an erase, programming can't set the high nibble back
+        programmer.write(0, &[0xF0; 256], None).unwrap();
+
+        assert!(!programmer.verify(0, &[0xF0; 256], None).unwrap());
+        assert!(programmer.verify(0, &[0x00; 256], None).unwrap());
+    }
+
+    #[test]
+    fn address_command_switches_to_4byte_opcodes() {
+        assert_eq!(address_command(CMD_READ_DATA, 0x123456, false), [0x03, 0x12, 0x34, 0x56]);
+        assert_eq!(address_command(CMD_READ_DATA, 0x01234567, true), [0x13, 0x01, 0x23, 0x45, 0x67]);
+        assert_eq!(address_command(CMD_FAST_READ, 0x01234567, true), [0x0C, 0x01, 0x23, 0x45, 0x67]);
+        assert_eq!(address_command(CMD_PAGE_PROGRAM, 0x01000000, true), [0x12, 0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(address_command(CMD_SECTOR_ERASE, 0x01000000, true), [0x21, 0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(address_command(CMD_BLOCK_ERASE_32K, 0x01000000, true), [0x5C, 0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(address_command(CMD_BLOCK_ERASE_64K, 0x01000000, true), [0xDC, 0x01, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn reads_past_16mb_send_four_address_bytes() {
+        let w25q256 = chip("W25Q256");
+        assert!(w25q256.uses_4byte_addr());
+        let mut programmer = recording(&w25q256);
+        let mut buf = [0u8; 16];
+
+        programmer.read(0x01234567, &mut buf).unwrap();
+        programmer.read_fast(0x01234567, &mut buf, ReadMode::Single).unwrap();
+
+        let reads = sent(&programmer, &[CMD_READ_DATA, CMD_READ_DATA_4B, CMD_FAST_READ, CMD_FAST_READ_4B]);
+        assert_eq!(reads, [
+            vec![CMD_READ_DATA_4B, 0x01, 0x23, 0x45, 0x67],
+            vec![CMD_FAST_READ_4B, 0x01, 0x23, 0x45, 0x67, 0x00],  // One dummy byte
+        ]);
+    }
+
+    #[test]
+    fn reads_up_to_16mb_keep_three_address_bytes() {
+        let mut programmer = recording(&chip("W25Q128"));
+        let mut buf = [0u8; 16];
+
+        programmer.read(0x123456, &mut buf).unwrap();
+
+        assert_eq!(sent(&programmer, &[CMD_READ_DATA, CMD_READ_DATA_4B]), [vec![CMD_READ_DATA, 0x12, 0x34, 0x56]]);
+    }
+
+    #[test]
+    fn writes_past_16mb_do_not_wrap_into_the_low_half() {
+        let w25q256 = chip("W25Q256");
+        let mut programmer = programmer(&w25q256);
+        let addr = MAX_3BYTE_SIZE as u32 + 0x1000;
+        let data = pattern(0x1000);
+
+        programmer.erase_range(addr, data.len(), None).unwrap();
+        programmer.write(addr, &data, None).unwrap();
+
+        assert!(programmer.verify(addr, &data, None).unwrap());
+        assert_eq!(programmer.is_blank(0x1000, data.len()).unwrap(), None);
+    }
+
+    #[test]
+    fn read_modes_have_their_opcode_and_one_dummy_byte() {
+        assert_eq!((ReadMode::Single.opcode(), ReadMode::Single.dummy_bytes()), (0x0B, 1));
+        assert_eq!((ReadMode::DualOutput.opcode(), ReadMode::DualOutput.dummy_bytes()), (0x3B, 1));
+        assert_eq!((ReadMode::QuadOutput.opcode(), ReadMode::QuadOutput.dummy_bytes()), (0x6B, 1));
+    }
+
+    #[test]
+    fn fast_read_sends_opcode_address_and_dummy_byte() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = recording(&w25q16);
+        let mut buf = [0u8; 16];
+
+        programmer.read_fast(0x012345, &mut buf, ReadMode::Single).unwrap();
+
+        assert_eq!(sent(&programmer, &[CMD_FAST_READ]), [vec![CMD_FAST_READ, 0x01, 0x23, 0x45, 0x00]]);
+    }
+
+    #[test]
+    fn multi_lane_reads_are_refused_without_touching_the_bus() {
+        let mut custom = chip("W25Q16");
+        custom.read_modes = vec![ReadMode::Single, ReadMode::DualOutput, ReadMode::QuadOutput];
+        let mut programmer = recording(&custom);
+        let mut buf = [0u8; 16];
+
+        for mode in [ReadMode::DualOutput, ReadMode::QuadOutput] {
+            assert!(matches!(programmer.read_fast(0, &mut buf, mode), Err(Ch347Error::Unsupported(_))));
+        }
+        assert!(programmer.backend().commands.is_empty());
+    }
+
+    #[test]
+    fn database_offers_only_single_lane_reads() {
+        for chip in get_flash_database() {
+            assert_eq!(chip.read_modes, [ReadMode::Single], "{}", chip.name);
+        }
+    }
+
+    /// Number of each of 64KB, 32KB and 4KB erases in a plan
+    fn plan_counts(plan: &[(EraseOp, u32)]) -> (usize, usize, usize) {
+        let count = |op| plan.iter().filter(|(o, _)| *o == op).count();
+        (count(EraseOp::Block64K), count(EraseOp::Block32K), count(EraseOp::Sector4K))
+    }
+
+    #[test]
+    fn plan_erase_covers_unaligned_lengths() {
+        let all = default_erase_ops();
+
+        // 0x3A800 rounds up to 0x3B000: three 64KB blocks, a 32KB block, three sectors
+        let plan = plan_erase(0, 0x3A800, &all);
+        assert_eq!(plan_counts(&plan), (3, 1, 3));
+        assert_eq!(plan.last(), Some(&(EraseOp::Sector4K, 0x3A000)));
+
+        // Less than one sector still erases that sector
+        assert_eq!(plan_erase(0x1234, 1, &all), [(EraseOp::Sector4K, 0x1000)]);
+
+        // Without 32KB blocks the gap before the next 64KB block is all sectors
+        let no_32k = [EraseOp::Sector4K, EraseOp::Block64K];
+        assert_eq!(plan_counts(&plan_erase(0, 0x3A800, &no_32k)), (3, 0, 11));
+    }
+
+    #[test]
+    fn erase_range_emits_the_planned_opcodes() {
+        let mut programmer = recording(&chip("W25Q16"));
+
+        programmer.erase_range(0, 0x3A800, None).unwrap();
+
+        let count = |opcode| sent(&programmer, &[opcode]).len();
+        assert_eq!(count(CMD_BLOCK_ERASE_64K), 3);
+        assert_eq!(count(CMD_BLOCK_ERASE_32K), 1);
+        assert_eq!(count(CMD_SECTOR_ERASE), 3);
+        assert_eq!(programmer.is_blank(0, 0x3B000).unwrap(), None);
+    }
+
+    #[test]
+    fn plan_erase_mixes_opcodes_for_0x1000_to_0x90000() {
+        let plan = plan_erase(0x1000, 0x90000 - 0x1000, &default_erase_ops());
+
+        // Sectors up to the first 32KB boundary, one 32KB block, then 64KB blocks
+        let mut expected: Vec<_> = (1..8).map(|i| (EraseOp::Sector4K, i * 0x1000)).collect();
+        expected.push((EraseOp::Block32K, 0x8000));
+        expected.extend((1..9).map(|i| (EraseOp::Block64K, i * 0x10000)));
+        assert_eq!(plan, expected);
+    }
+
+    #[test]
+    fn database_entries_validate() {
+        for chip in get_flash_database() {
+            assert_eq!(chip.validate(), Ok(()), "{}", chip.name);
+        }
+    }
+
+    #[test]
+    fn dataflash_page_sizes_are_rejected() {
+        let mut custom = chip("W25Q16");
+        for page_size in [264, 528] {
+            custom.page_size = page_size;
+            assert!(custom.validate().is_err(), "{}-byte pages", page_size);
+        }
+    }
+
+    #[test]
+    fn writes_split_at_512_byte_page_boundaries() {
+        let s25fl512s = chip("S25FL512S");
+        assert_eq!(s25fl512s.page_size, 512);
+        let mut programmer = recording(&s25fl512s);
+        let data = pattern(1000);
+
+        programmer.write(0x100, &data, None).unwrap();
+
+        // (address, length) of each page program: up to 0x200, one full page, the rest
+        let programs: Vec<(u32, usize)> = sent(&programmer, &[CMD_PAGE_PROGRAM, CMD_PAGE_PROGRAM_4B])
+            .iter()
+            .map(|c| (u32::from_be_bytes([c[1], c[2], c[3], c[4]]), c.len() - 5))
+            .collect();
+        assert_eq!(programs, [(0x100, 256), (0x200, 512), (0x400, 232)]);
+        assert!(programmer.verify(0x100, &data, None).unwrap());
+    }
+
+    #[test]
+    fn uniform_256k_sectors_erase_whole_sectors() {
+        let s25fl512s = chip("S25FL512S");
+        assert_eq!(plan_erase(0x41000, 0x1000, &s25fl512s.erase_ops), [(EraseOp::Block256K, 0x40000)]);
+
+        let mut programmer = recording(&s25fl512s);
+        programmer.write(0x40000, &[0u8; 512], None).unwrap();
+        programmer.erase_range(0x41000, 0x1000, None).unwrap();
+
+        assert_eq!(sent(&programmer, &[CMD_BLOCK_ERASE_64K_4B]), [vec![CMD_BLOCK_ERASE_64K_4B, 0x00, 0x04, 0x00, 0x00]]);
+        assert_eq!(programmer.is_blank(0x40000, 0x40000).unwrap(), None);
+    }
+
+    #[test]
+    fn config_register_follows_the_chip_not_the_manufacturer_byte() {
+        // XMC shares Micron's 0x20 manufacturer byte but has no NVCR
+        let xm25qh128a = chip("XM25QH128A");
+        assert_eq!(xm25qh128a.jedec_id[0], 0x20);
+        let mut programmer = recording(&xm25qh128a);
+
+        assert!(matches!(programmer.read_config_register(), Err(Ch347Error::Unsupported(_))));
+        assert!(matches!(programmer.write_config_register(0), Err(Ch347Error::Unsupported(_))));
+        assert!(programmer.backend().commands.is_empty());
+
+        let mut programmer = recording(&chip("S25FL128S"));
+        let cr = programmer.read_config_register().unwrap();
+        assert_eq!(cr.kind, ConfigRegisterKind::Spansion);
+        assert_eq!(sent(&programmer, &[CMD_READ_STATUS2, CMD_READ_NV_CONFIG]), [vec![CMD_READ_STATUS2]]);
+    }
+
+    #[test]
+    fn digests_match_reference_values() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        let counting: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+        programmer.write(0x1000, &counting, None).unwrap();
+
+        // Reference values from Python's hashlib and zlib.crc32
+        let cases = [
+            (0x1000, DigestAlgo::Sha256, "c8f5d0341d54d951a71b136e6e2afcb14d11ed8489a7ae126a8fee0df6ecf193"),
+            (0x1000, DigestAlgo::Md5, "2bcd3c4de20c918e19fab5c36249c70d"),
+            (0x1000, DigestAlgo::Crc32, "a2912082"),
+            (0x2000, DigestAlgo::Sha256, "f47a8ec3e9aff2318d896942282ad4fe37d6391c82914f54a5da8a37de1300c6"),
+            (0x2000, DigestAlgo::Md5, "6ae59e64850377ee5470c854761551ea"),
+            (0x2000, DigestAlgo::Crc32, "f154670a"),
+        ];
+        for (address, algo, expected) in cases {
+            assert_eq!(programmer.digest(address, 4096, algo, None).unwrap(), expected, "{:?} at 0x{:X}", algo, address);
+        }
+    }
+
+    #[test]
+    fn write_preserving_keeps_the_rest_of_each_sector() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        let original = pattern(0x3000);
+        programmer.write(0, &original, None).unwrap();
+
+        // Straddles the boundary between the first two sectors
+        let patch = vec![0xA5u8; 0x100];
+        programmer.write_preserving(0xF80, &patch, None).unwrap();
+
+        let mut expected = original.clone();
+        expected[0xF80..0x1080].copy_from_slice(&patch);
+        let mut actual = vec![0u8; 0x3000];
+        programmer.read(0, &mut actual).unwrap();
+        assert!(actual == expected, "first difference at {:?}", actual.iter().zip(&expected).position(|(a, b)| a != b));
+    }
+
+    #[test]
+    fn continuation_codes_select_the_jep106_bank() {
+        let id = JedecId::parse(&[0x7F, 0x7F, 0x9D, 0x60, 0x18, 0xFF]).unwrap();
+        assert_eq!(id, JedecId { bank: 2, mfr: 0x9D, dev: [0x60, 0x18] });
+        assert_eq!(id.to_string(), "7F7F9D6018");
+        assert_eq!(id.manufacturer(), "Unknown");
+        assert_eq!(JedecId::parse(&[0x7F, 0x7F, 0x9D]), None);
+
+        let mut programmer = Replies::new(&[&[0x7F, 0x7F, 0x9D, 0x60, 0x18]]);
+        assert_eq!(programmer.read_jedec_id().unwrap(), id);
+
+        // Same bytes as IS25LP128 (bank 1), but a different manufacturer
+        let detected = programmer.detect(&get_flash_database(), DetectOptions::default()).unwrap();
+        assert_ne!(detected.name, "IS25LP128");
+    }
+
+    #[test]
+    fn custom_512_byte_page_chip_programs_whole_pages() {
+        let custom = FlashChip { name: "Custom512".into(), page_size: 512, ..chip("W25Q16") };
+        let mut programmer = programmer(&custom);
+        let data = pattern(0x1000);
+
+        programmer.write_verify_pages(0, &data, None).unwrap();
+        assert_eq!(programmer.backend().pages_programmed(), 8);
+        assert!(programmer.verify(0, &data, None).unwrap());
+
+        // A full 512-byte page is accepted, one byte more is not
+        programmer.erase_sector(0x1000).unwrap();
+        programmer.program_page(0x1000, &data[..512]).unwrap();
+        assert!(matches!(programmer.program_page(0x1200, &data[..513]), Err(Ch347Error::TransferFailed(_))));
+    }
+
+    #[test]
+    fn reads_fill_whole_usb_packets() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = recording(&w25q16);
+        let mut buf = vec![0u8; 64 * 1024];
+
+        // 256-byte chunks took 256 transfers
+        programmer.read(0, &mut buf).unwrap();
+        assert_eq!(programmer.backend().reads, buf.len().div_ceil(MAX_DATA_LEN));
+        assert!(programmer.backend().reads < 256);
+
+        let mut programmer = recording(&w25q16);
+        programmer.read_fast(0, &mut buf, ReadMode::Single).unwrap();
+        assert_eq!(programmer.backend().reads, buf.len().div_ceil(MAX_DATA_LEN));
+    }
+
+    #[test]
+    fn volatile_status_writes_use_the_0x50_enable() {
+        let w25q16 = chip("W25Q16");
+        let enables_and_writes = [CMD_VOLATILE_SR_WRITE_ENABLE, CMD_WRITE_ENABLE, CMD_WRITE_STATUS2];
+
+        let mut programmer = recording(&w25q16);
+        programmer.write_status(2, 0x02, true).unwrap();
+        assert_eq!(sent(&programmer, &enables_and_writes), [vec![CMD_VOLATILE_SR_WRITE_ENABLE], vec![CMD_WRITE_STATUS2, 0x02]]);
+
+        let mut programmer = recording(&w25q16);
+        programmer.write_status(2, 0x02, false).unwrap();
+        assert_eq!(sent(&programmer, &enables_and_writes), [vec![CMD_WRITE_ENABLE], vec![CMD_WRITE_STATUS2, 0x02]]);
+    }
+
+    #[test]
+    fn differing_jedec_reads_are_reported_as_unstable() {
+        let mut programmer = Replies::new(&[&[0xEF, 0x40, 0x18], &[0xEF, 0x40, 0x18], &[0xEF, 0x40, 0x19]]);
+        match programmer.read_jedec_id_stable(3) {
+            Err(Ch347Error::JedecUnstable(first, other)) => assert_eq!((first.as_str(), other.as_str()), ("EF4018", "EF4019")),
+            other => panic!("expected JedecUnstable, got {:?}", other),
+        }
+
+        let mut programmer = Replies::new(&[&[0xEF, 0x40, 0x18], &[0xEF, 0x00, 0x18]]);
+        let options = DetectOptions { robust: true, ..DetectOptions::default() };
+        assert!(matches!(programmer.detect(&get_flash_database(), options), Err(Ch347Error::JedecUnstable(..))));
+        assert!(programmer.get_chip().is_none());
+
+        let mut programmer = Replies::new(&[&[0xEF, 0x40, 0x18]]);
+        assert_eq!(programmer.detect(&get_flash_database(), options).unwrap().name, "W25Q128");
+    }
+
+    #[test]
+    fn strict_erase_check_refuses_unerased_pages() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = recording(&w25q16);
+        programmer.write(0x110, &[0x0F], None).unwrap();
+        programmer.set_strict_erase_check(true);
+
+        assert!(matches!(programmer.write(0x100, &[0xA5; 256], None), Err(Ch347Error::NotErased(0x110))));
+        assert_eq!(sent(&programmer, &[CMD_PAGE_PROGRAM]).len(), 1);
+
+        // Clearing more bits of the programmed byte needs no erase
+        programmer.write(0x110, &[0x05], None).unwrap();
+        assert_eq!(sent(&programmer, &[CMD_PAGE_PROGRAM]).len(), 2);
+    }
+
+    #[test]
+    fn macronix_config_register_is_not_read_as_wps() {
+        let mx25l12835f = chip("MX25L12835F");
+        let mut programmer = recording(&mx25l12835f);
+        // CR with ODS2 (bit 2, where WPS would be) set as shipped
+        programmer.device.reg_0x15 = Some(0x07);
+
+        assert!(!programmer.is_write_protected().unwrap());
+
+        programmer.lock_all().unwrap();
+        assert_eq!(programmer.read_status().unwrap() & STATUS_BP_MASK, STATUS_BP_MASK);
+        assert!(programmer.is_write_protected().unwrap());
+
+        programmer.unlock(false).unwrap();
+        assert_eq!(programmer.read_status().unwrap() & STATUS_PROTECT_MASK, 0);
+
+        programmer.lock(LockLevel::UpperHalf).unwrap();
+        assert_eq!(programmer.read_status().unwrap() & STATUS_BP_MASK, LockLevel::UpperHalf.bp_bits());
+
+        assert!(sent(&programmer, &[CMD_GLOBAL_BLOCK_LOCK, CMD_GLOBAL_BLOCK_UNLOCK]).is_empty());
+    }
+
+    #[test]
+    fn erase_range_holds_between_operations_while_paused() {
+        let w25q16 = chip("W25Q16");
+        let mut programmer = programmer(&w25q16);
+        let pause = Arc::new(AtomicBool::new(true));
+        programmer.set_pause_flag(pause.clone());
+
+        let resume = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            pause.store(false, Ordering::SeqCst);
+        });
+
+        let started = Instant::now();
+        programmer.erase_range(0, 0x2000, None).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(programmer.backend().erases(), 2);
+        resume.join().unwrap();
+    }
+
+    #[test]
+    fn erase_block_uses_the_largest_supported_op() {
+        let s25fl512s = chip("S25FL512S");
+        let mut programmer = recording(&s25fl512s);
+        programmer.write(0x40000, &pattern(512), None).unwrap();
+        programmer.write(0x7FE00, &pattern(512), None).unwrap();
+
+        // 0xD8 (0xDC with a 4-byte address) erases a whole 256KB sector on this part
+        programmer.erase_block(0x40000).unwrap();
+        assert_eq!(sent(&programmer, &[CMD_BLOCK_ERASE_64K_4B]), [vec![CMD_BLOCK_ERASE_64K_4B, 0x00, 0x04, 0x00, 0x00]]);
+        assert_eq!(programmer.is_blank(0x40000, 0x40000).unwrap(), None);
+
+        let mut programmer = recording(&chip("W25Q16"));
+        programmer.erase_block(0x10000).unwrap();
+        assert_eq!(sent(&programmer, &[CMD_BLOCK_ERASE_64K]), [vec![CMD_BLOCK_ERASE_64K, 0x01, 0x00, 0x00]]);
+
+        let no_64k = FlashChip { erase_ops: vec![EraseOp::Sector4K, EraseOp::Block32K], ..chip("W25Q16") };
+        let mut programmer = recording(&no_64k);
+        programmer.erase_block(0x8000).unwrap();
+        assert_eq!(
+            sent(&programmer, &[CMD_BLOCK_ERASE_32K, CMD_BLOCK_ERASE_64K]),
+            [vec![CMD_BLOCK_ERASE_32K, 0x00, 0x80, 0x00]]
+        );
+    }
+
+    #[test]
+    fn config_register_range_error_names_the_limit() {
+        let mut programmer = recording(&chip("S25FL128S"));
+        match programmer.write_config_register(0x1FF) {
+            Err(Ch347Error::Unsupported(msg)) => {
+                assert_eq!(msg, "Spansion configuration register takes at most 0xFF (got 0x1FF)")
+            }
+            other => panic!("expected Unsupported, got {:?}", other),
+        }
+        assert!(programmer.backend().commands.is_empty());
+    }
+}
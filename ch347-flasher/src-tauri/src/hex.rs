@@ -0,0 +1,192 @@
+//! Intel HEX Format
+//!
+//! Parses and generates Intel HEX records (types 00/01/02/04; start address
+//! records 03/05 are accepted and ignored)
+
+use thiserror::Error;
+
+// Record types
+pub const REC_DATA: u8 = 0x00;
+pub const REC_EOF: u8 = 0x01;
+pub const REC_EXT_SEGMENT: u8 = 0x02;
+pub const REC_START_SEGMENT: u8 = 0x03;
+pub const REC_EXT_LINEAR: u8 = 0x04;
+pub const REC_START_LINEAR: u8 = 0x05;
+
+// Data bytes per generated record
+pub const BYTES_PER_RECORD: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum HexError {
+    #[error("line {0}: missing ':' start code")]
+    MissingStartCode(usize),
+
+    #[error("line {0}: invalid hex digits")]
+    InvalidDigits(usize),
+
+    #[error("line {0}: record length does not match byte count")]
+    LengthMismatch(usize),
+
+    #[error("line {0}: checksum mismatch (expected {1:02X}, got {2:02X})")]
+    Checksum(usize, u8, u8),
+
+    #[error("line {0}: unsupported record type {1:02X}")]
+    UnsupportedRecord(usize, u8),
+
+    #[error("missing end-of-file record")]
+    MissingEof,
+}
+
+pub type Result<T> = std::result::Result<T, HexError>;
+
+/// Parse Intel HEX text into contiguous `(address, bytes)` segments
+pub fn parse_intel_hex(text: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+    let mut segments: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut base: u32 = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let digits = line.strip_prefix(':').ok_or(HexError::MissingStartCode(line_no))?;
+        let bytes = ::hex::decode(digits).map_err(|_| HexError::InvalidDigits(line_no))?;
+
+        // Byte count, 16-bit address, record type, data, checksum
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(HexError::LengthMismatch(line_no));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = record_checksum(body);
+        if expected != checksum[0] {
+            return Err(HexError::Checksum(line_no, expected, checksum[0]));
+        }
+
+        let address = ((body[1] as u32) << 8) | body[2] as u32;
+        let data = &body[4..];
+
+        match body[3] {
+            REC_DATA => {
+                let addr = base.wrapping_add(address);
+                match segments.last_mut() {
+                    Some((start, seg)) if *start + seg.len() as u32 == addr => {
+                        seg.extend_from_slice(data);
+                    }
+                    _ => segments.push((addr, data.to_vec())),
+                }
+            }
+            REC_EOF => return Ok(segments),
+            REC_EXT_SEGMENT if data.len() == 2 => {
+                base = (((data[0] as u32) << 8) | data[1] as u32) << 4;
+            }
+            REC_EXT_LINEAR if data.len() == 2 => {
+                base = (((data[0] as u32) << 8) | data[1] as u32) << 16;
+            }
+            REC_START_SEGMENT | REC_START_LINEAR => {}
+            REC_EXT_SEGMENT | REC_EXT_LINEAR => return Err(HexError::LengthMismatch(line_no)),
+            other => return Err(HexError::UnsupportedRecord(line_no, other)),
+        }
+    }
+
+    Err(HexError::MissingEof)
+}
+
+/// Generate Intel HEX text for `data` located at `base`
+pub fn to_intel_hex(base: u32, data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut upper: Option<u16> = None;
+
+    for (i, chunk) in data.chunks(BYTES_PER_RECORD).enumerate() {
+        let addr = base + (i * BYTES_PER_RECORD) as u32;
+        let addr_upper = (addr >> 16) as u16;
+
+        // Emit an extended linear address record whenever the upper 16 bits change
+        if upper != Some(addr_upper) {
+            push_record(&mut out, 0, REC_EXT_LINEAR, &addr_upper.to_be_bytes());
+            upper = Some(addr_upper);
+        }
+
+        // A record must not wrap across a 64KB boundary
+        let room = 0x10000 - (addr & 0xFFFF) as usize;
+        if chunk.len() > room {
+            push_record(&mut out, addr as u16, REC_DATA, &chunk[..room]);
+            let next = addr + room as u32;
+            let next_upper = (next >> 16) as u16;
+            push_record(&mut out, 0, REC_EXT_LINEAR, &next_upper.to_be_bytes());
+            upper = Some(next_upper);
+            push_record(&mut out, next as u16, REC_DATA, &chunk[room..]);
+        } else {
+            push_record(&mut out, addr as u16, REC_DATA, chunk);
+        }
+    }
+
+    push_record(&mut out, 0, REC_EOF, &[]);
+    out
+}
+
+fn push_record(out: &mut String, address: u16, record_type: u8, data: &[u8]) {
+    let mut body = Vec::with_capacity(data.len() + 4);
+    body.push(data.len() as u8);
+    body.extend_from_slice(&address.to_be_bytes());
+    body.push(record_type);
+    body.extend_from_slice(data);
+
+    out.push(':');
+    out.push_str(&::hex::encode_upper(&body));
+    out.push_str(&format!("{:02X}\n", record_checksum(&body)));
+}
+
+/// Two's complement of the byte sum
+fn record_checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)).wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_data_and_eof_records() {
+        let text = ":10010000214601360121470136007EFE09D2190140\n:00000001FF\n";
+        let segments = parse_intel_hex(text).unwrap();
+
+        assert_eq!(segments, [(0x0100, vec![
+            0x21, 0x46, 0x01, 0x36, 0x01, 0x21, 0x47, 0x01, 0x36, 0x00, 0x7E, 0xFE, 0x09, 0xD2, 0x19, 0x01,
+        ])]);
+    }
+
+    #[test]
+    fn extended_linear_address_sets_upper_bits() {
+        let text = ":020000040800F2\n:0400100001020304E2\n:00000001FF\n";
+        let segments = parse_intel_hex(text).unwrap();
+
+        assert_eq!(segments, [(0x0800_0010, vec![1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn bad_checksum_is_rejected() {
+        let text = ":10010000214601360121470136007EFE09D2190141\n:00000001FF\n";
+
+        assert!(matches!(parse_intel_hex(text), Err(HexError::Checksum(1, 0x40, 0x41))));
+    }
+
+    #[test]
+    fn missing_eof_is_rejected() {
+        assert!(matches!(parse_intel_hex(":0400100001020304E2\n"), Err(HexError::MissingEof)));
+    }
+
+    #[test]
+    fn round_trip_is_byte_identical() {
+        // Starts mid-record and crosses a 64KB boundary
+        let data: Vec<u8> = (0..0x20000u32).map(|i| (i * 31 % 253) as u8).collect();
+        let base = 0xFFF8;
+
+        let segments = parse_intel_hex(&to_intel_hex(base, &data)).unwrap();
+
+        assert_eq!(segments, [(base, data)]);
+    }
+}
@@ -0,0 +1,170 @@
+//! USB Hotplug Monitoring
+//!
+//! Watches for CH347 adapters being plugged in or removed. Uses libusb
+//! hotplug callbacks where the platform supports them and falls back to
+//! polling the device list once a second elsewhere.
+
+use crate::ch347::{self, DeviceInfo, CH347F_PID, CH347T_PID, CH347_VID};
+use rusb::{Context, Device, Hotplug, HotplugBuilder, UsbContext};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// How often the watcher thread checks for shutdown
+pub const EVENT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Device list polling interval when hotplug is unavailable
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A CH347 appearing or disappearing
+#[derive(Debug, Clone)]
+pub struct DeviceEvent {
+    pub arrived: bool,
+    pub info: DeviceInfo,
+}
+
+/// Background watcher; stops and deregisters its callback when dropped
+pub struct HotplugWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HotplugWatcher {
+    /// Start watching, calling `on_change` from the watcher thread for each event
+    pub fn start<F>(on_change: F) -> Self
+    where
+        F: Fn(DeviceEvent) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread = std::thread::spawn(move || {
+            if rusb::has_hotplug() {
+                if let Err(e) = watch_hotplug(&thread_stop, &on_change) {
+                    log::warn!("USB hotplug unavailable ({}), polling instead", e);
+                } else {
+                    return;
+                }
+            }
+            watch_polling(&thread_stop, &on_change);
+        });
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stop the watcher thread and wait for it to exit
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Forwards libusb callbacks to the watcher thread
+///
+/// Blocking USB calls are not allowed inside hotplug callbacks, so only the
+/// cached descriptor is read here.
+struct Forwarder {
+    tx: Sender<DeviceEvent>,
+}
+
+impl Forwarder {
+    fn send(&self, device: &Device<Context>, arrived: bool) {
+        let desc = match device.device_descriptor() {
+            Ok(d) => d,
+            Err(_) => return,
+        };
+
+        let pid = desc.product_id();
+        if pid != CH347T_PID && pid != CH347F_PID {
+            return;
+        }
+
+        let _ = self.tx.send(DeviceEvent {
+            arrived,
+            info: DeviceInfo {
+                vid: desc.vendor_id(),
+                pid,
+                manufacturer: String::new(),
+                product: String::new(),
+                serial: None,
+                interface: ch347::interface_for_pid(pid),
+                bus: device.bus_number(),
+                address: device.address(),
+                is_ch347t: pid == CH347T_PID,
+            },
+        });
+    }
+}
+
+impl Hotplug<Context> for Forwarder {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        self.send(&device, true);
+    }
+
+    fn device_left(&mut self, device: Device<Context>) {
+        self.send(&device, false);
+    }
+}
+
+fn watch_hotplug(stop: &AtomicBool, on_change: &dyn Fn(DeviceEvent)) -> rusb::Result<()> {
+    let context = Context::new()?;
+    let (tx, rx) = mpsc::channel();
+
+    // Deregistered when dropped at the end of this function
+    let _registration = HotplugBuilder::new()
+        .vendor_id(CH347_VID)
+        .register(&context, Box::new(Forwarder { tx }))?;
+
+    while !stop.load(Ordering::SeqCst) {
+        context.handle_events(Some(EVENT_TIMEOUT))?;
+
+        for event in rx.try_iter() {
+            on_change(event);
+        }
+    }
+
+    Ok(())
+}
+
+fn watch_polling(stop: &AtomicBool, on_change: &dyn Fn(DeviceEvent)) {
+    let mut known = ch347::list_devices().unwrap_or_default();
+    let mut last_poll = Instant::now();
+
+    while !stop.load(Ordering::SeqCst) {
+        std::thread::sleep(EVENT_TIMEOUT);
+
+        if last_poll.elapsed() < POLL_INTERVAL {
+            continue;
+        }
+        last_poll = Instant::now();
+
+        let current = match ch347::list_devices() {
+            Ok(devices) => devices,
+            Err(_) => continue,
+        };
+
+        let same = |a: &DeviceInfo, b: &DeviceInfo| a.bus == b.bus && a.address == b.address;
+
+        for gone in known.iter().filter(|d| !current.iter().any(|c| same(c, d))) {
+            on_change(DeviceEvent { arrived: false, info: gone.clone() });
+        }
+        for new in current.iter().filter(|c| !known.iter().any(|d| same(c, d))) {
+            on_change(DeviceEvent { arrived: true, info: new.clone() });
+        }
+
+        known = current;
+    }
+}
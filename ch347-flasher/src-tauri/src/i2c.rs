@@ -0,0 +1,171 @@
+//! CH347 I2C Communication Layer
+//!
+//! I2C transfers share the SPI interface's bulk endpoints and use the
+//! CH341-style stream command set: a 0xAA packet carrying start, stop, out
+//! and in sub-commands. The reply holds one ACK status byte per byte sent,
+//! followed by the bytes read.
+
+use crate::ch347::{Ch347Device, Ch347Error, Result, PACKET_SIZE};
+
+// I2C stream command and sub-commands
+pub const CMD_I2C_STREAM: u8 = 0xAA;
+pub const I2C_STM_STA: u8 = 0x74;  // Start (or repeated start) condition
+pub const I2C_STM_STO: u8 = 0x75;  // Stop condition
+pub const I2C_STM_OUT: u8 = 0x80;  // Send bytes, bits 5:0 = count
+pub const I2C_STM_IN: u8 = 0xC0;   // Receive bytes with ACK, bits 5:0 = count (0 = one byte, NACK)
+pub const I2C_STM_SET: u8 = 0x60;  // Bus speed, bits 1:0
+pub const I2C_STM_END: u8 = 0x00;  // End of stream
+
+// Largest count a single OUT/IN sub-command can carry
+pub const I2C_STM_MAX: usize = 0x3F;
+
+// Data bytes per transaction, so a stream always fits in one packet
+pub const MAX_TRANSFER: usize = 256;
+
+// 7-bit addresses outside the reserved ranges
+pub const SCAN_FIRST: u8 = 0x08;
+pub const SCAN_LAST: u8 = 0x77;
+
+/// I2C bus speeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum I2cSpeed {
+    Khz20 = 0,
+    #[default]
+    Khz100 = 1,
+    Khz400 = 2,
+    Khz750 = 3,
+}
+
+impl I2cSpeed {
+    pub const ALL: [I2cSpeed; 4] = [
+        I2cSpeed::Khz20,
+        I2cSpeed::Khz100,
+        I2cSpeed::Khz400,
+        I2cSpeed::Khz750,
+    ];
+
+    /// SCL frequency in kHz
+    pub fn khz(self) -> u32 {
+        match self {
+            I2cSpeed::Khz20 => 20,
+            I2cSpeed::Khz100 => 100,
+            I2cSpeed::Khz400 => 400,
+            I2cSpeed::Khz750 => 750,
+        }
+    }
+
+    /// Pick the speed nearest to the requested one
+    pub fn from_khz(speed_khz: u32) -> I2cSpeed {
+        Self::ALL
+            .into_iter()
+            .min_by_key(|speed| (speed.khz() as i64 - speed_khz as i64).abs())
+            .unwrap_or_default()
+    }
+}
+
+impl Ch347Device {
+    /// Set the I2C bus speed
+    pub fn i2c_init(&mut self, speed: I2cSpeed) -> Result<()> {
+        self.write_bulk(&[CMD_I2C_STREAM, I2C_STM_SET | speed as u8, I2C_STM_END])?;
+        Ok(())
+    }
+
+    /// Write `data` to the device at 7-bit address `addr`
+    pub fn i2c_write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        self.i2c_transfer(addr, data, &mut [])
+    }
+
+    /// Write `write` (if any), then read `read.len()` bytes after a repeated start
+    pub fn i2c_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        self.i2c_transfer(addr, write, read)
+    }
+
+    /// Whether a device acknowledges its address
+    pub fn i2c_probe(&mut self, addr: u8) -> Result<bool> {
+        match self.i2c_transfer(addr, &[], &mut []) {
+            Ok(()) => Ok(true),
+            Err(Ch347Error::I2cNack(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Addresses of all devices that acknowledge on the bus
+    pub fn i2c_scan(&mut self) -> Result<Vec<u8>> {
+        let mut found = Vec::new();
+
+        for addr in SCAN_FIRST..=SCAN_LAST {
+            if self.i2c_probe(addr)? {
+                found.push(addr);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Run one start..stop transaction and check every sent byte was acknowledged
+    fn i2c_transfer(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        if write.len() > MAX_TRANSFER || read.len() > MAX_TRANSFER {
+            return Err(Ch347Error::TransferFailed("I2C transfer too long".into()));
+        }
+
+        let mut cmd = vec![CMD_I2C_STREAM, I2C_STM_STA];
+        let mut sent = 0;
+
+        // A bare address probe still sends the address in write mode
+        if !write.is_empty() || read.is_empty() {
+            let mut out = vec![addr << 1];
+            out.extend_from_slice(write);
+            push_out(&mut cmd, &out);
+            sent += out.len();
+        }
+
+        if !read.is_empty() {
+            if sent > 0 {
+                cmd.push(I2C_STM_STA);
+            }
+            push_out(&mut cmd, &[(addr << 1) | 1]);
+            sent += 1;
+
+            // All but the last byte are acknowledged; the last one is NACKed
+            let mut remaining = read.len() - 1;
+            while remaining > 0 {
+                let n = remaining.min(I2C_STM_MAX);
+                cmd.push(I2C_STM_IN | n as u8);
+                remaining -= n;
+            }
+            cmd.push(I2C_STM_IN);
+        }
+
+        cmd.push(I2C_STM_STO);
+        cmd.push(I2C_STM_END);
+
+        if cmd.len() > PACKET_SIZE {
+            return Err(Ch347Error::TransferFailed("I2C stream exceeds packet size".into()));
+        }
+
+        self.write_bulk(&cmd)?;
+
+        let expected = sent + read.len();
+        let mut resp = vec![0u8; expected.max(PACKET_SIZE)];
+        if self.read_bulk(&mut resp)? < expected {
+            return Err(Ch347Error::InvalidResponse);
+        }
+
+        // Bit 0 of each status byte is set when the byte was acknowledged
+        if resp[..sent].iter().any(|&status| status & 0x01 == 0) {
+            return Err(Ch347Error::I2cNack(addr));
+        }
+
+        read.copy_from_slice(&resp[sent..expected]);
+        Ok(())
+    }
+}
+
+/// Append OUT sub-commands carrying `data`
+fn push_out(cmd: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(I2C_STM_MAX) {
+        cmd.push(I2C_STM_OUT | chunk.len() as u8);
+        cmd.extend_from_slice(chunk);
+    }
+}
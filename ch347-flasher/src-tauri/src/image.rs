@@ -0,0 +1,273 @@
+//! Image File Formats
+//!
+//! Loads and saves flash images, choosing the file format from the extension.
+//! A trailing `.gz` or `.zst` compresses the file, e.g. `dump.bin.zst`.
+
+use crate::{hex, srec};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// Decompressed images larger than this are rejected (largest 4-byte-address parts)
+pub const MAX_IMAGE_SIZE: usize = 256 * 1024 * 1024;
+
+// zstd level for saved dumps; mostly-blank images compress well at any level
+const ZSTD_LEVEL: i32 = 3;
+
+/// Supported image file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Binary,
+    IntelHex,
+    Srec,
+}
+
+impl ImageFormat {
+    /// Pick the format from the file extension, defaulting to raw binary
+    pub fn from_path(path: &str) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "hex" | "ihex" | "ihx" => ImageFormat::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::Srec,
+            _ => ImageFormat::Binary,
+        }
+    }
+}
+
+/// Compression wrapped around an image file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Pick the compression from the file extension, returning it with the
+    /// path of the file inside (used to choose its format)
+    pub fn from_path(path: &str) -> (Self, &str) {
+        let lower = path.to_ascii_lowercase();
+
+        if lower.ends_with(".gz") {
+            (Compression::Gzip, &path[..path.len() - 3])
+        } else if lower.ends_with(".zst") {
+            (Compression::Zstd, &path[..path.len() - 4])
+        } else {
+            (Compression::None, path)
+        }
+    }
+}
+
+/// Load a flash image as raw bytes starting at flash address 0
+///
+/// Gaps between records in address-based formats are filled with 0xFF.
+pub fn load_image(path: &str) -> Result<Vec<u8>, String> {
+    let (compression, inner) = Compression::from_path(path);
+    let raw = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let bytes = decompress(compression, raw)?;
+
+    match ImageFormat::from_path(inner) {
+        ImageFormat::Binary => Ok(bytes),
+        ImageFormat::IntelHex => {
+            let text = String::from_utf8(bytes)
+                .map_err(|_| "Invalid Intel HEX file: not valid text".to_string())?;
+            let segments = hex::parse_intel_hex(&text)
+                .map_err(|e| format!("Invalid Intel HEX file: {}", e))?;
+            Ok(flatten(&segments))
+        }
+        ImageFormat::Srec => {
+            let text = String::from_utf8(bytes)
+                .map_err(|_| "Invalid S-record file: not valid text".to_string())?;
+            let segments = srec::parse_srec(&text)
+                .map_err(|e| format!("Invalid S-record file: {}", e))?;
+            Ok(flatten(&segments))
+        }
+    }
+}
+
+/// Save flash contents that were read from flash address `base`
+pub fn save_image(path: &str, base: u32, data: &[u8]) -> Result<(), String> {
+    let (compression, inner) = Compression::from_path(path);
+
+    let encoded;
+    let bytes = match ImageFormat::from_path(inner) {
+        ImageFormat::Binary => data,
+        ImageFormat::IntelHex => {
+            encoded = hex::to_intel_hex(base, data);
+            encoded.as_bytes()
+        }
+        ImageFormat::Srec => {
+            encoded = srec::to_srec(base, data);
+            encoded.as_bytes()
+        }
+    };
+
+    compress(compression, bytes)
+        .and_then(|out| std::fs::write(path, out))
+        .map_err(|e| format!("Failed to save file: {}", e))
+}
+
+/// Writes a raw binary image chunk by chunk, compressing on the fly
+///
+/// Lets whole-chip reads go straight to disk without buffering the chip.
+pub struct BinaryWriter {
+    sink: Sink,
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl BinaryWriter {
+    /// Create (or truncate) `path` for streaming, or `None` if its format
+    /// is text based and has to be generated from the whole image
+    pub fn create(path: &str) -> Result<Option<Self>, String> {
+        let (compression, inner) = Compression::from_path(path);
+        if ImageFormat::from_path(inner) != ImageFormat::Binary {
+            return Ok(None);
+        }
+
+        let file = File::create(path).map_err(|e| format!("Failed to save file: {}", e))?;
+        let out = BufWriter::new(file);
+
+        let sink = match compression {
+            Compression::None => Sink::Plain(out),
+            Compression::Gzip => Sink::Gzip(flate2::write::GzEncoder::new(out, flate2::Compression::default())),
+            Compression::Zstd => Sink::Zstd(
+                zstd::Encoder::new(out, ZSTD_LEVEL).map_err(|e| format!("Failed to save file: {}", e))?,
+            ),
+        };
+
+        Ok(Some(Self { sink }))
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        let result = match &mut self.sink {
+            Sink::Plain(out) => out.write_all(data),
+            Sink::Gzip(out) => out.write_all(data),
+            Sink::Zstd(out) => out.write_all(data),
+        };
+        result.map_err(|e| format!("Failed to save file: {}", e))
+    }
+
+    /// Finish any compression and flush everything to disk
+    pub fn finish(self) -> Result<(), String> {
+        let out = match self.sink {
+            Sink::Plain(out) => Ok(out),
+            Sink::Gzip(out) => out.finish(),
+            Sink::Zstd(out) => out.finish(),
+        };
+
+        out.and_then(|out| out.into_inner().map_err(|e| e.into_error()))
+            .and_then(|file| file.sync_all())
+            .map_err(|e| format!("Failed to save file: {}", e))
+    }
+}
+
+/// Reads an image chunk by chunk from address 0
+///
+/// Uncompressed binaries are read straight from disk, so comparing one
+/// against the chip never holds the whole file. Compressed and text based
+/// formats have no cheap length or random access and are loaded up front.
+pub struct ImageReader {
+    source: Box<dyn Read>,
+    len: usize,
+}
+
+impl ImageReader {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let (compression, inner) = Compression::from_path(path);
+
+        if compression == Compression::None && ImageFormat::from_path(inner) == ImageFormat::Binary {
+            let file = File::open(path).map_err(|e| format!("Failed to read file: {}", e))?;
+            let len = file.metadata().map_err(|e| format!("Failed to read file: {}", e))?.len() as usize;
+            return Ok(Self { source: Box::new(BufReader::new(file)), len });
+        }
+
+        let data = load_image(path)?;
+        let len = data.len();
+        Ok(Self { source: Box::new(std::io::Cursor::new(data)), len })
+    }
+
+    /// Image length in bytes
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    /// Fill as much of `buf` as the image has left, returning the byte count
+    ///
+    /// Only the final chunk comes back short; 0 means the end was reached.
+    pub fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize, String> {
+        let mut filled = 0;
+
+        while filled < buf.len() {
+            match self.source.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Failed to read file: {}", e)),
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+fn compress(compression: Compression, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Compression::Zstd => zstd::encode_all(data, ZSTD_LEVEL),
+    }
+}
+
+/// Decompress, refusing output beyond `MAX_IMAGE_SIZE`
+fn decompress(compression: Compression, raw: Vec<u8>) -> Result<Vec<u8>, String> {
+    let reader: Box<dyn Read + '_> = match compression {
+        Compression::None => return Ok(raw),
+        Compression::Gzip => Box::new(flate2::read::GzDecoder::new(&raw[..])),
+        Compression::Zstd => Box::new(
+            zstd::Decoder::new(&raw[..]).map_err(|e| format!("Failed to decompress file: {}", e))?,
+        ),
+    };
+
+    let mut out = Vec::new();
+    reader
+        .take(MAX_IMAGE_SIZE as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("Failed to decompress file: {}", e))?;
+
+    if out.len() > MAX_IMAGE_SIZE {
+        return Err(format!("Decompressed image exceeds {} bytes", MAX_IMAGE_SIZE));
+    }
+
+    Ok(out)
+}
+
+/// Lay out address/data segments in a buffer starting at address 0
+fn flatten(segments: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let len = segments
+        .iter()
+        .map(|(addr, data)| *addr as usize + data.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut image = vec![0xFFu8; len];
+    for (addr, data) in segments {
+        let start = *addr as usize;
+        image[start..start + data.len()].copy_from_slice(data);
+    }
+
+    image
+}
@@ -3,18 +3,70 @@
 //! Provides Tauri commands for the frontend GUI
 
 mod ch347;
+mod diff;
+mod dump;
+mod eeprom;
 mod flash;
+mod hex;
+mod hotplug;
+mod i2c;
+mod image;
+mod logging;
+mod mock;
+mod settings;
+mod sfdp;
+mod srec;
 
-use flash::{FlashChip, FlashProgrammer, get_flash_database};
-use parking_lot::Mutex;
+use ch347::{BitOrder, Ch347Error, SpiClock, SpiMode, SpiSettings};
+use diff::ImageDiff;
+use eeprom::{EepromChip, EepromProgrammer, get_eeprom_database};
+use hotplug::{DeviceEvent, HotplugWatcher};
+use i2c::I2cSpeed;
+use flash::{ConfigRegister, DetectOptions, DigestAlgo, FlashChip, FlashProgrammer, LockLevel, ReadMode, SpiBackend, StatusRegisters, WritePlan, STATUS_BP_MASK, get_flash_database, identify_chip, merge_chip_database, plan_erase, similar_chip_names, unknown_chip};
+use mock::MockFlash;
+use parking_lot::{Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
+use settings::Settings;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::Cell;
 use std::sync::Arc;
-use tauri::{State, Emitter, AppHandle};
+use std::time::{Duration, Instant};
+use tauri::{State, Emitter, AppHandle, Manager, RunEvent};
 
 /// Application state
 pub struct AppState {
     programmer: Mutex<Option<FlashProgrammer>>,
     current_chip: Mutex<Option<FlashChip>>,
+    chip_database: Mutex<Vec<FlashChip>>,  // Built-ins merged with any user database
+    cancel: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    busy: AtomicBool,
+    hotplug: Mutex<Option<HotplugWatcher>>,
+    settings: Mutex<Settings>,
+    settings_path: Mutex<Option<PathBuf>>,  // Set once the config dir is known
+    last_connection: Mutex<Option<LastConnection>>,
+    last_outcome: Mutex<Option<OperationOutcome>>,  // Result of the last long operation
+}
+
+/// What `reconnect` needs to reopen the last adapter as it was
+#[derive(Debug, Clone)]
+struct LastConnection {
+    options: ConnectOptions,       // Resolved serial and interface of the last connect
+    spi: Option<SpiSettings>,      // SPI settings in use when the connection dropped
+    chip_select: u8,
+    chip: Option<FlashChip>,       // Detected chip, kept across the drop
+}
+
+impl LastConnection {
+    /// Keep the state of a programmer that is about to be dropped
+    fn remember(&mut self, programmer: &FlashProgrammer, chip: Option<FlashChip>) {
+        self.spi = Some(programmer.spi_settings());
+        self.chip_select = programmer.chip_select();
+        if chip.is_some() {
+            self.chip = chip;
+        }
+    }
 }
 
 impl Default for AppState {
@@ -22,8 +74,190 @@ impl Default for AppState {
         Self {
             programmer: Mutex::new(None),
             current_chip: Mutex::new(None),
+            chip_database: Mutex::new(get_flash_database()),
+            cancel: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            busy: AtomicBool::new(false),
+            hotplug: Mutex::new(None),
+            settings: Mutex::new(Settings::default()),
+            settings_path: Mutex::new(None),
+            last_connection: Mutex::new(None),
+            last_outcome: Mutex::new(None),
+        }
+    }
+}
+
+impl AppState {
+    /// Claim the operation slot and clear any stale cancel or pause request
+    ///
+    /// Returns `None` if another operation is already running.
+    fn begin_operation(&self) -> Option<OperationGuard<'_>> {
+        if self.busy.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        self.cancel.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+        Some(OperationGuard(&self.busy))
+    }
+
+    /// Lock the programmer for a short command
+    ///
+    /// Returns `None` while a long operation runs, including before its
+    /// worker has taken the lock, so commands never interleave on the bus.
+    fn try_programmer(&self) -> Option<MutexGuard<'_, Option<FlashProgrammer>>> {
+        if self.busy.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.programmer.try_lock()
+    }
+
+    /// Wait out a pause between chunks, then report whether to stop
+    fn checkpoint(&self) -> bool {
+        flash::wait_while_paused(&self.paused, &self.cancel)
+    }
+
+    /// Drop the connection if the adapter that was just unplugged is ours
+    fn handle_device_event(&self, event: &DeviceEvent) {
+        if event.arrived {
+            return;
+        }
+
+        let mut programmer_guard = self.programmer.lock();
+        let ours = programmer_guard
+            .as_ref()
+            .is_some_and(|p| p.device().bus_address() == (event.info.bus, event.info.address));
+
+        if ours {
+            let chip = self.current_chip.lock().take();
+            if let (Some(last), Some(programmer)) = (self.last_connection.lock().as_mut(), programmer_guard.as_ref()) {
+                last.remember(programmer, chip);
+            }
+            *programmer_guard = None;
+        }
+    }
+}
+
+/// Releases the operation slot when dropped
+struct OperationGuard<'a>(&'a AtomicBool);
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+// Error returned when a command arrives while an operation holds the device
+const BUSY: &str = "Busy: another operation is in progress";
+
+/// Run a long operation on its own thread
+///
+/// The calling command is registered as async so the invoke thread stays free
+/// for status and cancel commands while the worker holds the programmer.
+fn run_operation<T, F>(state: &Arc<AppState>, op: F) -> CmdResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppState) -> CmdResult<T> + Send + 'static,
+{
+    let _guard = match state.begin_operation() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let worker_state = state.clone();
+    let worker = std::thread::Builder::new()
+        .name("flash-operation".into())
+        .spawn(move || op(&worker_state));
+
+    let result = match worker {
+        Ok(handle) => handle
+            .join()
+            .unwrap_or_else(|_| CmdResult::err("Operation failed unexpectedly")),
+        Err(e) => CmdResult::err(format!("Failed to start operation: {}", e)),
+    };
+
+    *state.last_outcome.lock() = Some(OperationOutcome::of(&result));
+    result
+}
+
+/// How the last long operation ended, kept for diagnostics reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationOutcome {
+    pub success: bool,
+    pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    pub finished_at: u64,  // Milliseconds since the Unix epoch
+}
+
+impl OperationOutcome {
+    fn of<T>(result: &CmdResult<T>) -> Self {
+        Self {
+            success: result.success,
+            error: result.error.clone(),
+            error_code: result.error_code,
+            finished_at: logging::now_millis(),
+        }
+    }
+}
+
+/// Failure category the frontend can branch on without parsing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    NotConnected,
+    NoChip,
+    UsbError,
+    VerifyFailed,
+    FileIo,
+    Cancelled,
+    Timeout,
+    SizeExceeded,
+    Busy,
+}
+
+impl ErrorCode {
+    /// Categorise a device error (write protection and unsupported commands have no code)
+    fn of(e: &Ch347Error) -> Option<Self> {
+        match e {
+            Ch347Error::Usb(rusb::Error::Timeout) | Ch347Error::Timeout(_) | Ch347Error::ChipNotResponding(_) => {
+                Some(ErrorCode::Timeout)
+            }
+            Ch347Error::Cancelled => Some(ErrorCode::Cancelled),
+            Ch347Error::VerifyFailed(_) => Some(ErrorCode::VerifyFailed),
+            Ch347Error::Unsupported(_) | Ch347Error::Locked(_) | Ch347Error::JedecUnstable(..) | Ch347Error::NotErased(_) => None,
+            _ => Some(ErrorCode::UsbError),
+        }
+    }
+}
+
+/// Error from a helper shared by several commands
+#[derive(Debug)]
+struct CmdError {
+    code: Option<ErrorCode>,
+    message: String,
+    resume_offset: Option<usize>,
+}
+
+impl CmdError {
+    fn new(code: impl Into<Option<ErrorCode>>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            resume_offset: None,
         }
     }
+
+    /// Record that everything before `offset` was written successfully
+    fn resume_at(mut self, offset: usize) -> Self {
+        self.resume_offset = Some(offset);
+        self
+    }
+}
+
+impl<T> From<CmdError> for CmdResult<T> {
+    fn from(e: CmdError) -> Self {
+        let mut result = CmdResult::fail(e.code, e.message);
+        result.resume_offset = e.resume_offset;
+        result
+    }
 }
 
 /// Result type for Tauri commands
@@ -32,6 +266,8 @@ pub struct CmdResult<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    pub resume_offset: Option<usize>,  // Where an interrupted write can continue from
 }
 
 impl<T> CmdResult<T> {
@@ -40,14 +276,22 @@ impl<T> CmdResult<T> {
             success: true,
             data: Some(data),
             error: None,
+            error_code: None,
+            resume_offset: None,
         }
     }
 
     pub fn err(msg: impl Into<String>) -> Self {
+        Self::fail(None, msg)
+    }
+
+    pub fn fail(code: impl Into<Option<ErrorCode>>, msg: impl Into<String>) -> Self {
         Self {
             success: false,
             data: None,
             error: Some(msg.into()),
+            error_code: code.into(),
+            resume_offset: None,
         }
     }
 }
@@ -59,6 +303,9 @@ pub struct DeviceInfo {
     pub vid: Option<u16>,
     pub pid: Option<u16>,
     pub name: Option<String>,
+    pub variant: String,
+    pub interface: u8,
+    pub serial: Option<String>,
 }
 
 /// Chip info for frontend
@@ -70,6 +317,39 @@ pub struct ChipInfo {
     pub jedec_id: String,
     pub size: usize,
     pub size_str: String,
+    pub summary: String,
+    pub has_unique_id: bool,
+    pub signature: Option<String>,  // 0xAB electronic signature, set by detection
+}
+
+impl DeviceInfo {
+    fn from_device(info: ch347::DeviceInfo, connected: bool) -> Self {
+        Self {
+            connected,
+            vid: Some(info.vid),
+            pid: Some(info.pid),
+            name: Some(info.product.clone()).filter(|p| !p.is_empty()),
+            variant: info.variant().into(),
+            interface: info.interface,
+            serial: info.serial,
+        }
+    }
+}
+
+impl ChipInfo {
+    fn from_chip(chip: &FlashChip) -> Self {
+        Self {
+            detected: true,
+            name: chip.name.clone(),
+            manufacturer: chip.manufacturer.clone(),
+            jedec_id: chip.jedec_hex(),
+            size: chip.size,
+            size_str: chip.size_str(),
+            summary: chip.summary(),
+            has_unique_id: chip.has_unique_id,
+            signature: None,
+        }
+    }
 }
 
 /// Progress info
@@ -79,337 +359,3551 @@ pub struct ProgressInfo {
     pub total: usize,
     pub percent: f32,
     pub operation: String,
+    pub bytes_per_sec: f32,  // 0 until a rate is known, or when not counting bytes
+    pub eta_secs: f32,       // 0 until a rate is known
+    pub device: Option<String>,  // Adapter key during `program_all`
 }
 
-// ============================================================================
-// Tauri Commands
-// ============================================================================
+/// Emits `progress` events for one operation phase, rate limited
+///
+/// An event is sent at most every 100ms and only after at least 1% more
+/// progress, so large writes don't flood the webview. The first event and
+/// the one reaching completion are always sent.
+///
+/// Throughput is an exponential moving average over the intervals between
+/// sent events, which smooths out per-chunk jitter.
+struct ProgressEmitter<'a> {
+    app: &'a AppHandle,
+    operation: String,
+    counts_bytes: bool,
+    last: Cell<Option<(Instant, usize, f32)>>,
+    rate: Cell<f32>,
+    device: Option<String>,
+}
 
-/// Connect to CH347 device
-#[tauri::command]
-fn connect(state: State<'_, Arc<AppState>>) -> CmdResult<DeviceInfo> {
-    let mut programmer_guard = state.programmer.lock();
+impl<'a> ProgressEmitter<'a> {
+    const MIN_INTERVAL: Duration = Duration::from_millis(100);
+    const MIN_STEP: f32 = 1.0;
+    const SMOOTHING: f32 = 0.3;  // Weight of the newest interval
 
-    match FlashProgrammer::new() {
-        Ok(prog) => {
-            *programmer_guard = Some(prog);
-            CmdResult::ok(DeviceInfo {
-                connected: true,
-                vid: Some(ch347::CH347_VID),
-                pid: Some(ch347::CH347T_PID),
-                name: Some("CH347".into()),
-            })
+    /// Progress measured in bytes
+    fn new(app: &'a AppHandle, operation: &str) -> Self {
+        Self {
+            app,
+            operation: operation.into(),
+            counts_bytes: true,
+            last: Cell::new(None),
+            rate: Cell::new(0.0),
+            device: None,
         }
-        Err(e) => CmdResult::err(format!("Failed to connect: {}", e)),
     }
-}
 
-/// Disconnect from device
-#[tauri::command]
-fn disconnect(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
-    let mut programmer_guard = state.programmer.lock();
-    let mut chip_guard = state.current_chip.lock();
+    /// Progress measured in steps (e.g. erase operations) rather than bytes
+    fn steps(app: &'a AppHandle, operation: &str) -> Self {
+        Self {
+            counts_bytes: false,
+            ..Self::new(app, operation)
+        }
+    }
 
-    *programmer_guard = None;
-    *chip_guard = None;
+    /// Tag events with the adapter they belong to
+    fn for_device(self, device: &str) -> Self {
+        Self {
+            device: Some(device.into()),
+            ..self
+        }
+    }
 
-    CmdResult::ok(())
-}
+    fn emit(&self, current: usize, total: usize) {
+        let percent = if total == 0 {
+            100.0
+        } else {
+            (current as f32 / total as f32) * 100.0
+        };
+        let now = Instant::now();
 
-/// Check connection status
-#[tauri::command]
-fn is_connected(state: State<'_, Arc<AppState>>) -> bool {
-    state.programmer.lock().is_some()
-}
+        let due = match self.last.get() {
+            None => true,
+            Some(_) if current >= total => true,
+            Some((at, _, last_percent)) => {
+                now.duration_since(at) >= Self::MIN_INTERVAL
+                    && percent - last_percent >= Self::MIN_STEP
+            }
+        };
+        if !due {
+            return;
+        }
 
-/// Detect flash chip
-#[tauri::command]
-fn detect_chip(state: State<'_, Arc<AppState>>) -> CmdResult<ChipInfo> {
-    let mut programmer_guard = state.programmer.lock();
-    let mut chip_guard = state.current_chip.lock();
+        if let Some((at, last_current, _)) = self.last.get() {
+            let secs = now.duration_since(at).as_secs_f32();
+            if secs > 0.0 && current > last_current {
+                let instant = (current - last_current) as f32 / secs;
+                let rate = self.rate.get();
+                if rate == 0.0 {
+                    self.rate.set(instant);
+                } else {
+                    self.rate.set(rate + Self::SMOOTHING * (instant - rate));
+                }
+            }
+        }
+        self.last.set(Some((now, current, percent)));
 
-    let programmer = match programmer_guard.as_mut() {
-        Some(p) => p,
-        None => return CmdResult::err("Not connected"),
-    };
+        let rate = self.rate.get();
+        let eta_secs = if rate > 0.0 {
+            total.saturating_sub(current) as f32 / rate
+        } else {
+            0.0
+        };
 
-    match programmer.detect() {
-        Ok(chip) => {
-            let info = ChipInfo {
-                detected: true,
-                name: chip.name.clone(),
-                manufacturer: chip.manufacturer.clone(),
-                jedec_id: format!("{:02X} {:02X} {:02X}",
-                    chip.jedec_id[0], chip.jedec_id[1], chip.jedec_id[2]),
-                size: chip.size,
-                size_str: chip.size_str(),
-            };
-            *chip_guard = Some(chip);
-            CmdResult::ok(info)
-        }
-        Err(e) => CmdResult::err(format!("Detection failed: {}", e)),
+        let _ = self.app.emit("progress", ProgressInfo {
+            current,
+            total,
+            percent,
+            operation: self.operation.clone(),
+            bytes_per_sec: if self.counts_bytes { rate } else { 0.0 },
+            eta_secs,
+            device: self.device.clone(),
+        });
     }
 }
 
-/// Read flash to file
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Optional settings applied when connecting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectOptions {
+    pub serial: Option<String>,    // Adapter to open, else the saved one
+    pub clock_khz: Option<u32>,    // SPI clock, else the saved one
+    pub mode: Option<u8>,          // SPI mode 0-3, default 0
+    pub interface: Option<u8>,     // SPI interface override, else the saved one or the default
+    pub wp_gpio: Option<u8>,       // CH347 GPIO wired to the flash WP# pin
+    pub hold_gpio: Option<u8>,     // CH347 GPIO wired to HOLD#, kept high
+}
+
+/// Connect to CH347 device, optionally selecting one by serial number
+///
+/// Without a serial the saved preferred adapter is used, and the SPI clock
+/// starts at the saved speed.
 #[tauri::command]
-fn read_flash(
+fn connect(
     state: State<'_, Arc<AppState>>,
-    app: AppHandle,
-    path: String,
-) -> CmdResult<()> {
-    let mut programmer_guard = state.programmer.lock();
-    let chip_guard = state.current_chip.lock();
+    serial: Option<String>,
+    options: Option<ConnectOptions>,
+) -> CmdResult<DeviceInfo> {
+    let mut options = options.unwrap_or_default();
+    if serial.is_some() {
+        options.serial = serial;
+    }
 
-    let programmer = match programmer_guard.as_mut() {
-        Some(p) => p,
-        None => return CmdResult::err("Not connected"),
+    connect_with(state, options)
+}
+
+/// Connect with the adapter, SPI clock and mode chosen up front
+///
+/// Anything left unset falls back to the saved settings, then to 15MHz mode 0.
+#[tauri::command]
+fn connect_with(state: State<'_, Arc<AppState>>, options: ConnectOptions) -> CmdResult<DeviceInfo> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
     };
 
-    let chip = match chip_guard.as_ref() {
-        Some(c) => c,
-        None => return CmdResult::err("No chip detected"),
+    let settings = state.settings.lock().clone();
+    let options = ConnectOptions {
+        serial: options.serial.or(settings.serial).filter(|s| !s.is_empty()),
+        clock_khz: options.clock_khz.or(Some(settings.clock_khz)),
+        interface: options.interface.or(settings.interface),
+        ..options
     };
 
-    let size = chip.size;
-    let mut data = vec![0u8; size];
+    let (mut prog, info) = match open_programmer(&options) {
+        Ok(opened) => opened,
+        Err(e) => return e.into(),
+    };
 
-    // Read in 64KB chunks for progress
-    const CHUNK_SIZE: usize = 65536;
-    let mut offset = 0;
+    log::info!("Connected to {} (bus {:03}, address {:03})", info.variant(), info.bus, info.address);
+    prog.set_cancel_flag(state.cancel.clone());
+    prog.set_pause_flag(state.paused.clone());
+    *programmer_guard = Some(prog);
+
+    // Reopen this exact adapter on reconnect, even if it was picked as "first found"
+    *state.last_connection.lock() = Some(LastConnection {
+        options: ConnectOptions { serial: info.serial.clone().or(options.serial.clone()), ..options },
+        spi: None,
+        chip_select: 1,
+        chip: None,
+    });
 
-    while offset < size {
-        let chunk_len = std::cmp::min(CHUNK_SIZE, size - offset);
+    CmdResult::ok(DeviceInfo::from_device(info, true))
+}
+
+/// Open the adapter described by `options` and apply its SPI and GPIO settings
+fn open_programmer(options: &ConnectOptions) -> Result<(FlashProgrammer, ch347::DeviceInfo), CmdError> {
+    let mode = match options.mode {
+        None => SpiMode::default(),
+        Some(number) => SpiMode::from_number(number)
+            .ok_or_else(|| CmdError::new(None, format!("Invalid SPI mode {} (0-3)", number)))?,
+    };
+
+    let result = match (options.serial.as_deref(), options.interface) {
+        (serial, Some(interface)) => FlashProgrammer::open_with_interface(serial, interface),
+        (Some(serial), None) => FlashProgrammer::open_by_serial(serial),
+        (None, None) => FlashProgrammer::new(),
+    };
 
-        if let Err(e) = programmer.read(offset as u32, &mut data[offset..offset + chunk_len]) {
-            return CmdResult::err(format!("Read error at 0x{:06X}: {}", offset, e));
+    let mut prog = match result {
+        Ok(prog) => prog,
+        Err(e @ Ch347Error::Usb(rusb::Error::NotFound)) if options.interface.is_none() => {
+            return Err(CmdError::new(
+                ErrorCode::of(&e),
+                format!("Failed to connect: {} (the SPI function may be on another interface; set an interface override)", e),
+            ));
         }
+        Err(e) => return Err(CmdError::new(ErrorCode::of(&e), format!("Failed to connect: {}", e))),
+    };
 
-        offset += chunk_len;
+    let info = prog
+        .device()
+        .get_info()
+        .map_err(|e| CmdError::new(ErrorCode::of(&e), format!("Failed to read device info: {}", e)))?;
 
-        // Send progress
-        let _ = app.emit("progress", ProgressInfo {
-            current: offset,
-            total: size,
-            percent: (offset as f32 / size as f32) * 100.0,
-            operation: "Reading".into(),
-        });
+    let clock = options.clock_khz.map(SpiClock::from_khz).unwrap_or_default();
+    if clock != SpiClock::default() || mode != SpiMode::default() {
+        prog.configure_spi(clock, mode)
+            .map_err(|e| CmdError::new(ErrorCode::of(&e), format!("Failed to configure SPI: {}", e)))?;
     }
 
-    // Write to file
-    if let Err(e) = std::fs::write(&path, &data) {
-        return CmdResult::err(format!("Failed to save file: {}", e));
-    }
+    prog.set_control_pins(options.wp_gpio, options.hold_gpio)
+        .map_err(|e| CmdError::new(ErrorCode::of(&e), format!("Failed to configure WP#/HOLD# GPIO: {}", e)))?;
 
-    CmdResult::ok(())
+    Ok((prog, info))
 }
 
-/// Write flash from file
-#[tauri::command]
-fn write_flash(
-    state: State<'_, Arc<AppState>>,
-    app: AppHandle,
-    path: String,
-    verify: bool,
-) -> CmdResult<()> {
-    let mut programmer_guard = state.programmer.lock();
-    let chip_guard = state.current_chip.lock();
-
-    let programmer = match programmer_guard.as_mut() {
-        Some(p) => p,
-        None => return CmdResult::err("Not connected"),
-    };
+/// Result of `reconnect`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reconnected {
+    pub device: DeviceInfo,
+    pub chip: Option<ChipInfo>,  // The chip carried over from before the drop
+}
 
-    let chip = match chip_guard.as_ref() {
-        Some(c) => c.clone(),
-        None => return CmdResult::err("No chip detected"),
+/// Reopen the adapter from the last connect after a USB drop
+///
+/// SPI settings, chip select and the detected chip carry over, so reads and
+/// writes can resume without detecting again. The chip's JEDEC ID is read
+/// fresh and must match the one detected before.
+#[tauri::command]
+fn reconnect(state: State<'_, Arc<AppState>>) -> CmdResult<Reconnected> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
     };
+    let mut chip_guard = state.current_chip.lock();
 
-    // Read file
-    let data = match std::fs::read(&path) {
-        Ok(d) => d,
-        Err(e) => return CmdResult::err(format!("Failed to read file: {}", e)),
+    let mut last = match state.last_connection.lock().clone() {
+        Some(last) => last,
+        None => return CmdResult::err("Nothing to reconnect to: connect first"),
     };
 
-    if data.len() > chip.size {
-        return CmdResult::err(format!(
-            "File size ({}) exceeds chip size ({})",
-            data.len(),
-            chip.size
-        ));
+    // Release the stale handle so its interface can be claimed again
+    if let Some(old) = programmer_guard.take() {
+        last.remember(&old, chip_guard.take());
     }
 
-    let size = data.len();
-
-    // Erase required sectors
-    let sectors = (size + chip.sector_size - 1) / chip.sector_size;
-    let _ = app.emit("progress", ProgressInfo {
-        current: 0,
-        total: sectors,
-        percent: 0.0,
-        operation: "Erasing".into(),
-    });
+    let (mut prog, info) = match open_programmer(&last.options) {
+        Ok(opened) => opened,
+        Err(e) => return e.into(),
+    };
 
-    for i in 0..sectors {
-        let addr = (i * chip.sector_size) as u32;
-        if let Err(e) = programmer.erase_sector(addr) {
-            return CmdResult::err(format!("Erase error at 0x{:06X}: {}", addr, e));
+    if let Some(spi) = last.spi {
+        let restored = prog
+            .configure_spi(spi.clock, spi.mode)
+            .and_then(|()| prog.set_bit_order(spi.bit_order));
+        if let Err(e) = restored {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Failed to configure SPI: {}", e));
         }
+    }
 
-        let _ = app.emit("progress", ProgressInfo {
-            current: i + 1,
-            total: sectors,
-            percent: ((i + 1) as f32 / sectors as f32) * 100.0,
-            operation: "Erasing".into(),
-        });
+    if let Err(e) = prog.set_chip_select(last.chip_select) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to select CS{}: {}", last.chip_select, e));
     }
 
-    // Write data
-    const PAGE_SIZE: usize = 256;
-    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    prog.set_cancel_flag(state.cancel.clone());
+    prog.set_pause_flag(state.paused.clone());
+    let mut chip_info = None;
 
-    for i in 0..pages {
-        let offset = i * PAGE_SIZE;
-        let addr = offset as u32;
-        let chunk_len = std::cmp::min(PAGE_SIZE, size - offset);
+    if let Some(chip) = last.chip.take() {
+        let id = match prog.read_jedec_id() {
+            Ok(id) => id,
+            Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Failed to read JEDEC ID: {}", e)),
+        };
 
-        if let Err(e) = programmer.program_page(addr, &data[offset..offset + chunk_len]) {
-            return CmdResult::err(format!("Write error at 0x{:06X}: {}", addr, e));
+        if id.bytes() != chip.jedec_id {
+            // Stay connected, but the old chip no longer applies
+            *programmer_guard = Some(prog);
+            return CmdResult::fail(ErrorCode::NoChip, format!(
+                "Chip changed since detection (was {}, now {}); detect again",
+                chip.jedec_hex(),
+                id
+            ));
         }
 
-        let _ = app.emit("progress", ProgressInfo {
-            current: i + 1,
-            total: pages,
-            percent: ((i + 1) as f32 / pages as f32) * 100.0,
-            operation: "Writing".into(),
-        });
+        chip_info = Some(ChipInfo::from_chip(&chip));
+        prog.set_chip(chip.clone());
+        *chip_guard = Some(chip);
     }
 
-    // Verify if requested
-    if verify {
-        let _ = app.emit("progress", ProgressInfo {
-            current: 0,
-            total: size,
-            percent: 0.0,
-            operation: "Verifying".into(),
-        });
-
-        const CHUNK_SIZE: usize = 4096;
-        let mut read_buf = vec![0u8; CHUNK_SIZE];
-        let mut offset = 0;
-
-        while offset < size {
-            let chunk_len = std::cmp::min(CHUNK_SIZE, size - offset);
-
-            if let Err(e) = programmer.read(offset as u32, &mut read_buf[..chunk_len]) {
-                return CmdResult::err(format!("Verify read error at 0x{:06X}: {}", offset, e));
-            }
+    log::info!("Reconnected to {} (bus {:03}, address {:03})", info.variant(), info.bus, info.address);
+    *programmer_guard = Some(prog);
+    CmdResult::ok(Reconnected {
+        device: DeviceInfo::from_device(info, true),
+        chip: chip_info,
+    })
+}
 
-            if read_buf[..chunk_len] != data[offset..offset + chunk_len] {
-                return CmdResult::err(format!("Verification failed at 0x{:06X}", offset));
-            }
+/// Disconnect from device
+#[tauri::command]
+fn disconnect(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+    let mut chip_guard = state.current_chip.lock();
 
-            offset += chunk_len;
+    if programmer_guard.take().is_some() {
+        log::info!("Disconnected");
+    }
+    *chip_guard = None;
 
-            let _ = app.emit("progress", ProgressInfo {
-                current: offset,
-                total: size,
-                percent: (offset as f32 / size as f32) * 100.0,
-                operation: "Verifying".into(),
-            });
-        }
+    // A deliberate disconnect ends the session; reconnect must detect again
+    if let Some(last) = state.last_connection.lock().as_mut() {
+        last.chip = None;
     }
 
     CmdResult::ok(())
 }
 
-/// Erase entire chip
+/// Check connection status
 #[tauri::command]
-fn erase_chip(
-    state: State<'_, Arc<AppState>>,
-    app: AppHandle,
-) -> CmdResult<()> {
-    let mut programmer_guard = state.programmer.lock();
+fn is_connected(state: State<'_, Arc<AppState>>) -> bool {
+    match state.try_programmer() {
+        Some(guard) => guard.is_some(),
+        // Held by a running operation, which requires a connection
+        None => true,
+    }
+}
+
+/// Set SPI clock speed, returning the actually selected frequency in Hz
+#[tauri::command]
+fn set_clock_speed(state: State<'_, Arc<AppState>>, speed_khz: u32) -> CmdResult<u32> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
 
     let programmer = match programmer_guard.as_mut() {
         Some(p) => p,
-        None => return CmdResult::err("Not connected"),
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
     };
 
-    let _ = app.emit("progress", ProgressInfo {
-        current: 0,
-        total: 1,
-        percent: 0.0,
-        operation: "Erasing chip...".into(),
-    });
-
-    if let Err(e) = programmer.erase_chip() {
-        return CmdResult::err(format!("Erase failed: {}", e));
+    let clock = SpiClock::from_khz(speed_khz);
+    match programmer.set_clock(clock) {
+        Ok(()) => CmdResult::ok(clock.hz()),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to set clock: {}", e)),
     }
+}
 
-    let _ = app.emit("progress", ProgressInfo {
-        current: 1,
-        total: 1,
-        percent: 100.0,
-        operation: "Erase complete".into(),
-    });
+/// Current SPI bus configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpiConfig {
+    pub clock_hz: u32,
+    pub mode: u8,  // SPI mode 0-3
+    pub lsb_first: bool,
+}
 
-    CmdResult::ok(())
+impl SpiConfig {
+    fn of(programmer: &FlashProgrammer) -> Self {
+        programmer.spi_settings().into()
+    }
 }
 
-/// Verify flash against file
+impl From<SpiSettings> for SpiConfig {
+    fn from(settings: SpiSettings) -> Self {
+        Self {
+            clock_hz: settings.clock.hz(),
+            mode: settings.mode.number(),
+            lsb_first: settings.bit_order == BitOrder::LsbFirst,
+        }
+    }
+}
+
+/// Get the SPI clock, mode and bit order as reported by the CH347
 #[tauri::command]
-fn verify_flash(
-    state: State<'_, Arc<AppState>>,
-    app: AppHandle,
-    path: String,
-) -> CmdResult<bool> {
-    let mut programmer_guard = state.programmer.lock();
+fn get_spi_config(state: State<'_, Arc<AppState>>) -> CmdResult<SpiConfig> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
 
     let programmer = match programmer_guard.as_mut() {
         Some(p) => p,
-        None => return CmdResult::err("Not connected"),
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
     };
 
-    // Read file
-    let data = match std::fs::read(&path) {
-        Ok(d) => d,
-        Err(e) => return CmdResult::err(format!("Failed to read file: {}", e)),
-    };
+    match programmer.device_mut().read_spi_config() {
+        Ok(settings) => CmdResult::ok(settings.into()),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read SPI config: {}", e)),
+    }
+}
 
-    let size = data.len();
-    const CHUNK_SIZE: usize = 4096;
-    let mut read_buf = vec![0u8; CHUNK_SIZE];
-    let mut offset = 0;
+/// Set the SPI mode (CPOL/CPHA), re-initializing the SPI interface
+///
+/// Mode 0 suits nearly all flashes; some parts and level-shifted setups
+/// need mode 3.
+#[tauri::command]
+fn set_spi_mode(state: State<'_, Arc<AppState>>, mode: u8) -> CmdResult<SpiConfig> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
 
-    while offset < size {
-        let chunk_len = std::cmp::min(CHUNK_SIZE, size - offset);
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
 
-        if let Err(e) = programmer.read(offset as u32, &mut read_buf[..chunk_len]) {
-            return CmdResult::err(format!("Read error at 0x{:06X}: {}", offset, e));
-        }
+    let spi_mode = match SpiMode::from_number(mode) {
+        Some(m) => m,
+        None => return CmdResult::err(format!("Invalid SPI mode {} (0-3)", mode)),
+    };
 
-        if read_buf[..chunk_len] != data[offset..offset + chunk_len] {
-            return CmdResult::ok(false);
+    match programmer.set_spi_mode(spi_mode) {
+        Ok(()) => {
+            log::info!("SPI mode {}", mode);
+            CmdResult::ok(SpiConfig::of(programmer))
         }
-
-        offset += chunk_len;
-
-        let _ = app.emit("progress", ProgressInfo {
-            current: offset,
-            total: size,
-            percent: (offset as f32 / size as f32) * 100.0,
-            operation: "Verifying".into(),
-        });
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to set SPI mode: {}", e)),
     }
-
-    CmdResult::ok(true)
 }
 
-/// Get flash chip database
+/// Switch the SPI bit order between MSB first (default) and LSB first
 #[tauri::command]
-fn get_chip_database() -> Vec<FlashChip> {
-    get_flash_database()
+fn set_bit_order(state: State<'_, Arc<AppState>>, lsb_first: bool) -> CmdResult<SpiConfig> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let bit_order = if lsb_first { BitOrder::LsbFirst } else { BitOrder::MsbFirst };
+    match programmer.set_bit_order(bit_order) {
+        Ok(()) => {
+            log::info!("SPI bit order {:?}", bit_order);
+            CmdResult::ok(SpiConfig::of(programmer))
+        }
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to set bit order: {}", e)),
+    }
+}
+
+/// Set how many times transient USB errors are retried
+#[tauri::command]
+fn set_usb_retries(state: State<'_, Arc<AppState>>, retries: u32) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    programmer.device_mut().set_usb_retries(retries);
+    CmdResult::ok(())
+}
+
+/// Set the USB bulk transfer timeout in milliseconds
+#[tauri::command]
+fn set_usb_timeout(state: State<'_, Arc<AppState>>, timeout_ms: u64) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if timeout_ms == 0 {
+        return CmdResult::err("Timeout must be greater than zero");
+    }
+
+    programmer.device_mut().set_timeout(std::time::Duration::from_millis(timeout_ms));
+    CmdResult::ok(())
+}
+
+/// Set how long a status of 0xFF is tolerated while waiting for the chip
+///
+/// Waits fail with "chip not responding" after this long instead of running
+/// to the full erase timeout on a dead or disconnected part.
+#[tauri::command]
+fn set_stuck_grace(state: State<'_, Arc<AppState>>, grace_ms: u64) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if grace_ms == 0 {
+        return CmdResult::err("Grace period must be greater than zero");
+    }
+
+    programmer.set_stuck_grace(std::time::Duration::from_millis(grace_ms));
+    CmdResult::ok(())
+}
+
+/// Check before each page program that the target bytes are erased
+///
+/// Programming can only clear bits, so data written over stale contents
+/// comes back wrong. With the check on, such a write fails at the first
+/// byte that would need a 0 turned into a 1. Costs a read per page.
+#[tauri::command]
+fn set_strict_erase_check(state: State<'_, Arc<AppState>>, enabled: bool) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    programmer.set_strict_erase_check(enabled);
+    CmdResult::ok(())
+}
+
+/// Change which backend log records are forwarded to the frontend
+#[tauri::command]
+fn set_log_level(level: String) -> CmdResult<()> {
+    match logging::set_level(&level) {
+        Ok(_) => CmdResult::ok(()),
+        Err(e) => CmdResult::err(e),
+    }
+}
+
+/// Re-read the settings file and apply its log level
+///
+/// A missing or invalid file yields the defaults.
+#[tauri::command]
+fn load_settings(state: State<'_, Arc<AppState>>) -> CmdResult<Settings> {
+    let loaded = match state.settings_path.lock().as_deref() {
+        Some(path) => settings::load(path),
+        None => Settings::default(),
+    };
+
+    apply_settings(&state, loaded.clone());
+    CmdResult::ok(loaded)
+}
+
+/// Save settings to the config directory and apply them
+///
+/// The clock and serial take effect on the next connect.
+#[tauri::command]
+fn save_settings(state: State<'_, Arc<AppState>>, settings: Settings) -> CmdResult<()> {
+    let path = match state.settings_path.lock().clone() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::FileIo, "Config directory unavailable"),
+    };
+
+    if let Err(e) = logging::set_level(&settings.log_level) {
+        return CmdResult::err(e);
+    }
+
+    if let Err(e) = settings::save(&path, &settings) {
+        return CmdResult::fail(ErrorCode::FileIo, e);
+    }
+
+    *state.settings.lock() = settings;
+    CmdResult::ok(())
+}
+
+fn apply_settings(state: &AppState, settings: Settings) {
+    if let Err(e) = logging::set_level(&settings.log_level) {
+        log::warn!("Ignoring saved log level: {}", e);
+    }
+    *state.settings.lock() = settings;
+}
+
+/// Read the raw JEDEC ID as hex (e.g. "EF4018") without looking it up
+///
+/// Unlike `detect_chip` this leaves the current chip alone. An all 0x00 or
+/// all 0xFF reply is reported as no chip responding.
+#[tauri::command]
+fn read_jedec(state: State<'_, Arc<AppState>>) -> CmdResult<String> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.read_jedec_id() {
+        Ok(id) => CmdResult::ok(id.to_string()),
+        Err(Ch347Error::DeviceNotFound) => {
+            CmdResult::fail(ErrorCode::NoChip, "No chip responded (JEDEC ID all 0x00 or 0xFF)")
+        }
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read JEDEC ID: {}", e)),
+    }
+}
+
+/// Detect flash chip
+///
+/// `robust` reads the JEDEC ID several times and refuses to identify the
+/// chip unless the reads agree.
+#[tauri::command]
+fn detect_chip(state: State<'_, Arc<AppState>>, reset: Option<bool>, robust: Option<bool>) -> CmdResult<ChipInfo> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let options = DetectOptions {
+        reset_first: reset.unwrap_or(false),
+        robust: robust.unwrap_or(false),
+    };
+
+    run_detection(&state, programmer, options)
+}
+
+/// Switch to the flash on CS1 or CS2 and detect it
+#[tauri::command]
+fn set_chip_select(state: State<'_, Arc<AppState>>, line: u8) -> CmdResult<ChipInfo> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.set_chip_select(line) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to select CS{}: {}", line, e));
+    }
+
+    // The chip on the old line no longer applies, even if detection fails
+    *state.current_chip.lock() = None;
+    log::info!("Using chip select CS{}", line);
+
+    run_detection(&state, programmer, DetectOptions::default())
+}
+
+/// Identify the chip and make it the current one
+fn run_detection(state: &AppState, programmer: &mut FlashProgrammer, options: DetectOptions) -> CmdResult<ChipInfo> {
+    let mut chip_guard = state.current_chip.lock();
+    let database = state.chip_database.lock().clone();
+
+    match programmer.detect(&database, options) {
+        Ok(chip) => {
+            log::info!("Detected {}", chip);
+            let mut info = ChipInfo::from_chip(&chip);
+
+            match programmer.read_electronic_signature() {
+                Ok(signature) => info.signature = Some(format!("{:02X}", signature)),
+                Err(e) => log::warn!("Electronic signature unavailable: {}", e),
+            }
+
+            *chip_guard = Some(chip);
+            CmdResult::ok(info)
+        }
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Detection failed: {}", e)),
+    }
+}
+
+/// A `diagnostics` step that failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepError {
+    pub step: String,
+    pub error: String,
+}
+
+/// Everything the read-only identification steps return, for bug reports
+///
+/// Fields are `None` when their step failed; the reason is in `errors`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    pub jedec_id: Option<String>,
+    pub manufacturer: Option<String>,
+    pub signature: Option<String>,       // 0xAB electronic signature
+    pub status: Option<StatusRegisters>,
+    pub sfdp_present: bool,
+    pub sfdp_size: Option<usize>,        // Density from the Basic Flash Parameter Table
+    pub database_match: Option<String>,  // Chip name
+    pub guessed_size: Option<usize>,
+    pub size_source: Option<String>,     // "database", "sfdp" or "id"
+    pub errors: Vec<StepError>,
+}
+
+impl DiagnosticReport {
+    fn failed(&mut self, step: &str, e: Ch347Error) {
+        self.errors.push(StepError { step: step.into(), error: e.to_string() });
+    }
+}
+
+/// Run every read-only identification step and report them together
+///
+/// Unlike detection, a failing step does not stop the others and the
+/// current chip is left alone.
+#[tauri::command]
+fn diagnostics(state: State<'_, Arc<AppState>>) -> CmdResult<DiagnosticReport> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let mut report = DiagnosticReport::default();
+
+    let id = match programmer.read_jedec_id() {
+        Ok(id) => {
+            report.jedec_id = Some(id.to_string());
+            report.manufacturer = Some(id.manufacturer().into());
+            Some(id)
+        }
+        Err(e) => {
+            report.failed("jedec_id", e);
+            None
+        }
+    };
+
+    match programmer.read_electronic_signature() {
+        Ok(signature) => report.signature = Some(format!("{:02X}", signature)),
+        Err(e) => report.failed("signature", e),
+    }
+
+    match programmer.read_status_registers() {
+        Ok(regs) => report.status = Some(regs),
+        Err(e) => report.failed("status", e),
+    }
+
+    let params = match programmer.read_sfdp() {
+        Ok(table) => {
+            report.sfdp_present = true;
+            sfdp::parse_sfdp(&table)
+        }
+        Err(Ch347Error::Unsupported(_)) => None,
+        Err(e) => {
+            report.failed("sfdp", e);
+            None
+        }
+    };
+    report.sfdp_size = params.as_ref().map(|p| p.size);
+
+    // Same precedence as detection: database, then SFDP, then the ID's capacity byte
+    if let Some(id) = id {
+        let database = state.chip_database.lock();
+        let known = if id.bank == 0 { identify_chip(&database, &id.bytes()) } else { None };
+
+        let (size, source) = match (&known, &params) {
+            (Some(chip), _) => (chip.size, "database"),
+            (None, Some(p)) => (p.size, "sfdp"),
+            (None, None) => (unknown_chip(id).size, "id"),
+        };
+
+        report.database_match = known.map(|chip| chip.name);
+        report.guessed_size = Some(size);
+        report.size_source = Some(source.into());
+    }
+
+    CmdResult::ok(report)
+}
+
+/// Override the detected chip with a database entry chosen by name
+///
+/// Only the geometry used by later operations changes; the hardware is not touched.
+#[tauri::command]
+fn select_chip(state: State<'_, Arc<AppState>>, name: String) -> CmdResult<ChipInfo> {
+    let database = state.chip_database.lock().clone();
+
+    let chip = match database.iter().find(|c| c.name.eq_ignore_ascii_case(name.trim())) {
+        Some(c) => c.clone(),
+        None => {
+            let similar = similar_chip_names(&database, &name);
+            return if similar.is_empty() {
+                CmdResult::err(format!("Unknown chip \"{}\"", name))
+            } else {
+                CmdResult::err(format!("Unknown chip \"{}\". Did you mean: {}?", name, similar.join(", ")))
+            };
+        }
+    };
+
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    if let Some(programmer) = programmer_guard.as_mut() {
+        programmer.set_chip(chip.clone());
+    }
+
+    let info = ChipInfo::from_chip(&chip);
+    *state.current_chip.lock() = Some(chip);
+
+    CmdResult::ok(info)
+}
+
+/// Issue a software reset to the flash chip
+#[tauri::command]
+fn reset_chip(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.reset() {
+        Ok(()) => CmdResult::ok(()),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Reset failed: {}", e)),
+    }
+}
+
+/// Put the flash chip into deep power-down
+#[tauri::command]
+fn power_down(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.power_down() {
+        Ok(()) => CmdResult::ok(()),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Power-down failed: {}", e)),
+    }
+}
+
+/// Wake the flash chip from deep power-down, returning its electronic signature
+#[tauri::command]
+fn wake_up(state: State<'_, Arc<AppState>>) -> CmdResult<u8> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.release_power_down() {
+        Ok(signature) => CmdResult::ok(signature),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Wake-up failed: {}", e)),
+    }
+}
+
+/// Read the flash unique ID as a hex string
+#[tauri::command]
+fn read_unique_id(state: State<'_, Arc<AppState>>) -> CmdResult<String> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.read_unique_id() {
+        Ok(id) => CmdResult::ok(::hex::encode_upper(id)),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read unique ID: {}", e)),
+    }
+}
+
+/// Parameter table listed in the SFDP header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SfdpTable {
+    pub id: u16,        // 0xFF00 = JEDEC Basic Flash Parameter Table
+    pub major: u8,
+    pub offset: usize,  // SFDP address of the table
+    pub length: usize,  // Bytes
+}
+
+/// Outcome of `dump_sfdp`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SfdpDump {
+    pub size: usize,  // Bytes written to the file
+    pub header_count: usize,
+    pub tables: Vec<SfdpTable>,
+}
+
+/// Save the raw SFDP region (header and every parameter table) to a file
+#[tauri::command]
+fn dump_sfdp(state: State<'_, Arc<AppState>>, path: String) -> CmdResult<SfdpDump> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    // A missing signature (usually all 0xFF) means the chip has no SFDP
+    let table = match programmer.read_sfdp() {
+        Ok(t) => t,
+        Err(Ch347Error::Unsupported(_)) => return CmdResult::err("No SFDP: the chip did not return an SFDP signature"),
+        Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Failed to read SFDP: {}", e)),
+    };
+
+    let headers = sfdp::parse_headers(&table).unwrap_or_default();
+
+    if let Err(e) = std::fs::write(&path, &table) {
+        return CmdResult::fail(ErrorCode::FileIo, format!("Failed to save file: {}", e));
+    }
+
+    log::info!("Saved {} bytes of SFDP to {}", table.len(), path);
+
+    CmdResult::ok(SfdpDump {
+        size: table.len(),
+        header_count: headers.len(),
+        tables: headers
+            .iter()
+            .map(|h| SfdpTable {
+                id: h.id,
+                major: h.major,
+                offset: h.pointer,
+                length: h.length_dwords * 4,
+            })
+            .collect(),
+    })
+}
+
+/// Read status registers 1-3
+#[tauri::command]
+fn read_status_registers(state: State<'_, Arc<AppState>>) -> CmdResult<StatusRegisters> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.read_status_registers() {
+        Ok(regs) => CmdResult::ok(regs),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read status registers: {}", e)),
+    }
+}
+
+/// Write status register 1, 2 or 3 and return the registers read back
+///
+/// `volatile` uses the 0x50 write enable so the change is lost at power-off.
+#[tauri::command]
+fn write_status_register(
+    state: State<'_, Arc<AppState>>,
+    reg: u8,
+    value: u8,
+    volatile: Option<bool>,
+) -> CmdResult<StatusRegisters> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.write_status(reg, value, volatile.unwrap_or(false)) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to write status register {}: {}", reg, e));
+    }
+
+    match programmer.read_status_registers() {
+        Ok(regs) => CmdResult::ok(regs),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read status registers: {}", e)),
+    }
+}
+
+/// Read the configuration register of a Spansion, Macronix or Micron chip
+///
+/// The opcode and width follow the `config_register` field of the detected
+/// chip's database entry (see `ConfigRegisterKind`); other parts report
+/// unsupported.
+#[tauri::command]
+fn read_config_register(state: State<'_, Arc<AppState>>) -> CmdResult<ConfigRegister> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.read_config_register() {
+        Ok(reg) => CmdResult::ok(reg),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read configuration register: {}", e)),
+    }
+}
+
+/// Write the configuration register and return it read back
+///
+/// Used to set QE or the address mode on Spansion/Macronix/Micron parts.
+/// Micron NVCR changes only take effect after a power cycle.
+#[tauri::command]
+fn write_config_register(state: State<'_, Arc<AppState>>, value: u16) -> CmdResult<ConfigRegister> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.write_config_register(value) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to write configuration register: {}", e));
+    }
+
+    match programmer.read_config_register() {
+        Ok(reg) => CmdResult::ok(reg),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read configuration register: {}", e)),
+    }
+}
+
+/// Clear block protection so the whole array can be written
+///
+/// With `volatile` the protection returns at the next power cycle, which
+/// suits one-off flashing and spares the non-volatile register.
+#[tauri::command]
+fn unlock_flash(state: State<'_, Arc<AppState>>, volatile: Option<bool>) -> CmdResult<StatusRegisters> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.unlock(volatile.unwrap_or(false)) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to unlock flash: {}", e));
+    }
+
+    match programmer.read_status_registers() {
+        Ok(regs) => CmdResult::ok(regs),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read status registers: {}", e)),
+    }
+}
+
+/// Set block protection over the whole array
+#[tauri::command]
+fn lock_flash(state: State<'_, Arc<AppState>>) -> CmdResult<StatusRegisters> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.lock_all() {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to lock flash: {}", e));
+    }
+
+    match programmer.read_status_registers() {
+        Ok(regs) => CmdResult::ok(regs),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read status registers: {}", e)),
+    }
+}
+
+/// Read a security (OTP) register, index 0..=2
+#[tauri::command]
+fn read_otp(state: State<'_, Arc<AppState>>, register: u8) -> CmdResult<Vec<u8>> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.read_security_register(register) {
+        Ok(data) => CmdResult::ok(data),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read security register {}: {}", register, e)),
+    }
+}
+
+/// Erase and reprogram a security (OTP) register, index 0..=2
+#[tauri::command]
+fn write_otp(state: State<'_, Arc<AppState>>, register: u8, data: Vec<u8>) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    if let Err(e) = programmer.erase_security_register(register) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to erase security register {}: {}", register, e));
+    }
+
+    if let Err(e) = programmer.program_security_register(register, &data) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to program security register {}: {}", register, e));
+    }
+
+    match programmer.read_security_register(register) {
+        Ok(readback) if readback[..data.len()] == data[..] => CmdResult::ok(()),
+        Ok(_) => CmdResult::fail(ErrorCode::VerifyFailed, format!("Verification of security register {} failed", register)),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read security register {}: {}", register, e)),
+    }
+}
+
+/// Read flash to file, optionally using a fast read mode
+///
+/// Binary images (compressed or not) are streamed to disk a chunk at a
+/// time; HEX and S-record output is generated from the whole image.
+#[tauri::command(async)]
+fn read_flash(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    read_mode: Option<ReadMode>,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let mut writer = match image::BinaryWriter::create(&path) {
+            Ok(Some(w)) => w,
+            Ok(None) => {
+                let data = match read_range(state, programmer, &app, 0, chip.size, read_mode) {
+                    Ok(d) => d,
+                    Err(e) => return e.into(),
+                };
+
+                return match image::save_image(&path, 0, &data) {
+                    Ok(()) => CmdResult::ok(()),
+                    Err(e) => CmdResult::fail(ErrorCode::FileIo, e),
+                };
+            }
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        let result = read_chunks(state, programmer, &app, 0, chip.size, read_mode, |chunk| {
+            writer.write(chunk).map_err(|e| CmdError::new(ErrorCode::FileIo, e))
+        })
+        .and_then(|()| writer.finish().map_err(|e| CmdError::new(ErrorCode::FileIo, e)));
+
+        // Don't leave a truncated dump behind
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&path);
+            return e.into();
+        }
+
+        CmdResult::ok(())
+    })
+}
+
+/// Read a region of flash to file
+#[tauri::command(async)]
+fn read_region(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    offset: usize,
+    len: usize,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        let data = match read_range(state, programmer, &app, offset, len, None) {
+            Ok(d) => d,
+            Err(e) => return e.into(),
+        };
+
+        if let Err(e) = image::save_image(&path, offset as u32, &data) {
+            return CmdResult::fail(ErrorCode::FileIo, e);
+        }
+
+        CmdResult::ok(())
+    })
+}
+
+// Largest range read_to_buffer returns over IPC
+const MAX_BUFFER_READ: usize = 1024 * 1024;
+
+/// Read a region of flash and return the bytes instead of saving them
+#[tauri::command(async)]
+fn read_to_buffer(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+) -> CmdResult<Vec<u8>> {
+    if len > MAX_BUFFER_READ {
+        return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+            "Length 0x{:X} exceeds the {} byte limit for in-memory reads",
+            len, MAX_BUFFER_READ
+        ));
+    }
+
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        match read_range(state, programmer, &app, offset, len, None) {
+            Ok(data) => CmdResult::ok(data),
+            Err(e) => e.into(),
+        }
+    })
+}
+
+// Largest range hex_dump formats
+const MAX_HEX_DUMP: usize = 4096;
+
+/// Read a small range and return it as an `xxd`-style dump
+#[tauri::command]
+fn hex_dump(state: State<'_, Arc<AppState>>, offset: usize, len: usize) -> CmdResult<String> {
+    if len > MAX_HEX_DUMP {
+        return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+            "Length 0x{:X} exceeds the {} byte limit for hex dumps",
+            len, MAX_HEX_DUMP
+        ));
+    }
+
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+    let chip_guard = state.current_chip.lock();
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let chip = match chip_guard.as_ref() {
+        Some(c) => c,
+        None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+    };
+
+    if !chip.contains_range(offset, len) {
+        return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+            "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+            offset, len, chip.size
+        ));
+    }
+
+    let mut data = vec![0u8; len];
+    match programmer.read(offset as u32, &mut data) {
+        Ok(()) => CmdResult::ok(dump::hex_dump(offset as u32, &data)),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", offset, e)),
+    }
+}
+
+/// Read `len` bytes from `offset` in 64KB chunks, emitting progress for the range
+fn read_range(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    len: usize,
+    read_mode: Option<ReadMode>,
+) -> Result<Vec<u8>, CmdError> {
+    let mut data = Vec::with_capacity(len);
+    read_chunks(state, programmer, app, offset, len, read_mode, |chunk| {
+        data.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(data)
+}
+
+/// Read `len` bytes from `offset` in 64KB chunks, handing each chunk to `sink`
+///
+/// Only one chunk is held in memory at a time.
+fn read_chunks(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    len: usize,
+    read_mode: Option<ReadMode>,
+    mut sink: impl FnMut(&[u8]) -> Result<(), CmdError>,
+) -> Result<(), CmdError> {
+    log::info!("Reading {} bytes at 0x{:06X}", len, offset);
+    let progress = ProgressEmitter::new(app, "Reading");
+
+    const CHUNK_SIZE: usize = 65536;
+    let mut buf = vec![0u8; std::cmp::min(CHUNK_SIZE, len)];
+    let mut done = 0;
+
+    while done < len {
+        if state.checkpoint() {
+            return Err(CmdError::new(ErrorCode::Cancelled, "Cancelled"));
+        }
+
+        let chunk_len = std::cmp::min(CHUNK_SIZE, len - done);
+        let addr = (offset + done) as u32;
+
+        let chunk = &mut buf[..chunk_len];
+        let result = match read_mode {
+            Some(mode) => programmer.read_fast(addr, chunk, mode),
+            None => programmer.read(addr, chunk),
+        };
+
+        if let Err(e) = result {
+            return Err(CmdError::new(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", addr, e)));
+        }
+
+        sink(chunk)?;
+        done += chunk_len;
+
+        progress.emit(done, len);
+    }
+
+    Ok(())
+}
+
+/// Time spent in each phase of a write
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Timing {
+    pub erase_ms: u64,
+    pub program_ms: u64,   // Includes read-back with per-page verify
+    pub verify_ms: u64,
+    pub total_ms: u64,
+}
+
+/// Erase, program and verify the chip with the image at `path`
+fn write_file(
+    state: &AppState,
+    app: &AppHandle,
+    path: &str,
+    verify: VerifyMode,
+    strategy: EraseStrategy,
+    skip_blank: bool,
+) -> Result<Timing, CmdError> {
+    let data = image::load_image(path).map_err(|e| CmdError::new(ErrorCode::FileIo, e))?;
+    write_image(state, app, &data, verify, strategy, skip_blank)
+}
+
+/// Erase, program and verify the chip with `data`, starting at address 0
+fn write_image(
+    state: &AppState,
+    app: &AppHandle,
+    data: &[u8],
+    verify: VerifyMode,
+    strategy: EraseStrategy,
+    skip_blank: bool,
+) -> Result<Timing, CmdError> {
+    let mut programmer_guard = state.programmer.lock();
+    let chip_guard = state.current_chip.lock();
+
+    let programmer = programmer_guard
+        .as_mut()
+        .ok_or_else(|| CmdError::new(ErrorCode::NotConnected, "Not connected"))?;
+
+    let chip = chip_guard
+        .clone()
+        .ok_or_else(|| CmdError::new(ErrorCode::NoChip, "No chip detected"))?;
+
+    if !chip.contains_range(0, data.len()) {
+        return Err(CmdError::new(ErrorCode::SizeExceeded, format!(
+            "File size ({}) exceeds chip size ({})",
+            data.len(),
+            chip.size
+        )));
+    }
+
+    let whole_chip = data.len() == chip.size;
+    if strategy == EraseStrategy::FullChip && !whole_chip {
+        return Err(CmdError::new(None, format!(
+            "Full chip erase would wipe the rest of the chip: file covers {} of {} bytes",
+            data.len(),
+            chip.size
+        )));
+    }
+
+    let started = Instant::now();
+    let used = erase_for_write(state, programmer, app, data.len(), strategy, whole_chip, skip_blank)?;
+    log::info!("Erased using {:?} strategy", used);
+    let erased = Instant::now();
+
+    // Verify separately so its time is not counted as programming
+    let program_verify = if verify == VerifyMode::PerPage { verify } else { VerifyMode::None };
+    program_range(state, programmer, app, 0, data, program_verify)?;
+    let programmed = Instant::now();
+
+    if verify == VerifyMode::AfterAll {
+        verify_range(state, programmer, app, 0, data)?;
+    }
+
+    Ok(Timing {
+        erase_ms: (erased - started).as_millis() as u64,
+        program_ms: (programmed - erased).as_millis() as u64,
+        verify_ms: programmed.elapsed().as_millis() as u64,
+        total_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Write flash from file
+///
+/// `verify_mode` takes precedence over the older `verify` flag when given.
+/// `erase_strategy` defaults to per-sector erases; the strategy used is logged.
+/// `skip_blank_erase` reads each sector first and skips erasing blank ones.
+#[tauri::command(async)]
+fn write_flash(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    verify: bool,
+    verify_mode: Option<VerifyMode>,
+    erase_strategy: Option<EraseStrategy>,
+    skip_blank_erase: Option<bool>,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let verify = verify_mode.unwrap_or(VerifyMode::from_flag(verify));
+        let strategy = erase_strategy.unwrap_or_default();
+
+        match write_file(state, &app, &path, verify, strategy, skip_blank_erase.unwrap_or(false)) {
+            Ok(_) => CmdResult::ok(()),
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Same as `write_flash`, returning how long erase, program and verify took
+#[tauri::command(async)]
+fn write_flash_timed(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    verify_mode: Option<VerifyMode>,
+    erase_strategy: Option<EraseStrategy>,
+    skip_blank_erase: Option<bool>,
+) -> CmdResult<Timing> {
+    run_operation(&state, move |state| {
+        let verify = verify_mode.unwrap_or(VerifyMode::AfterAll);
+        let strategy = erase_strategy.unwrap_or_default();
+
+        match write_file(state, &app, &path, verify, strategy, skip_blank_erase.unwrap_or(false)) {
+            Ok(timing) => {
+                log::info!(
+                    "Write timing: erase {} ms, program {} ms, verify {} ms, total {} ms",
+                    timing.erase_ms, timing.program_ms, timing.verify_ms, timing.total_ms
+                );
+                CmdResult::ok(timing)
+            }
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Outcome of `write_protect_flow`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectFlowReport {
+    pub timing: Timing,
+    pub level: LockLevel,
+    pub status: StatusRegisters,  // Read back after locking
+}
+
+/// Write `path`, verify it, then set block protection so the part can't be
+/// overwritten by accident
+///
+/// Each step is logged as it starts. The chip is only locked once the
+/// read-back matches, so a failed write can still be redone, and only
+/// reported locked once SR1 shows the BP bits for `lock_level` (default:
+/// the whole array).
+#[tauri::command(async)]
+fn write_protect_flow(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    lock_level: Option<LockLevel>,
+) -> CmdResult<ProtectFlowReport> {
+    run_operation(&state, move |state| {
+        let level = lock_level.unwrap_or(LockLevel::All);
+
+        // Loaded once so the verify compares against exactly what was written
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        log::info!("Step 1/3: writing {}", path);
+        let mut timing = match write_image(state, &app, &data, VerifyMode::None, EraseStrategy::default(), false) {
+            Ok(t) => t,
+            Err(e) => {
+                log::warn!("Write failed; chip was not locked");
+                return e.into();
+            }
+        };
+
+        let mut programmer_guard = state.programmer.lock();
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        log::info!("Step 2/3: verifying");
+        let verify_started = Instant::now();
+        if let Err(e) = verify_range(state, programmer, &app, 0, &data) {
+            log::warn!("Verify failed; chip was not locked");
+            return e.into();
+        }
+        timing.verify_ms = verify_started.elapsed().as_millis() as u64;
+        timing.total_ms += timing.verify_ms;
+
+        log::info!("Step 3/3: locking ({:?})", level);
+        if let Err(e) = programmer.lock(level) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Written and verified, but locking failed: {}", e));
+        }
+
+        let status = match programmer.read_status_registers() {
+            Ok(status) => status,
+            Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Failed to read status registers: {}", e)),
+        };
+
+        if let Err(e) = confirm_lock(status.sr1, level) {
+            return e.into();
+        }
+
+        log::info!("Chip locked, SR1=0x{:02X}", status.sr1);
+        CmdResult::ok(ProtectFlowReport { timing, level, status })
+    })
+}
+
+/// Check that SR1 holds the BP bits of `level`
+fn confirm_lock(sr1: u8, level: LockLevel) -> Result<(), CmdError> {
+    if sr1 & STATUS_BP_MASK == level.bp_bits() {
+        return Ok(());
+    }
+
+    Err(CmdError::new(None, format!(
+        "Written and verified, but the lock did not take: SR1=0x{:02X}, expected BP bits 0x{:02X}",
+        sr1,
+        level.bp_bits()
+    )))
+}
+
+/// Report the sectors a write of `path` at `offset` would erase, the pages it
+/// would program and a rough duration, without touching the chip
+#[tauri::command]
+fn plan_write(state: State<'_, Arc<AppState>>, path: String, offset: Option<usize>) -> CmdResult<WritePlan> {
+    let programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_ref() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let chip = match state.current_chip.lock().clone() {
+        Some(c) => c,
+        None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+    };
+
+    let data = match image::load_image(&path) {
+        Ok(d) => d,
+        Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+    };
+
+    let offset = offset.unwrap_or(0);
+    if !chip.contains_range(offset, data.len()) {
+        return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+            "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+            offset, data.len(), chip.size
+        ));
+    }
+
+    CmdResult::ok(flash::plan_write(&chip, programmer.clock(), offset, data.len()))
+}
+
+/// Outcome of a write carried out on a simulated chip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedWrite {
+    pub chip: String,
+    pub bytes: usize,
+    pub erase_ops: usize,
+    pub pages_programmed: usize,
+    pub verified: bool,
+}
+
+/// Erase, write and verify a file on a simulated copy of the detected chip
+///
+/// Runs the same flash code as a real write against an in-memory chip, so
+/// the flow can be tried (or demonstrated) without touching the hardware.
+#[tauri::command(async)]
+fn simulate_write(state: State<'_, Arc<AppState>>, path: String, offset: Option<usize>) -> CmdResult<SimulatedWrite> {
+    let chip = match state.current_chip.lock().clone() {
+        Some(c) => c,
+        None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+    };
+
+    let data = match image::load_image(&path) {
+        Ok(d) => d,
+        Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+    };
+
+    let offset = offset.unwrap_or(0);
+    if !chip.contains_range(offset, data.len()) {
+        return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+            "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+            offset, data.len(), chip.size
+        ));
+    }
+
+    let mut programmer = FlashProgrammer::with_backend(MockFlash::new(&chip));
+    programmer.set_chip(chip.clone());
+
+    let result = programmer
+        .erase_range(offset as u32, data.len(), None)
+        .and_then(|_| programmer.write(offset as u32, &data, None))
+        .and_then(|_| programmer.verify(offset as u32, &data, None));
+
+    match result {
+        Ok(verified) => {
+            let mock = programmer.backend();
+            CmdResult::ok(SimulatedWrite {
+                chip: chip.name,
+                bytes: data.len(),
+                erase_ops: mock.erases(),
+                pages_programmed: mock.pages_programmed(),
+                verified,
+            })
+        }
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Simulated write failed: {}", e)),
+    }
+}
+
+/// Outcome of `program_all` for one adapter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceResult {
+    pub device: String,  // Serial, or USB location when the serial isn't unique
+    pub serial: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Write one image to the flash on every connected CH347 at once
+///
+/// Each adapter is opened and programmed on its own thread, with progress
+/// events tagged by device. A failure on one adapter doesn't stop the others.
+/// The app must be disconnected so every adapter can be opened.
+#[tauri::command(async)]
+fn program_all(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    verify: bool,
+) -> CmdResult<Vec<DeviceResult>> {
+    run_operation(&state, move |state| {
+        if state.programmer.lock().is_some() {
+            return CmdResult::err("Disconnect before programming all adapters");
+        }
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        let devices = match ch347::list_devices() {
+            Ok(d) if !d.is_empty() => d,
+            Ok(_) => return CmdResult::fail(ErrorCode::NotConnected, "No CH347 adapters found"),
+            Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Failed to list devices: {}", e)),
+        };
+        log::info!("Programming {} bytes to {} adapters", data.len(), devices.len());
+
+        let keys: Vec<String> = devices.iter().map(|info| device_key(info, &devices)).collect();
+
+        let results = std::thread::scope(|scope| {
+            let workers: Vec<_> = devices
+                .iter()
+                .zip(&keys)
+                .map(|(info, key)| {
+                    let (app, data) = (&app, &data);
+                    let worker = scope.spawn(move || program_device(state, app, info, key, data, verify));
+                    (key, info, worker)
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|(key, info, worker)| {
+                    let result = worker
+                        .join()
+                        .unwrap_or_else(|_| Err("Operation failed unexpectedly".into()));
+                    if let Err(e) = &result {
+                        log::warn!("{}: {}", key, e);
+                    }
+                    DeviceResult {
+                        device: key.clone(),
+                        serial: info.serial.clone(),
+                        success: result.is_ok(),
+                        error: result.err(),
+                    }
+                })
+                .collect()
+        });
+
+        CmdResult::ok(results)
+    })
+}
+
+/// Name an adapter by serial, or by USB location if another shares the serial
+fn device_key(info: &ch347::DeviceInfo, all: &[ch347::DeviceInfo]) -> String {
+    match &info.serial {
+        Some(serial) if all.iter().filter(|d| d.serial.as_ref() == Some(serial)).count() == 1 => serial.clone(),
+        _ => format!("bus {:03} address {:03}", info.bus, info.address),
+    }
+}
+
+/// Detect the flash on one adapter, erase what the image covers and program it
+fn program_device(
+    state: &AppState,
+    app: &AppHandle,
+    info: &ch347::DeviceInfo,
+    key: &str,
+    data: &[u8],
+    verify: bool,
+) -> Result<(), String> {
+    let mut programmer = FlashProgrammer::open_at(info.bus, info.address)
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+    programmer.set_cancel_flag(state.cancel.clone());
+    programmer.set_pause_flag(state.paused.clone());
+
+    let clock = SpiClock::from_khz(state.settings.lock().clock_khz);
+    if clock != SpiClock::default() {
+        programmer.set_clock(clock).map_err(|e| format!("Failed to set clock: {}", e))?;
+    }
+
+    let database = state.chip_database.lock().clone();
+    let options = DetectOptions { reset_first: false, robust: true };
+    let chip = programmer.detect(&database, options).map_err(|e| format!("Detection failed: {}", e))?;
+
+    if !chip.contains_range(0, data.len()) {
+        return Err(format!("File size ({}) exceeds chip size ({})", data.len(), chip.size));
+    }
+
+    let erase_progress = ProgressEmitter::steps(app, "Erasing").for_device(key);
+    programmer
+        .erase_range(0, data.len(), Some(&|current, total| erase_progress.emit(current, total)))
+        .map_err(|e| format!("Erase failed: {}", e))?;
+
+    let write_progress = ProgressEmitter::new(app, "Writing").for_device(key);
+    let progress = |current: usize, total: usize| write_progress.emit(current, total);
+    let result = if verify {
+        programmer.write_verify_pages(0, data, Some(&progress))
+    } else {
+        programmer.write(0, data, Some(&progress))
+    };
+
+    result.map_err(|e| match e {
+        Ch347Error::VerifyFailed(_) => e.to_string(),
+        _ => format!("Write failed: {}", e),
+    })
+}
+
+/// Continue an interrupted `write_flash` of the same file
+///
+/// Everything before the sector containing `start_offset` is assumed to be
+/// written correctly already; that sector and the rest of the file are erased
+/// and programmed again.
+#[tauri::command(async)]
+fn write_flash_resume(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    start_offset: usize,
+    verify: bool,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c.clone(),
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        if !chip.contains_range(0, data.len()) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "File size ({}) exceeds chip size ({})",
+                data.len(),
+                chip.size
+            ));
+        }
+
+        // A partially programmed sector has to be erased again from its start
+        let start = chip.align_down_sector(start_offset);
+        if start >= data.len() {
+            return CmdResult::ok(());
+        }
+
+        log::info!("Resuming write at 0x{:06X}", start);
+
+        match write_range(state, programmer, &app, start, &data[start..], VerifyMode::from_flag(verify)) {
+            Ok(()) => CmdResult::ok(()),
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Write flash from file, only rewriting sectors that differ
+///
+/// Returns the addresses of the rewritten sectors, to pass to `verify_sectors`.
+#[tauri::command(async)]
+fn write_flash_diff(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+) -> CmdResult<Vec<u32>> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        if !chip.contains_range(0, data.len()) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "File size ({}) exceeds chip size ({})",
+                data.len(),
+                chip.size
+            ));
+        }
+
+        let emitter = ProgressEmitter::new(&app, "Writing changes");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        match programmer.write_diff(0, &data, Some(&progress)) {
+            Ok(rewritten) => CmdResult::ok(rewritten),
+            Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Write failed: {}", e)),
+        }
+    })
+}
+
+/// Write an image (raw, Intel HEX or S-record) into flash starting at `offset`,
+/// leaving the rest untouched
+///
+/// With `verify` only `[offset, offset + file length)` is read back.
+#[tauri::command(async)]
+fn write_region(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    offset: usize,
+    verify: bool,
+    preserve: Option<bool>,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c.clone(),
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        if !chip.contains_range(offset, data.len()) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Region 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset,
+                data.len(),
+                chip.size
+            ));
+        }
+
+        // Erasing a partially covered sector would destroy the bytes around the region
+        let end = offset + data.len();
+        let aligned = offset.is_multiple_of(chip.sector_size)
+            && (end.is_multiple_of(chip.sector_size) || end == chip.size);
+
+        if !aligned && !preserve.unwrap_or(false) {
+            return CmdResult::err(format!(
+                "Region 0x{:06X}..0x{:06X} is not aligned to {}-byte sectors; \
+                 surrounding data in the partial sectors would be erased \
+                 (enable read-modify-write to preserve it)",
+                offset, end, chip.sector_size
+            ));
+        }
+
+        if aligned {
+            return match write_range(state, programmer, &app, offset, &data, VerifyMode::from_flag(verify)) {
+                Ok(()) => CmdResult::ok(()),
+                Err(e) => e.into(),
+            };
+        }
+
+        let emitter = ProgressEmitter::new(&app, "Writing (read-modify-write)");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        if let Err(e) = programmer.write_preserving(offset as u32, &data, Some(&progress)) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Write error: {}", e));
+        }
+
+        if verify {
+            if let Err(e) = verify_range(state, programmer, &app, offset, &data) {
+                return e.into();
+            }
+        }
+
+        CmdResult::ok(())
+    })
+}
+
+/// When data written by `write_range` is read back and compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyMode {
+    None,      // No read-back
+    AfterAll,  // Read the whole range back in 4KB chunks once it is written
+    PerPage,   // Read each page back after programming it, stopping at the first bad byte
+}
+
+impl VerifyMode {
+    /// Mode selected by the plain `verify` checkbox
+    fn from_flag(verify: bool) -> Self {
+        if verify { VerifyMode::AfterAll } else { VerifyMode::None }
+    }
+}
+
+/// Erase the sectors covering `[offset, offset + size)`, using 64KB/32KB
+/// blocks where aligned and the chip supports them
+///
+/// Each erase command is tried up to `attempts` times before giving up. With
+/// `skip_blank` each sector/block is read first and left alone if it is
+/// already all 0xFF, which is much faster than erasing on a fresh chip.
+fn erase_span(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    size: usize,
+    attempts: usize,
+    skip_blank: bool,
+) -> Result<(), CmdError> {
+    let plan = plan_erase(offset as u32, size, &programmer.erase_ops());
+    let ops = plan.len();
+    log::info!("Erasing 0x{:06X}..0x{:06X} ({} operations)", offset, offset + size, ops);
+    let erase_progress = ProgressEmitter::steps(app, "Erasing");
+    erase_progress.emit(0, ops);
+    let mut skipped = 0;
+
+    for (i, (op, addr)) in plan.into_iter().enumerate() {
+        if state.checkpoint() {
+            return Err(CmdError::new(ErrorCode::Cancelled, "Cancelled: flash is partially erased").resume_at(offset));
+        }
+
+        if skip_blank {
+            match programmer.is_blank(addr, op.size() as usize) {
+                Ok(None) => {
+                    skipped += 1;
+                    erase_progress.emit(i + 1, ops);
+                    continue;
+                }
+                Ok(Some(_)) => {}
+                Err(e) => {
+                    return Err(CmdError::new(ErrorCode::of(&e), format!("Blank check error at 0x{:06X}: {}", addr, e)).resume_at(offset));
+                }
+            }
+        }
+
+        let mut attempt = 1;
+        while let Err(e) = programmer.erase(op, addr) {
+            if attempt >= attempts || matches!(e, Ch347Error::Cancelled) {
+                return Err(CmdError::new(ErrorCode::of(&e), format!("Erase error at 0x{:06X}: {}", addr, e)).resume_at(offset));
+            }
+            log::warn!("Erase at 0x{:06X} failed ({}), retrying {}/{}", addr, e, attempt, attempts - 1);
+            attempt += 1;
+        }
+
+        erase_progress.emit(i + 1, ops);
+    }
+
+    if skipped > 0 {
+        log::info!("Skipped {} of {} erase operations on already blank areas", skipped, ops);
+    }
+
+    Ok(())
+}
+
+/// How `write_flash` clears the chip before programming
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EraseStrategy {
+    #[default]
+    PerSector,  // Sector/block erases covering the file only
+    FullChip,   // One chip erase; only allowed when the file fills the chip
+    Auto,       // Sector erases, switching to a chip erase if they keep failing
+}
+
+// Tries per erase command before `Auto` gives up on sector erases
+const AUTO_ERASE_ATTEMPTS: usize = 3;
+
+/// Erase ahead of writing `size` bytes from address 0, returning the
+/// strategy that was actually used
+///
+/// A full chip erase is only ever used when `size` covers the whole chip,
+/// since it would wipe everything past the file. `skip_blank` applies to
+/// sector/block erases only.
+fn erase_for_write(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    size: usize,
+    strategy: EraseStrategy,
+    whole_chip: bool,
+    skip_blank: bool,
+) -> Result<EraseStrategy, CmdError> {
+    let sector_result = match strategy {
+        EraseStrategy::PerSector => {
+            return erase_span(state, programmer, app, 0, size, 1, skip_blank).map(|_| strategy);
+        }
+        EraseStrategy::FullChip => None,
+        EraseStrategy::Auto => Some(erase_span(state, programmer, app, 0, size, AUTO_ERASE_ATTEMPTS, skip_blank)),
+    };
+
+    match sector_result {
+        Some(Ok(())) => return Ok(EraseStrategy::PerSector),
+        Some(Err(e)) if !whole_chip || e.code == Some(ErrorCode::Cancelled) => return Err(e),
+        Some(Err(e)) => log::warn!("{}; falling back to a full chip erase", e.message),
+        None => {}
+    }
+
+    log::info!("Erasing entire chip");
+    ProgressEmitter::steps(app, "Erasing chip...").emit(0, 1);
+    if let Err(e) = programmer.erase_chip(true) {
+        return Err(CmdError::new(ErrorCode::of(&e), format!("Erase failed: {}", e)).resume_at(0));
+    }
+    ProgressEmitter::steps(app, "Erase complete").emit(1, 1);
+
+    Ok(EraseStrategy::FullChip)
+}
+
+/// Erase the sectors covering `[offset, offset + data.len())`, program `data` and
+/// optionally verify the written span
+fn write_range(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    data: &[u8],
+    verify: VerifyMode,
+) -> Result<(), CmdError> {
+    erase_span(state, programmer, app, offset, data.len(), 1, false)?;
+    program_range(state, programmer, app, offset, data, verify)
+}
+
+/// Program already erased `[offset, offset + data.len())` with `data` and
+/// optionally verify it
+fn program_range(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    data: &[u8],
+    verify: VerifyMode,
+) -> Result<(), CmdError> {
+    let size = data.len();
+
+    // Write data, splitting at the chip's page boundaries
+    log::info!("Programming {} bytes at 0x{:06X}", size, offset);
+    let write_progress = ProgressEmitter::new(app, "Writing");
+    let written = Cell::new(0);
+    let progress = |current: usize, total: usize| {
+        written.set(current);
+        write_progress.emit(current, total);
+    };
+
+    let started = Instant::now();
+    let result = if verify == VerifyMode::PerPage {
+        programmer.write_verify_pages(offset as u32, data, Some(&progress))
+    } else {
+        programmer.write(offset as u32, data, Some(&progress))
+    };
+
+    if let Err(e) = result {
+        let addr = offset + written.get();
+        let message = match e {
+            Ch347Error::Cancelled => format!("Cancelled: flash is partially written (stopped at 0x{:06X})", addr),
+            Ch347Error::VerifyFailed(_) => e.to_string(),
+            _ => format!("Write error at 0x{:06X}: {}", addr, e),
+        };
+        return Err(CmdError::new(ErrorCode::of(&e), message).resume_at(addr));
+    }
+    log::info!("Programmed {} bytes in {} ms", size, started.elapsed().as_millis());
+
+    // Verify if requested
+    if verify == VerifyMode::AfterAll {
+        verify_range(state, programmer, app, offset, data)?;
+    }
+
+    Ok(())
+}
+
+/// Read back `[offset, offset + data.len())` and compare it against `data`
+///
+/// Only the written bytes are read, so verifying a small region is quick and
+/// an unaligned region isn't checked against whatever surrounds it. A
+/// mismatch is reported at its chip address, not its offset in `data`.
+fn verify_range(
+    state: &AppState,
+    programmer: &mut FlashProgrammer,
+    app: &AppHandle,
+    offset: usize,
+    data: &[u8],
+) -> Result<(), CmdError> {
+    let size = data.len();
+    log::info!("Verifying {} bytes at 0x{:06X}", size, offset);
+
+    let progress = ProgressEmitter::new(app, "Verifying");
+    progress.emit(0, size);
+
+    const CHUNK_SIZE: usize = 4096;
+    let mut read_buf = vec![0u8; CHUNK_SIZE];
+    let mut done = 0;
+
+    while done < size {
+        if state.checkpoint() {
+            return Err(CmdError::new(ErrorCode::Cancelled, "Cancelled: write completed but was not verified"));
+        }
+
+        let chunk_len = std::cmp::min(CHUNK_SIZE, size - done);
+        let addr = offset + done;
+
+        if let Err(e) = programmer.read(addr as u32, &mut read_buf[..chunk_len]) {
+            return Err(CmdError::new(ErrorCode::of(&e), format!("Verify read error at 0x{:06X}: {}", addr, e)));
+        }
+
+        // `first_mismatch` works in file offsets; report the chip address
+        if let Some(bad) = first_mismatch(&read_buf[..chunk_len], data, done, 0xFF) {
+            let chip_addr = offset + bad;
+            let (expected, actual) = (data[bad], read_buf[bad - done]);
+            log::warn!("Verification failed at 0x{:06X}: expected 0x{:02X}, got 0x{:02X}", chip_addr, expected, actual);
+            return Err(CmdError::new(ErrorCode::VerifyFailed, format!(
+                "Verification failed at 0x{:06X}: expected 0x{:02X}, got 0x{:02X}",
+                chip_addr, expected, actual
+            )));
+        }
+
+        done += chunk_len;
+
+        progress.emit(done, size);
+    }
+
+    Ok(())
+}
+
+/// Outcome of `self_test`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub failed_step: Option<String>,  // "read", "erase", "write", "verify" or "restore"
+    pub error: Option<String>,
+    pub restored: bool,               // Original sector content was put back
+}
+
+/// Round-trip the last sector of the chip: read, erase, write a pattern,
+/// verify it and restore the original content
+#[tauri::command(async)]
+fn self_test(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> CmdResult<SelfTestReport> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if let Err(e) = ensure_writable(programmer, "the self-test") {
+            return e.into();
+        }
+
+        let progress = ProgressEmitter::steps(&app, "Self-test");
+        CmdResult::ok(self_test_sector(state, programmer, chip, &|step| progress.emit(step, 5)))
+    })
+}
+
+/// Refuse to start a command that rewrites a sector while the chip is protected
+fn ensure_writable<B: SpiBackend>(programmer: &mut FlashProgrammer<B>, what: &str) -> Result<(), CmdError> {
+    match programmer.is_write_protected() {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(CmdError::new(None, format!("Chip is write protected; unlock it before running {}", what))),
+        Err(e) => Err(CmdError::new(ErrorCode::of(&e), format!("Failed to read status registers: {}", e))),
+    }
+}
+
+/// The steps of `self_test` on the last sector of `chip`, reporting each of
+/// the five through `progress`
+fn self_test_sector<B: SpiBackend>(
+    state: &AppState,
+    programmer: &mut FlashProgrammer<B>,
+    chip: &FlashChip,
+    progress: &dyn Fn(usize),
+) -> SelfTestReport {
+    let addr = (chip.size - chip.sector_size) as u32;
+    let len = chip.sector_size;
+    log::info!("Self-test on sector 0x{:06X}", addr);
+
+    progress(0);
+
+    let mut original = vec![0u8; len];
+    if let Err(e) = programmer.read(addr, &mut original) {
+        return SelfTestReport {
+            passed: false,
+            failed_step: Some("read".into()),
+            error: Some(e.to_string()),
+            restored: true,  // Nothing was changed yet
+        };
+    }
+    progress(1);
+
+    // Address-dependent so a stuck address line shows up as a mismatch
+    let pattern: Vec<u8> = (0..len).map(|i| (i % 251) as u8 ^ 0xA5).collect();
+
+    let failure = (|| {
+        programmer.erase_range(addr, len, None).map_err(|e| ("erase", e.to_string()))?;
+        match programmer.is_blank(addr, len) {
+            Ok(None) => {}
+            Ok(Some(dirty)) => return Err(("erase", format!("0x{:06X} not blank after erase", dirty))),
+            Err(e) => return Err(("erase", e.to_string())),
+        }
+        progress(2);
+
+        programmer.write(addr, &pattern, None).map_err(|e| ("write", e.to_string()))?;
+        progress(3);
+
+        match programmer.verify(addr, &pattern, None) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(("verify", "read-back does not match the written pattern".to_string())),
+            Err(e) => Err(("verify", e.to_string())),
+        }
+    })()
+    .err();
+    progress(4);
+
+    // Put the original content back even if a step failed
+    let restored = restore_sector(state, programmer, addr, &original);
+    progress(5);
+
+    // Losing the user's data outranks whatever failed before it
+    let failure = match (failure, restored) {
+        (failure, true) => failure,
+        (None, false) => Some(("restore", "original sector content could not be put back".to_string())),
+        (Some((step, e)), false) => Some(("restore", format!(
+            "original sector content could not be put back after {} failed: {}",
+            step, e
+        ))),
+    };
+
+    SelfTestReport {
+        passed: failure.is_none(),
+        failed_step: failure.as_ref().map(|(step, _)| step.to_string()),
+        error: failure.map(|(_, e)| e),
+        restored,
+    }
+}
+
+/// Erase a sector and program its saved content back, reporting success
+///
+/// Runs on private cancel and pause flags: cancelling the operation that
+/// disturbed the sector must not leave it erased or half written.
+fn restore_sector<B: SpiBackend>(state: &AppState, programmer: &mut FlashProgrammer<B>, addr: u32, original: &[u8]) -> bool {
+    programmer.set_cancel_flag(Arc::new(AtomicBool::new(false)));
+    programmer.set_pause_flag(Arc::new(AtomicBool::new(false)));
+
+    let restored = programmer.erase_range(addr, original.len(), None).is_ok()
+        && programmer.write(addr, original, None).is_ok()
+        && matches!(programmer.verify(addr, original, None), Ok(true));
+
+    programmer.set_cancel_flag(state.cancel.clone());
+    programmer.set_pause_flag(state.paused.clone());
+
+    if !restored {
+        log::warn!("Could not restore sector 0x{:06X}", addr);
+    }
+
+    restored
+}
+
+/// Timings measured by `benchmark`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub read_kbps: f64,   // Sustained read throughput
+    pub erase_ms: f64,    // One sector erase
+    pub program_ms: f64,  // One page program
+}
+
+// Bytes read for the read throughput measurement
+const BENCHMARK_READ_LEN: usize = 256 * 1024;
+
+/// Time a read, a sector erase and a page program at the end of the chip
+///
+/// The last sector is saved first and restored afterwards.
+#[tauri::command(async)]
+fn benchmark(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> CmdResult<BenchmarkResult> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if let Err(e) = ensure_writable(programmer, "the benchmark") {
+            return e.into();
+        }
+
+        let addr = (chip.size - chip.sector_size) as u32;
+        let read_len = BENCHMARK_READ_LEN.min(chip.size);
+        let progress = ProgressEmitter::steps(&app, "Benchmark");
+        progress.emit(0, 4);
+
+        let mut original = vec![0u8; chip.sector_size];
+        if let Err(e) = programmer.read(addr, &mut original) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", addr, e));
+        }
+        progress.emit(1, 4);
+
+        let mut buf = vec![0u8; read_len];
+        let start = Instant::now();
+        if let Err(e) = programmer.read((chip.size - read_len) as u32, &mut buf) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Read failed: {}", e));
+        }
+        let read_secs = start.elapsed().as_secs_f64();
+        progress.emit(2, 4);
+
+        let page = vec![0x55u8; chip.page_size];
+        let timings = (|| {
+            let start = Instant::now();
+            programmer.erase_range(addr, chip.sector_size, None)?;
+            let erase = start.elapsed();
+
+            let start = Instant::now();
+            programmer.program_page(addr, &page)?;
+            Ok::<_, Ch347Error>((erase, start.elapsed()))
+        })();
+        progress.emit(3, 4);
+
+        let restored = restore_sector(state, programmer, addr, &original);
+        progress.emit(4, 4);
+
+        // Lost data matters more than a failed or cancelled measurement
+        if !restored {
+            let cause = match &timings {
+                Ok(_) => String::new(),
+                Err(e) => format!(" after the benchmark failed: {}", e),
+            };
+            return CmdResult::err(format!("Could not restore the last sector at 0x{:06X}{}", addr, cause));
+        }
+
+        let (erase, program) = match timings {
+            Ok(t) => t,
+            Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Benchmark failed: {}", e)),
+        };
+
+        CmdResult::ok(BenchmarkResult {
+            read_kbps: read_len as f64 / 1024.0 / read_secs,
+            erase_ms: erase.as_secs_f64() * 1000.0,
+            program_ms: program.as_secs_f64() * 1000.0,
+        })
+    })
+}
+
+/// Effective SPI speed from a timed read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockMeasurement {
+    pub nominal_hz: u32,     // Configured SCK
+    pub bytes_per_sec: f64,
+    pub effective_hz: f64,   // SCK that would move the same bits with no gaps
+    pub efficiency: f64,     // effective_hz / nominal_hz
+}
+
+/// Time a plain read of the last 256KB and work back to the effective clock
+///
+/// Reading doesn't change the chip. The figure includes USB round trips, so
+/// it shows how much of the nominal SCK is really achieved.
+#[tauri::command(async)]
+fn measure_clock(state: State<'_, Arc<AppState>>) -> CmdResult<ClockMeasurement> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let read_len = BENCHMARK_READ_LEN.min(chip.size);
+        let mut buf = vec![0u8; read_len];
+        let start = Instant::now();
+        if let Err(e) = programmer.read((chip.size - read_len) as u32, &mut buf) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Read failed: {}", e));
+        }
+        let secs = start.elapsed().as_secs_f64();
+
+        let nominal_hz = programmer.clock().hz();
+        let bytes_per_sec = read_len as f64 / secs;
+        let effective_hz = bytes_per_sec * 8.0;
+        log::info!(
+            "Read {} bytes in {:.1} ms: {:.2} MHz effective of {:.2} MHz",
+            read_len, secs * 1000.0, effective_hz / 1e6, nominal_hz as f64 / 1e6
+        );
+
+        CmdResult::ok(ClockMeasurement {
+            nominal_hz,
+            bytes_per_sec,
+            effective_hz,
+            efficiency: effective_hz / nominal_hz as f64,
+        })
+    })
+}
+
+/// Erase entire chip, returning the erase opcode that worked
+///
+/// Unless `retry_alternate` is false, a chip that ignores its chip erase
+/// opcode is retried with the other of 0xC7/0x60.
+#[tauri::command(async)]
+fn erase_chip(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    retry_alternate: Option<bool>,
+) -> CmdResult<u8> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        log::info!("Erasing entire chip");
+        ProgressEmitter::steps(&app, "Erasing chip...").emit(0, 1);
+
+        let opcode = match programmer.erase_chip(retry_alternate.unwrap_or(true)) {
+            Ok(opcode) => opcode,
+            Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Erase failed: {}", e)),
+        };
+
+        ProgressEmitter::steps(&app, "Erase complete").emit(1, 1);
+
+        CmdResult::ok(opcode)
+    })
+}
+
+/// Erase a byte range using the largest aligned erase units
+#[tauri::command(async)]
+fn erase_range(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        if len > 0 {
+            let last = offset + len - 1;
+            log::info!(
+                "Erasing sectors {}..={} (blocks {}..={})",
+                chip.sector_of(offset), chip.sector_of(last), chip.block_of(offset), chip.block_of(last)
+            );
+        }
+
+        let emitter = ProgressEmitter::steps(&app, "Erasing");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        match programmer.erase_range(offset as u32, len, Some(&progress)) {
+            Ok(()) => CmdResult::ok(()),
+            Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Erase failed: {}", e)),
+        }
+    })
+}
+
+/// Fill `[offset, offset + len)` with a repeated byte, no file needed
+///
+/// Like a write, whole sectors are erased; 0xFF stops after the erase.
+#[tauri::command(async)]
+fn fill_region(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+    byte: u8,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        let result = if byte == 0xFF {
+            erase_span(state, programmer, &app, offset, len, 1, false)
+        } else {
+            write_range(state, programmer, &app, offset, &vec![byte; len], VerifyMode::None)
+        };
+
+        match result {
+            Ok(()) => CmdResult::ok(()),
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Outcome of `verify_flash`; the mismatch fields describe the first bad byte
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub ok: bool,
+    pub mismatch_addr: Option<u32>,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+}
+
+/// Verify flash against file
+///
+/// By default the rest of the chip past the end of the file must read as
+/// `pad_with` (0xFF unless given). With `file_only` set, only the file's own
+/// length is compared; that matches `write_flash`, which erases just the
+/// sectors the file covers and leaves the remainder of the chip untouched.
+///
+/// `verify_crc` compares CRC32s of 64KB regions instead of every chunk's
+/// bytes; only a region whose CRC differs is compared byte by byte to find
+/// the first bad address.
+#[tauri::command(async)]
+fn verify_flash(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    pad_with: Option<u8>,
+    file_only: Option<bool>,
+    verify_crc: Option<bool>,
+) -> CmdResult<VerifyResult> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        // Streamed alongside the chip reads rather than loaded whole
+        let mut file = match image::ImageReader::open(&path) {
+            Ok(f) => f,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+        let file_len = file.size();
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        // Also applies to `file_only`, which would otherwise read past the end of the chip
+        if !chip.contains_range(0, file_len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "File size ({}) exceeds chip size ({})",
+                file_len,
+                chip.size
+            ));
+        }
+
+        let pad = pad_with.unwrap_or(0xFF);
+        let size = if file_only.unwrap_or(false) { file_len } else { chip.size };
+
+        let by_crc = verify_crc.unwrap_or(false);
+        let progress = ProgressEmitter::new(&app, "Verifying");
+        let chunk_size: usize = if by_crc { 65536 } else { 4096 };
+        let mut read_buf = vec![0u8; chunk_size];
+        let mut file_buf = vec![0u8; chunk_size];
+        let mut offset = 0;
+
+        while offset < size {
+            if state.checkpoint() {
+                return CmdResult::fail(ErrorCode::Cancelled, "Cancelled");
+            }
+
+            let chunk_len = std::cmp::min(chunk_size, size - offset);
+            let chunk = &mut read_buf[..chunk_len];
+
+            if let Err(e) = programmer.read(offset as u32, chunk) {
+                return CmdResult::fail(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", offset, e));
+            }
+
+            // Short (or empty) once past the end of the file; the rest must be `pad`
+            let file_chunk = match file.read_chunk(&mut file_buf[..chunk_len]) {
+                Ok(n) => &file_buf[..n],
+                Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+            };
+
+            let suspect = !by_crc || crc32fast::hash(chunk) != expected_crc(file_chunk, 0, chunk_len, pad);
+            if suspect {
+                if let Some(i) = first_mismatch(chunk, file_chunk, 0, pad) {
+                    let addr = offset + i;
+                    let expected = file_chunk.get(i).copied().unwrap_or(pad);
+                    let actual = chunk[i];
+                    log::warn!("Verification failed at 0x{:06X}: expected 0x{:02X}, got 0x{:02X}", addr, expected, actual);
+                    return CmdResult::ok(VerifyResult {
+                        ok: false,
+                        mismatch_addr: Some(addr as u32),
+                        expected: Some(expected),
+                        actual: Some(actual),
+                    });
+                }
+            }
+
+            offset += chunk_len;
+
+            progress.emit(offset, size);
+        }
+
+        CmdResult::ok(VerifyResult { ok: true, ..Default::default() })
+    })
+}
+
+/// CRC32 of every sector of the chip, in address order
+///
+/// Compared against the same hashes of an image, this tells which sectors
+/// need writing without reading the chip back into the frontend.
+#[tauri::command(async)]
+fn sector_hashes(state: State<'_, Arc<AppState>>, app: AppHandle) -> CmdResult<Vec<u32>> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let mut hashes = Vec::with_capacity(chip.size / chip.sector_size);
+        let mut hasher = crc32fast::Hasher::new();
+        let mut filled = 0;
+
+        // Read chunks needn't line up with sectors, so hash across them
+        let result = read_chunks(state, programmer, &app, 0, chip.size, None, |mut chunk| {
+            while !chunk.is_empty() {
+                let take = chunk.len().min(chip.sector_size - filled);
+                hasher.update(&chunk[..take]);
+                filled += take;
+                chunk = &chunk[take..];
+
+                if filled == chip.sector_size {
+                    hashes.push(std::mem::take(&mut hasher).finalize());
+                    filled = 0;
+                }
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => CmdResult::ok(hashes),
+            Err(e) => e.into(),
+        }
+    })
+}
+
+/// Verify only the given sectors against a file, e.g. those `write_flash_diff` rewrote
+///
+/// Each sector is compared over the part the file covers; bytes past the end
+/// of the file were left as they were by the write.
+#[tauri::command(async)]
+fn verify_sectors(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    sector_addrs: Vec<u32>,
+) -> CmdResult<VerifyResult> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        for &addr in &sector_addrs {
+            let addr = addr as usize;
+            if chip.align_down_sector(addr) != addr || !chip.contains_range(addr, chip.sector_size) {
+                return CmdResult::err(format!(
+                    "0x{:06X} is not the start of a {}-byte sector on this chip",
+                    addr, chip.sector_size
+                ));
+            }
+            if addr >= data.len() {
+                return CmdResult::err(format!("Sector 0x{:06X} lies past the end of the file", addr));
+            }
+        }
+
+        let progress = ProgressEmitter::steps(&app, "Verifying sectors");
+        let mut read_buf = vec![0u8; chip.sector_size];
+
+        for (i, &addr) in sector_addrs.iter().enumerate() {
+            if state.checkpoint() {
+                return CmdResult::fail(ErrorCode::Cancelled, "Cancelled");
+            }
+
+            let offset = addr as usize;
+            let len = chip.sector_size.min(data.len() - offset);
+            let chunk = &mut read_buf[..len];
+
+            if let Err(e) = programmer.read(addr, chunk) {
+                return CmdResult::fail(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", addr, e));
+            }
+
+            if let Some(bad) = first_mismatch(chunk, &data, offset, 0xFF) {
+                let expected = data[bad];
+                let actual = chunk[bad - offset];
+                log::warn!("Verification failed at 0x{:06X}: expected 0x{:02X}, got 0x{:02X}", bad, expected, actual);
+                return CmdResult::ok(VerifyResult {
+                    ok: false,
+                    mismatch_addr: Some(bad as u32),
+                    expected: Some(expected),
+                    actual: Some(actual),
+                });
+            }
+
+            progress.emit(i + 1, sector_addrs.len());
+        }
+
+        CmdResult::ok(VerifyResult { ok: true, ..Default::default() })
+    })
+}
+
+/// Address of the first byte of `chunk` (read at `offset`) that differs from
+/// the file; past the end of the file the chip should hold `pad`
+fn first_mismatch(chunk: &[u8], data: &[u8], offset: usize, pad: u8) -> Option<usize> {
+    chunk
+        .iter()
+        .enumerate()
+        .position(|(i, &b)| b != data.get(offset + i).copied().unwrap_or(pad))
+        .map(|i| offset + i)
+}
+
+/// CRC32 of `len` expected bytes at `offset`: file data, then `pad`
+fn expected_crc(data: &[u8], offset: usize, len: usize, pad: u8) -> u32 {
+    let file_part = &data[data.len().min(offset)..data.len().min(offset + len)];
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(file_part);
+    hasher.update(&vec![pad; len - file_part.len()]);
+    hasher.finalize()
+}
+
+/// Check a range is blank (all 0xFF), returning the first dirty address
+#[tauri::command(async)]
+fn blank_check(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+) -> CmdResult<Option<u32>> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        // Check in 64KB chunks for progress
+        let progress = ProgressEmitter::new(&app, "Blank checking");
+        const CHUNK_SIZE: usize = 65536;
+        let mut done = 0;
+
+        while done < len {
+            if state.checkpoint() {
+                return CmdResult::fail(ErrorCode::Cancelled, "Cancelled");
+            }
+
+            let chunk_len = std::cmp::min(CHUNK_SIZE, len - done);
+            let addr = (offset + done) as u32;
+
+            match programmer.is_blank(addr, chunk_len) {
+                Ok(Some(dirty)) => return CmdResult::ok(Some(dirty)),
+                Ok(None) => {}
+                Err(e) => return CmdResult::fail(ErrorCode::of(&e), format!("Read error at 0x{:06X}: {}", addr, e)),
+            }
+
+            done += chunk_len;
+
+            progress.emit(done, len);
+        }
+
+        CmdResult::ok(None)
+    })
+}
+
+/// Compute CRC32 of a flash range, returned as a hex string
+#[tauri::command(async)]
+fn checksum(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+) -> CmdResult<String> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        let emitter = ProgressEmitter::new(&app, "Checksumming");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        match programmer.checksum_crc32(offset as u32, len, Some(&progress)) {
+            Ok(crc) => CmdResult::ok(format!("{:08X}", crc)),
+            Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Checksum failed: {}", e)),
+        }
+    })
+}
+
+/// Compute a CRC32/MD5/SHA-256 digest of a flash range
+#[tauri::command(async)]
+fn flash_digest(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    offset: usize,
+    len: usize,
+    algo: DigestAlgo,
+) -> CmdResult<String> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c,
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        if !chip.contains_range(offset, len) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "Range 0x{:06X}+0x{:X} exceeds chip size ({})",
+                offset, len, chip.size
+            ));
+        }
+
+        let emitter = ProgressEmitter::new(&app, "Hashing");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        match programmer.digest(offset as u32, len, algo, Some(&progress)) {
+            Ok(digest) => CmdResult::ok(digest),
+            Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Digest failed: {}", e)),
+        }
+    })
+}
+
+/// Drive a CH347 GPIO pin, e.g. a target reset line or the flash WP# pin
+#[tauri::command]
+fn set_gpio(state: State<'_, Arc<AppState>>, pin: u8, level: bool) -> CmdResult<()> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.device_mut().gpio_set(pin, level) {
+        Ok(()) => CmdResult::ok(()),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to set GPIO{}: {}", pin, e)),
+    }
+}
+
+/// Read the level of a CH347 GPIO pin
+#[tauri::command]
+fn get_gpio(state: State<'_, Arc<AppState>>, pin: u8) -> CmdResult<bool> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    match programmer.device_mut().gpio_get(pin) {
+        Ok(level) => CmdResult::ok(level),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to read GPIO{}: {}", pin, e)),
+    }
+}
+
+/// Scan the I2C bus, returning the 7-bit addresses that acknowledge
+#[tauri::command]
+fn i2c_scan(state: State<'_, Arc<AppState>>, speed_khz: Option<u32>) -> CmdResult<Vec<u8>> {
+    let mut programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    let device = programmer.device_mut();
+
+    if let Err(e) = device.i2c_init(speed_khz.map(I2cSpeed::from_khz).unwrap_or_default()) {
+        return CmdResult::fail(ErrorCode::of(&e), format!("Failed to configure I2C: {}", e));
+    }
+
+    match device.i2c_scan() {
+        Ok(found) => CmdResult::ok(found),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("I2C scan failed: {}", e)),
+    }
+}
+
+/// Get the supported I2C EEPROM types
+#[tauri::command]
+fn get_eeprom_types() -> Vec<EepromChip> {
+    get_eeprom_database()
+}
+
+/// Configure I2C and check an EEPROM of type `chip` answers at `address`
+fn open_eeprom<'a>(
+    programmer: &'a mut FlashProgrammer,
+    chip: &str,
+    address: Option<u8>,
+    speed_khz: Option<u32>,
+) -> Result<EepromProgrammer<'a>, CmdError> {
+    let chip = get_eeprom_database()
+        .into_iter()
+        .find(|c| c.name.eq_ignore_ascii_case(chip.trim()))
+        .ok_or_else(|| CmdError::new(None, format!("Unknown EEPROM \"{}\"", chip)))?;
+
+    let address = address.unwrap_or(eeprom::DEFAULT_ADDRESS);
+    if address > i2c::SCAN_LAST {
+        return Err(CmdError::new(None, format!("Invalid I2C address 0x{:02X}", address)));
+    }
+
+    let device = programmer.device_mut();
+    device
+        .i2c_init(speed_khz.map(I2cSpeed::from_khz).unwrap_or_default())
+        .map_err(|e| CmdError::new(ErrorCode::of(&e), format!("Failed to configure I2C: {}", e)))?;
+
+    let mut eeprom = EepromProgrammer::new(device, chip, address);
+    match eeprom.detect() {
+        Ok(true) => Ok(eeprom),
+        Ok(false) => Err(CmdError::new(ErrorCode::NoChip, format!("No EEPROM at I2C address 0x{:02X}", address))),
+        Err(e) => Err(CmdError::new(ErrorCode::of(&e), format!("I2C error: {}", e))),
+    }
+}
+
+/// Read an entire I2C EEPROM to file
+#[tauri::command(async)]
+fn read_eeprom(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    chip: String,
+    address: Option<u8>,
+    speed_khz: Option<u32>,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let mut eeprom = match open_eeprom(programmer, &chip, address, speed_khz) {
+            Ok(e) => e,
+            Err(e) => return e.into(),
+        };
+
+        let mut data = vec![0u8; eeprom.size()];
+        let emitter = ProgressEmitter::new(&app, "Reading EEPROM");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        if let Err(e) = eeprom.read(0, &mut data, Some(&progress)) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Read failed: {}", e));
+        }
+
+        if let Err(e) = image::save_image(&path, 0, &data) {
+            return CmdResult::fail(ErrorCode::FileIo, e);
+        }
+
+        CmdResult::ok(())
+    })
+}
+
+/// Write a file into an I2C EEPROM from address 0
+#[tauri::command(async)]
+fn write_eeprom(
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    chip: String,
+    address: Option<u8>,
+    speed_khz: Option<u32>,
+    verify: bool,
+) -> CmdResult<()> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let mut eeprom = match open_eeprom(programmer, &chip, address, speed_khz) {
+            Ok(e) => e,
+            Err(e) => return e.into(),
+        };
+
+        let data = match image::load_image(&path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        if data.len() > eeprom.size() {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "File size ({}) exceeds EEPROM size ({})",
+                data.len(),
+                eeprom.size()
+            ));
+        }
+
+        let emitter = ProgressEmitter::new(&app, "Writing EEPROM");
+        let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+        if let Err(e) = eeprom.write(0, &data, Some(&progress)) {
+            return CmdResult::fail(ErrorCode::of(&e), format!("Write failed: {}", e));
+        }
+
+        if verify {
+            let mut readback = vec![0u8; data.len()];
+            let emitter = ProgressEmitter::new(&app, "Verifying EEPROM");
+            let progress = |current: usize, total: usize| emitter.emit(current, total);
+
+            if let Err(e) = eeprom.read(0, &mut readback, Some(&progress)) {
+                return CmdResult::fail(ErrorCode::of(&e), format!("Verify read failed: {}", e));
+            }
+
+            if let Some(i) = readback.iter().zip(&data).position(|(a, b)| a != b) {
+                return CmdResult::fail(ErrorCode::VerifyFailed, format!("Verification failed at 0x{:04X}", i));
+            }
+        }
+
+        CmdResult::ok(())
+    })
+}
+
+/// Request cancellation of the running operation
+#[tauri::command]
+fn cancel_operation(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    state.cancel.store(true, Ordering::SeqCst);
+    CmdResult::ok(())
+}
+
+// How long `recover` waits for a running operation to let go of the device
+const RECOVER_WAIT: Duration = Duration::from_secs(5);
+const RECOVER_POLL: Duration = Duration::from_millis(20);
+
+/// Abort whatever is running and reset the bus and flash to a known state
+///
+/// Cancels any operation, then releases chip select, re-initializes SPI and
+/// software-resets the chip. Unlike `disconnect` the adapter stays open.
+/// Returns the JEDEC ID read afterwards, showing the bus is healthy again.
+#[tauri::command(async)]
+fn recover(state: State<'_, Arc<AppState>>) -> CmdResult<String> {
+    state.cancel.store(true, Ordering::SeqCst);
+    state.paused.store(false, Ordering::SeqCst);
+
+    // Claim the operation slot once the cancelled operation has released it,
+    // so nothing else can start while the bus is being reset
+    let deadline = Instant::now() + RECOVER_WAIT;
+    let _operation = loop {
+        if let Some(guard) = state.begin_operation() {
+            break guard;
+        }
+        if Instant::now() >= deadline {
+            state.cancel.store(false, Ordering::SeqCst);
+            return CmdResult::fail(ErrorCode::Busy, "Busy: the running operation did not stop");
+        }
+        std::thread::sleep(RECOVER_POLL);
+    };
+
+    // A short command may still hold the programmer
+    let mut programmer_guard = match state.programmer.try_lock_for(deadline.saturating_duration_since(Instant::now())) {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let programmer = match programmer_guard.as_mut() {
+        Some(p) => p,
+        None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+    };
+
+    log::info!("Recovering: releasing CS, re-initializing SPI and resetting the chip");
+    match programmer.recover() {
+        Ok(id) => {
+            log::info!("Recovered, JEDEC ID {}", id);
+            CmdResult::ok(id.to_string())
+        }
+        Err(Ch347Error::DeviceNotFound) => {
+            CmdResult::fail(ErrorCode::NoChip, "Bus reset, but no chip responded (JEDEC ID all 0x00 or 0xFF)")
+        }
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Recovery failed: {}", e)),
+    }
+}
+
+/// Pause the running operation at its next chunk boundary
+///
+/// Reads, writes and verifies stop between chunks. Erases stop between
+/// sector/block commands; one already sent to the chip runs to completion.
+/// Cancelling still works while paused.
+#[tauri::command]
+fn pause_operation(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    state.paused.store(true, Ordering::SeqCst);
+    CmdResult::ok(())
+}
+
+/// Let a paused operation carry on
+#[tauri::command]
+fn resume_operation(state: State<'_, Arc<AppState>>) -> CmdResult<()> {
+    state.paused.store(false, Ordering::SeqCst);
+    CmdResult::ok(())
+}
+
+/// Compare two image files on disk, padding the shorter one with 0xFF
+#[tauri::command]
+fn diff_files(path_a: String, path_b: String) -> CmdResult<ImageDiff> {
+    let a = match image::load_image(&path_a) {
+        Ok(d) => d,
+        Err(e) => return CmdResult::fail(ErrorCode::FileIo, format!("{}: {}", path_a, e)),
+    };
+
+    let b = match image::load_image(&path_b) {
+        Ok(d) => d,
+        Err(e) => return CmdResult::fail(ErrorCode::FileIo, format!("{}: {}", path_b, e)),
+    };
+
+    CmdResult::ok(diff::diff_images(&a, &b))
+}
+
+/// Read the chip and list the ranges that differ from a baseline image
+///
+/// The read-side counterpart of `diff_files`. The baseline is padded with
+/// 0xFF to the chip size, so anything written past its end shows up too.
+#[tauri::command(async)]
+fn make_patch(state: State<'_, Arc<AppState>>, app: AppHandle, baseline_path: String) -> CmdResult<ImageDiff> {
+    run_operation(&state, move |state| {
+        let mut programmer_guard = state.programmer.lock();
+        let chip_guard = state.current_chip.lock();
+
+        let programmer = match programmer_guard.as_mut() {
+            Some(p) => p,
+            None => return CmdResult::fail(ErrorCode::NotConnected, "Not connected"),
+        };
+
+        let chip = match chip_guard.as_ref() {
+            Some(c) => c.clone(),
+            None => return CmdResult::fail(ErrorCode::NoChip, "No chip detected"),
+        };
+
+        let mut baseline = match image::load_image(&baseline_path) {
+            Ok(d) => d,
+            Err(e) => return CmdResult::fail(ErrorCode::FileIo, e),
+        };
+
+        if !chip.contains_range(0, baseline.len()) {
+            return CmdResult::fail(ErrorCode::SizeExceeded, format!(
+                "File size ({}) exceeds chip size ({})",
+                baseline.len(),
+                chip.size
+            ));
+        }
+        baseline.resize(chip.size, 0xFF);
+
+        let mut diff = ImageDiff::default();
+        let mut offset = 0;
+        let result = read_chunks(state, programmer, &app, 0, chip.size, None, |chunk| {
+            diff.compare_chunk(offset, chunk, &baseline[offset..offset + chunk.len()]);
+            offset += chunk.len();
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            return e.into();
+        }
+
+        log::info!("{} bytes differ from {} in {} ranges", diff.bytes_differing, baseline_path, diff.ranges.len());
+        CmdResult::ok(diff)
+    })
+}
+
+/// Get flash chip database, including any user-loaded entries
+#[tauri::command]
+fn get_chip_database(state: State<'_, Arc<AppState>>) -> Vec<FlashChip> {
+    state.chip_database.lock().clone()
+}
+
+/// Search the chip database by name, manufacturer or JEDEC ID hex
+#[tauri::command]
+fn search_chips(state: State<'_, Arc<AppState>>, query: String) -> Vec<FlashChip> {
+    flash::search_chips(&state.chip_database.lock(), &query)
+}
+
+/// Get the chip database entries from one manufacturer
+#[tauri::command]
+fn chips_by_manufacturer(state: State<'_, Arc<AppState>>, name: String) -> Vec<FlashChip> {
+    flash::chips_by_manufacturer(&state.chip_database.lock(), &name)
+}
+
+/// Load user chip definitions from a JSON file and merge them over the built-ins
+///
+/// Returns the number of custom entries loaded.
+#[tauri::command]
+fn load_chip_database(state: State<'_, Arc<AppState>>, path: String) -> CmdResult<usize> {
+    let text = match std::fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) => return CmdResult::fail(ErrorCode::FileIo, format!("Failed to read file: {}", e)),
+    };
+
+    let custom: Vec<FlashChip> = match serde_json::from_str(&text) {
+        Ok(c) => c,
+        Err(e) => return CmdResult::err(format!("Invalid chip database: {}", e)),
+    };
+
+    for chip in &custom {
+        if let Err(e) = chip.validate() {
+            return CmdResult::err(format!("Invalid chip definition: {}", e));
+        }
+    }
+
+    let count = custom.len();
+    *state.chip_database.lock() = merge_chip_database(get_flash_database(), custom);
+
+    CmdResult::ok(count)
+}
+
+/// Save the current chip database, built-ins plus custom entries, as JSON
+///
+/// The file loads back through `load_chip_database` to the same database.
+/// Returns the number of entries written.
+#[tauri::command]
+fn export_chip_database(state: State<'_, Arc<AppState>>, path: String) -> CmdResult<usize> {
+    let database = state.chip_database.lock().clone();
+
+    let text = match serde_json::to_string_pretty(&database) {
+        Ok(t) => t,
+        Err(e) => return CmdResult::err(format!("Failed to encode chip database: {}", e)),
+    };
+
+    if let Err(e) = std::fs::write(&path, text) {
+        return CmdResult::fail(ErrorCode::FileIo, format!("Failed to save file: {}", e));
+    }
+
+    CmdResult::ok(database.len())
+}
+
+// Bumped whenever fields of `DiagnosticsReport` change meaning or go away
+const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Snapshot of the session attached to bug reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub schema_version: u32,
+    pub app_version: String,
+    pub generated_at: u64,  // Milliseconds since the Unix epoch
+    pub os: String,
+    pub device: Option<DeviceInfo>,
+    pub chip: Option<ChipInfo>,
+    pub spi: Option<SpiConfig>,
+    pub last_operation: Option<OperationOutcome>,
+    pub log: Vec<logging::LogEntry>,
+}
+
+/// Write a JSON diagnostics report to share with maintainers
+///
+/// The adapter serial is left out (and masked in log lines) unless
+/// `include_serial` is set.
+#[tauri::command]
+fn export_report(
+    state: State<'_, Arc<AppState>>,
+    path: String,
+    include_serial: Option<bool>,
+) -> CmdResult<()> {
+    let programmer_guard = match state.try_programmer() {
+        Some(guard) => guard,
+        None => return CmdResult::fail(ErrorCode::Busy, BUSY),
+    };
+
+    let mut device = None;
+    let mut spi = None;
+    if let Some(programmer) = programmer_guard.as_ref() {
+        device = programmer.device().get_info().ok().map(|info| DeviceInfo::from_device(info, true));
+        spi = Some(SpiConfig::of(programmer));
+    }
+    drop(programmer_guard);
+
+    let mut log = logging::recent();
+    if !include_serial.unwrap_or(false) {
+        if let Some(serial) = device.as_mut().and_then(|d| d.serial.take()).filter(|s| !s.is_empty()) {
+            for entry in &mut log {
+                entry.message = entry.message.replace(&serial, "<serial>");
+            }
+        }
+    }
+
+    let report = DiagnosticsReport {
+        schema_version: REPORT_SCHEMA_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        generated_at: logging::now_millis(),
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        device,
+        chip: state.current_chip.lock().as_ref().map(ChipInfo::from_chip),
+        spi,
+        last_operation: state.last_outcome.lock().clone(),
+        log,
+    };
+
+    let text = match serde_json::to_string_pretty(&report) {
+        Ok(t) => t,
+        Err(e) => return CmdResult::err(format!("Failed to encode report: {}", e)),
+    };
+
+    if let Err(e) = std::fs::write(&path, text) {
+        return CmdResult::fail(ErrorCode::FileIo, format!("Failed to save file: {}", e));
+    }
+
+    log::info!("Saved diagnostics report to {}", path);
+    CmdResult::ok(())
 }
 
 /// List connected devices
@@ -419,16 +3913,11 @@ fn list_devices() -> CmdResult<Vec<DeviceInfo>> {
         Ok(devices) => {
             let infos: Vec<DeviceInfo> = devices
                 .into_iter()
-                .map(|d| DeviceInfo {
-                    connected: false,
-                    vid: Some(d.vid),
-                    pid: Some(d.pid),
-                    name: Some(d.product),
-                })
+                .map(|d| DeviceInfo::from_device(d, false))
                 .collect();
             CmdResult::ok(infos)
         }
-        Err(e) => CmdResult::err(format!("Failed to list devices: {}", e)),
+        Err(e) => CmdResult::fail(ErrorCode::of(&e), format!("Failed to list devices: {}", e)),
     }
 }
 
@@ -443,18 +3932,171 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .manage(Arc::new(AppState::default()))
+        .setup(|app| {
+            // Forward backend log records to the frontend log panel
+            let log_handle = app.handle().clone();
+            logging::init(move |entry| {
+                let _ = log_handle.emit("log", entry);
+            });
+
+            // Emit device-changed events and drop the connection if our adapter goes away
+            let handle = app.handle().clone();
+            let state = app.state::<Arc<AppState>>().inner().clone();
+            let watcher = HotplugWatcher::start(move |event| {
+                state.handle_device_event(&event);
+                let _ = handle.emit("device-changed", DeviceInfo::from_device(event.info, event.arrived));
+            });
+            *app.state::<Arc<AppState>>().hotplug.lock() = Some(watcher);
+
+            // Restore saved settings; without a config dir the defaults stay
+            let state = app.state::<Arc<AppState>>();
+            if let Ok(dir) = app.path().app_config_dir() {
+                let path = dir.join(settings::FILE_NAME);
+                apply_settings(&state, settings::load(&path));
+                *state.settings_path.lock() = Some(path);
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             connect,
+            connect_with,
+            reconnect,
             disconnect,
             is_connected,
+            set_clock_speed,
+            get_spi_config,
+            set_spi_mode,
+            set_bit_order,
+            set_usb_retries,
+            set_usb_timeout,
+            set_stuck_grace,
+            set_strict_erase_check,
+            set_log_level,
+            load_settings,
+            save_settings,
             detect_chip,
+            read_jedec,
+            set_chip_select,
+            diagnostics,
+            select_chip,
+            reset_chip,
+            power_down,
+            wake_up,
+            read_unique_id,
+            dump_sfdp,
+            read_status_registers,
+            write_status_register,
+            read_config_register,
+            write_config_register,
+            unlock_flash,
+            lock_flash,
+            read_otp,
+            write_otp,
             read_flash,
+            read_region,
+            read_to_buffer,
+            hex_dump,
+            plan_write,
+            simulate_write,
             write_flash,
+            write_flash_timed,
+            write_protect_flow,
+            write_flash_resume,
+            program_all,
+            write_region,
+            write_flash_diff,
             erase_chip,
+            self_test,
+            benchmark,
+            measure_clock,
+            erase_range,
+            fill_region,
             verify_flash,
+            verify_sectors,
+            sector_hashes,
+            blank_check,
+            checksum,
+            flash_digest,
+            set_gpio,
+            get_gpio,
+            i2c_scan,
+            get_eeprom_types,
+            read_eeprom,
+            write_eeprom,
+            cancel_operation,
+            recover,
+            pause_operation,
+            resume_operation,
+            diff_files,
+            make_patch,
             get_chip_database,
+            search_chips,
+            chips_by_manufacturer,
+            load_chip_database,
+            export_chip_database,
+            export_report,
             list_devices,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Stop the watcher so its libusb callback is deregistered
+            if let RunEvent::Exit = event {
+                if let Some(mut watcher) = app.state::<Arc<AppState>>().hotplug.lock().take() {
+                    watcher.stop();
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated `name` from the database, ready to be programmed
+    fn simulated(name: &str) -> (FlashChip, FlashProgrammer<MockFlash>) {
+        let chip = get_flash_database().into_iter().find(|c| c.name == name).unwrap();
+        let mut programmer = FlashProgrammer::with_backend(MockFlash::new(&chip));
+        programmer.set_chip(chip.clone());
+        (chip, programmer)
+    }
+
+    #[test]
+    fn self_test_runs_on_macronix_parts() {
+        // The mock answers 0x15 with 0xFF, so the CR bit where WPS would be is set
+        let (chip, mut programmer) = simulated("MX25L12835F");
+        let state = AppState::default();
+        let last = chip.size - chip.sector_size;
+        let original: Vec<u8> = (0..chip.sector_size).map(|i| (i % 7) as u8).collect();
+        programmer.write(last as u32, &original, None).unwrap();
+
+        ensure_writable(&mut programmer, "the self-test").unwrap();
+        let report = self_test_sector(&state, &mut programmer, &chip, &|_| {});
+        assert!(report.passed, "{:?}", report);
+        assert!(report.restored);
+        assert!(programmer.verify(last as u32, &original, None).unwrap());
+    }
+
+    #[test]
+    fn protected_chips_are_refused() {
+        let (_, mut programmer) = simulated("MX25L12835F");
+        programmer.lock_all().unwrap();
+
+        let e = ensure_writable(&mut programmer, "the benchmark").unwrap_err();
+        assert_eq!(e.message, "Chip is write protected; unlock it before running the benchmark");
+    }
+
+    #[test]
+    fn locks_are_confirmed_from_the_bp_bits() {
+        let (_, mut programmer) = simulated("MX25L12835F");
+
+        for level in [LockLevel::UpperQuarter, LockLevel::All] {
+            programmer.lock(level).unwrap();
+            confirm_lock(programmer.read_status().unwrap(), level).unwrap();
+        }
+
+        // A part that ignored the lock command still has BP clear
+        assert!(confirm_lock(0x00, LockLevel::All).is_err());
+        assert!(confirm_lock(LockLevel::UpperHalf.bp_bits(), LockLevel::All).is_err());
+    }
 }
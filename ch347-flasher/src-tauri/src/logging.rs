@@ -0,0 +1,102 @@
+//! Application Logging
+//!
+//! Forwards this crate's `log` records to a sink so the frontend can show
+//! an in-app log (no terminal needed for bug reports)
+
+use log::{LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Level used until the frontend changes it
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+// Entries kept for diagnostics reports
+const RECENT_LIMIT: usize = 500;
+
+/// One log line as sent to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub level: String,
+    pub timestamp: u64,  // Milliseconds since the Unix epoch
+    pub message: String,
+}
+
+type Sink = Box<dyn Fn(&LogEntry) + Send + Sync>;
+
+struct EventLogger {
+    sink: Mutex<Option<Sink>>,
+    recent: Mutex<VecDeque<LogEntry>>,
+}
+
+static LOGGER: EventLogger = EventLogger {
+    sink: parking_lot::const_mutex(None),
+    recent: parking_lot::const_mutex(VecDeque::new()),
+};
+
+impl Log for EventLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only our own records; tauri/wry logging would flood the view
+        metadata.level() <= log::max_level()
+            && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let entry = LogEntry {
+            level: record.level().to_string(),
+            timestamp: now_millis(),
+            message: record.args().to_string(),
+        };
+
+        if let Some(sink) = LOGGER.sink.lock().as_ref() {
+            sink(&entry);
+        }
+
+        let mut recent = LOGGER.recent.lock();
+        if recent.len() == RECENT_LIMIT {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Milliseconds since the Unix epoch, as used for log timestamps
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Install the logger, delivering each entry to `sink`
+pub fn init<F>(sink: F)
+where
+    F: Fn(&LogEntry) + Send + Sync + 'static,
+{
+    *LOGGER.sink.lock() = Some(Box::new(sink));
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+/// Change verbosity ("off", "error", "warn", "info", "debug" or "trace")
+pub fn set_level(level: &str) -> Result<LevelFilter, String> {
+    let filter: LevelFilter = level
+        .parse()
+        .map_err(|_| format!("Unknown log level: {}", level))?;
+    log::set_max_level(filter);
+    Ok(filter)
+}
+
+/// The most recent entries, oldest first
+pub fn recent() -> Vec<LogEntry> {
+    LOGGER.recent.lock().iter().cloned().collect()
+}
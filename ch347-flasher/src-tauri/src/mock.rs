@@ -0,0 +1,184 @@
+//! Simulated SPI Flash
+//!
+//! An in-memory NOR flash behind the `SpiBackend` trait, so write/verify
+//! logic can be exercised (and demonstrated) without a CH347 attached
+
+use crate::ch347::{BitOrder, Result, SpiClock, SpiMode};
+use crate::flash::{
+    EraseOp, FlashChip, SpiBackend, CMD_BLOCK_ERASE_32K, CMD_BLOCK_ERASE_32K_4B, CMD_BLOCK_ERASE_64K, CMD_BLOCK_ERASE_64K_4B,
+    CMD_CHIP_ERASE, CMD_CHIP_ERASE_ALT, CMD_DUAL_OUTPUT_READ, CMD_FAST_READ, CMD_FAST_READ_4B, CMD_PAGE_PROGRAM,
+    CMD_PAGE_PROGRAM_4B, CMD_QUAD_OUTPUT_READ, CMD_READ_DATA, CMD_READ_DATA_4B, CMD_READ_JEDEC_ID, CMD_READ_STATUS,
+    CMD_SECTOR_ERASE, CMD_SECTOR_ERASE_4B, CMD_WRITE_DISABLE, CMD_WRITE_ENABLE, CMD_WRITE_STATUS, CMD_WRITE_STATUS2,
+    CMD_WRITE_STATUS3, STATUS_WEL, STATUS_WIP,
+};
+
+/// A flash chip modelled as a byte array
+///
+/// Erases set bytes to 0xFF and page programs can only clear bits (new data
+/// is ANDed in), wrapping within the page like real parts. Every operation
+/// completes instantly, so WIP never reads as set. Opcodes it doesn't model
+/// read back as 0xFF, as from an undriven bus.
+pub struct MockFlash {
+    memory: Vec<u8>,
+    jedec_id: [u8; 3],
+    page_size: usize,
+    d8_erase_size: usize,  // 64KB, or 256KB on uniform 256KB-sector parts
+    status: u8,
+    command: Vec<u8>,  // Bytes written since chip select was asserted
+    read_pos: usize,   // Bytes read back in the current transaction
+    erases: usize,
+    pages_programmed: usize,
+}
+
+impl MockFlash {
+    /// A blank chip with the geometry and JEDEC ID of `chip`
+    pub fn new(chip: &FlashChip) -> Self {
+        Self {
+            memory: vec![0xFF; chip.size],
+            jedec_id: chip.jedec_id,
+            page_size: chip.page_size,
+            d8_erase_size: if chip.erase_ops.contains(&EraseOp::Block256K) { 256 * 1024 } else { 64 * 1024 },
+            status: 0,
+            command: Vec::new(),
+            read_pos: 0,
+            erases: 0,
+            pages_programmed: 0,
+        }
+    }
+
+    /// Sector, block and chip erases carried out
+    pub fn erases(&self) -> usize {
+        self.erases
+    }
+
+    /// Page program commands carried out
+    pub fn pages_programmed(&self) -> usize {
+        self.pages_programmed
+    }
+
+    /// 24-bit address following the opcode, wrapped to the chip size
+    fn address(&self) -> usize {
+        self.address_of_len(3)
+    }
+
+    /// Big-endian address of `len` bytes following the opcode, wrapped to the chip size
+    fn address_of_len(&self, len: usize) -> usize {
+        match self.command.get(1..1 + len) {
+            Some(a) => a.iter().fold(0, |addr, &b| addr << 8 | b as usize) % self.memory.len(),
+            None => 0,
+        }
+    }
+
+    /// Byte the chip shifts out next for the current command
+    fn next_byte(&self) -> u8 {
+        let opcode = self.command.first().copied();
+        let pos = self.read_pos;
+
+        match opcode {
+            Some(CMD_READ_JEDEC_ID) => self.jedec_id.get(pos).copied().unwrap_or(0xFF),
+            Some(CMD_READ_STATUS) => self.status,
+            Some(CMD_READ_DATA) if self.command.len() >= 4 => {
+                self.memory[(self.address() + pos) % self.memory.len()]
+            }
+            Some(CMD_FAST_READ | CMD_DUAL_OUTPUT_READ | CMD_QUAD_OUTPUT_READ) if self.command.len() >= 5 => {
+                self.memory[(self.address() + pos) % self.memory.len()]
+            }
+            Some(CMD_READ_DATA_4B) if self.command.len() >= 5 => {
+                self.memory[(self.address_of_len(4) + pos) % self.memory.len()]
+            }
+            Some(CMD_FAST_READ_4B) if self.command.len() >= 6 => {
+                self.memory[(self.address_of_len(4) + pos) % self.memory.len()]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    /// Carry out the buffered command when chip select is released
+    fn execute(&mut self) {
+        let opcode = match self.command.first() {
+            Some(&op) => op,
+            None => return,
+        };
+        let write_enabled = self.status & STATUS_WEL != 0;
+
+        // The 4-byte address variants differ only in address length
+        let address_len = match opcode {
+            CMD_PAGE_PROGRAM_4B | CMD_SECTOR_ERASE_4B | CMD_BLOCK_ERASE_32K_4B | CMD_BLOCK_ERASE_64K_4B => 4,
+            _ => 3,
+        };
+
+        match opcode {
+            CMD_WRITE_ENABLE => self.status |= STATUS_WEL,
+            CMD_WRITE_DISABLE => self.status &= !STATUS_WEL,
+            CMD_PAGE_PROGRAM | CMD_PAGE_PROGRAM_4B if write_enabled && self.command.len() > 1 + address_len => {
+                let addr = self.address_of_len(address_len);
+                let page_start = addr - addr % self.page_size;
+                for (i, &byte) in self.command[1 + address_len..].iter().enumerate() {
+                    let offset = (addr - page_start + i) % self.page_size;
+                    self.memory[page_start + offset] &= byte;
+                }
+                self.pages_programmed += 1;
+                self.status &= !STATUS_WEL;
+            }
+            CMD_SECTOR_ERASE | CMD_BLOCK_ERASE_32K | CMD_BLOCK_ERASE_64K | CMD_SECTOR_ERASE_4B
+            | CMD_BLOCK_ERASE_32K_4B | CMD_BLOCK_ERASE_64K_4B
+                if write_enabled && self.command.len() > address_len =>
+            {
+                let size = match opcode {
+                    CMD_SECTOR_ERASE | CMD_SECTOR_ERASE_4B => 4 * 1024,
+                    CMD_BLOCK_ERASE_32K | CMD_BLOCK_ERASE_32K_4B => 32 * 1024,
+                    _ => self.d8_erase_size,
+                }
+                .min(self.memory.len());
+                let addr = self.address_of_len(address_len);
+                let start = addr - addr % size;
+                self.memory[start..start + size].fill(0xFF);
+                self.erases += 1;
+                self.status &= !STATUS_WEL;
+            }
+            CMD_CHIP_ERASE | CMD_CHIP_ERASE_ALT if write_enabled => {
+                self.memory.fill(0xFF);
+                self.erases += 1;
+                self.status &= !STATUS_WEL;
+            }
+            // SR1 keeps its protection bits; SR2/SR3 aren't modelled, but the write still uses up WEL
+            CMD_WRITE_STATUS if write_enabled && self.command.len() > 1 => {
+                self.status = self.command[1] & !(STATUS_WIP | STATUS_WEL);
+            }
+            CMD_WRITE_STATUS | CMD_WRITE_STATUS2 | CMD_WRITE_STATUS3 => self.status &= !STATUS_WEL,
+            _ => {}
+        }
+    }
+}
+
+impl SpiBackend for MockFlash {
+    fn spi_init_mode(&mut self, _clock: SpiClock, _mode: SpiMode, _bit_order: BitOrder) -> Result<()> {
+        Ok(())
+    }
+
+    fn spi_cs(&mut self, _cs_line: u8, assert: bool) -> Result<()> {
+        if !assert {
+            self.execute();
+        }
+        self.command.clear();
+        self.read_pos = 0;
+        Ok(())
+    }
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<()> {
+        self.command.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn spi_read(&mut self, data: &mut [u8]) -> Result<()> {
+        for byte in data.iter_mut() {
+            *byte = self.next_byte();
+            self.read_pos += 1;
+        }
+        Ok(())
+    }
+
+    fn gpio_set(&mut self, _pin: u8, _level: bool) -> Result<()> {
+        Ok(())
+    }
+}
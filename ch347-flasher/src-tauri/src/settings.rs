@@ -0,0 +1,59 @@
+//! Persistent Settings
+//!
+//! Connection defaults kept as JSON in the app config directory so they
+//! survive restarts
+
+use crate::ch347::SpiClock;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// File name inside the app config directory
+pub const FILE_NAME: &str = "settings.json";
+
+/// User preferences applied at startup and on connect
+///
+/// Missing fields take their default, so older files keep loading.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub clock_khz: u32,
+    pub serial: Option<String>,  // Adapter to open when none is given
+    pub interface: Option<u8>,   // SPI interface override for unusual firmware
+    pub verify: bool,            // Verify after write
+    pub log_level: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            clock_khz: SpiClock::default().hz() / 1000,
+            serial: None,
+            interface: None,
+            verify: true,
+            log_level: "info".into(),
+        }
+    }
+}
+
+/// Load settings, falling back to defaults if the file is missing or corrupt
+pub fn load(path: &Path) -> Settings {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(_) => return Settings::default(),
+    };
+
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid settings file {}: {}", path.display(), e);
+        Settings::default()
+    })
+}
+
+/// Write settings, creating the config directory if needed
+pub fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let text = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(path, text).map_err(|e| format!("Failed to save settings: {}", e))
+}
@@ -0,0 +1,180 @@
+//! SFDP (Serial Flash Discoverable Parameters) parsing
+//!
+//! Decodes the JEDEC JESD216 header and Basic Flash Parameter Table so
+//! chips missing from the database can still be configured correctly
+
+use serde::{Deserialize, Serialize};
+
+// SFDP signature "SFDP" (little-endian)
+pub const SFDP_SIGNATURE: u32 = 0x5044_4653;
+
+// Parameter header ID of the JEDEC Basic Flash Parameter Table
+pub const BFPT_ID: u16 = 0xFF00;
+
+pub const HEADER_LEN: usize = 8;
+pub const PARAM_HEADER_LEN: usize = 8;
+
+/// Erase type advertised by the chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EraseType {
+    pub size: usize,
+    pub opcode: u8,
+    pub max_ms: Option<u32>,  // Worst-case erase time (JESD216A+)
+}
+
+/// Flash parameters decoded from the Basic Flash Parameter Table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashParams {
+    pub size: usize,               // Total size in bytes
+    pub page_size: usize,          // Page size (256 if not reported)
+    pub address_bytes: u8,         // 3 or 4
+    pub erase_types: Vec<EraseType>,  // Sorted smallest first
+    pub chip_erase_ms: Option<u32>,   // Worst-case chip erase time (JESD216A+)
+}
+
+/// SFDP parameter header
+#[derive(Debug, Clone, Copy)]
+pub struct ParamHeader {
+    pub id: u16,
+    pub major: u8,
+    pub length_dwords: usize,
+    pub pointer: usize,
+}
+
+fn dword(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Check signature and return the parameter headers that follow it
+pub fn parse_headers(bytes: &[u8]) -> Option<Vec<ParamHeader>> {
+    if dword(bytes, 0)? != SFDP_SIGNATURE {
+        return None;
+    }
+
+    // NPH is zero-based
+    let count = *bytes.get(6)? as usize + 1;
+    let mut headers = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let off = HEADER_LEN + i * PARAM_HEADER_LEN;
+        let h = bytes.get(off..off + PARAM_HEADER_LEN)?;
+        headers.push(ParamHeader {
+            id: ((h[7] as u16) << 8) | h[0] as u16,
+            major: h[2],
+            length_dwords: h[3] as usize,
+            pointer: (h[4] as usize) | ((h[5] as usize) << 8) | ((h[6] as usize) << 16),
+        });
+    }
+
+    Some(headers)
+}
+
+/// Total number of bytes covered by the header and all parameter tables
+pub fn table_extent(headers: &[ParamHeader]) -> usize {
+    let header_end = HEADER_LEN + headers.len() * PARAM_HEADER_LEN;
+    headers
+        .iter()
+        .map(|h| h.pointer + h.length_dwords * 4)
+        .fold(header_end, std::cmp::max)
+}
+
+/// Parse the Basic Flash Parameter Table from a raw SFDP dump
+///
+/// `bytes` must start at SFDP address 0 so table pointers can be used directly.
+pub fn parse_sfdp(bytes: &[u8]) -> Option<FlashParams> {
+    let headers = parse_headers(bytes)?;
+    let bfpt = headers.iter().find(|h| h.id == BFPT_ID && h.major == 1)?;
+
+    // Need at least DWORD 1 (address bytes) and DWORD 2 (density)
+    if bfpt.length_dwords < 2 {
+        return None;
+    }
+    let table = bytes.get(bfpt.pointer..bfpt.pointer + bfpt.length_dwords * 4)?;
+    let dw = |n: usize| dword(table, (n - 1) * 4);
+
+    // DWORD 2: density in bits
+    let density = dw(2)?;
+    let size = if density & 0x8000_0000 != 0 {
+        let exp = density & 0x7FFF_FFFF;
+        if !(3..64).contains(&exp) {
+            return None;
+        }
+        1usize.checked_shl(exp - 3)?
+    } else {
+        (density as usize + 1) / 8
+    };
+
+    // DWORD 1: address bytes (bits 18:17) and legacy 4KB erase opcode
+    let dw1 = dw(1)?;
+    let address_bytes = match (dw1 >> 17) & 0x03 {
+        0 => 3,
+        1 if size > 16 * 1024 * 1024 => 4,
+        1 => 3,
+        2 => 4,
+        _ => 3,
+    };
+
+    // DWORD 10: typical erase times for types 1-4 and the max-time multiplier
+    let dw10 = if bfpt.length_dwords >= 10 { dw(10) } else { None };
+    let erase_max_ms = |index: usize| {
+        dw10.map(|d| {
+            let field = (d >> (4 + 7 * index)) & 0x7F;
+            let unit_ms = [1, 16, 128, 1000][(field >> 5) as usize];
+            max_time_ms(field & 0x1F, unit_ms, d & 0x0F)
+        })
+    };
+
+    // DWORDs 8-9: erase types 1-4 as (size exponent, opcode) pairs
+    let mut erase_types = Vec::new();
+    if bfpt.length_dwords >= 9 {
+        for (n, dword_no) in [8, 9].into_iter().enumerate() {
+            let d = dw(dword_no)?;
+            for (h, half) in [d & 0xFFFF, d >> 16].into_iter().enumerate() {
+                let exp = half & 0xFF;
+                if exp != 0 && exp < 32 {
+                    erase_types.push(EraseType {
+                        size: 1 << exp,
+                        opcode: (half >> 8) as u8,
+                        max_ms: erase_max_ms(n * 2 + h),
+                    });
+                }
+            }
+        }
+    }
+    if erase_types.is_empty() && (dw1 & 0x03) == 0x01 {
+        erase_types.push(EraseType {
+            size: 4096,
+            opcode: ((dw1 >> 8) & 0xFF) as u8,
+            max_ms: None,
+        });
+    }
+    erase_types.sort_by_key(|e| e.size);
+
+    // DWORD 11 (JESD216A+): page size exponent in bits 7:4, typical chip
+    // erase time in bits 30:24 and the program/chip erase multiplier in 3:0
+    let (page_size, chip_erase_ms) = if bfpt.length_dwords >= 11 {
+        let d = dw(11)?;
+        let field = (d >> 24) & 0x7F;
+        let unit_ms = [16, 256, 4000, 64000][(field >> 5) as usize];
+        (1 << ((d >> 4) & 0x0F), Some(max_time_ms(field & 0x1F, unit_ms, d & 0x0F)))
+    } else {
+        (256, None)
+    };
+
+    Some(FlashParams {
+        size,
+        page_size,
+        address_bytes,
+        erase_types,
+        chip_erase_ms,
+    })
+}
+
+/// Worst-case time from an SFDP typical-time field: (count + 1) units,
+/// times 2 * (multiplier + 1)
+fn max_time_ms(count: u32, unit_ms: u32, multiplier: u32) -> u32 {
+    (count + 1)
+        .saturating_mul(unit_ms)
+        .saturating_mul(2 * (multiplier + 1))
+}
@@ -0,0 +1,168 @@
+//! Motorola S-Record Format
+//!
+//! Parses and generates S19/S28/S37 files: S1/S2/S3 data records with the
+//! matching S9/S8/S7 termination, S5/S6 record counts and an optional S0 header
+
+use thiserror::Error;
+
+// Data bytes per generated record
+pub const BYTES_PER_RECORD: usize = 32;
+
+// Header text written into the S0 record
+pub const HEADER: &[u8] = b"ch347-flasher";
+
+#[derive(Error, Debug)]
+pub enum SrecError {
+    #[error("line {0}: missing 'S' start code")]
+    MissingStartCode(usize),
+
+    #[error("line {0}: invalid hex digits")]
+    InvalidDigits(usize),
+
+    #[error("line {0}: record length does not match byte count")]
+    LengthMismatch(usize),
+
+    #[error("line {0}: checksum mismatch (expected {1:02X}, got {2:02X})")]
+    Checksum(usize, u8, u8),
+
+    #[error("line {0}: unsupported record type S{1}")]
+    UnsupportedRecord(usize, char),
+
+    #[error("line {0}: S{1} record mixes address widths with earlier S{2} records")]
+    MixedAddressWidth(usize, char, char),
+
+    #[error("line {0}: record count {1} does not match {2} data records")]
+    CountMismatch(usize, u32, u32),
+}
+
+pub type Result<T> = std::result::Result<T, SrecError>;
+
+/// Address bytes used by a data or termination record type
+fn address_len(record_type: char) -> Option<usize> {
+    match record_type {
+        '0' | '1' | '5' | '9' => Some(2),
+        '2' | '6' | '8' => Some(3),
+        '3' | '7' => Some(4),
+        _ => None,
+    }
+}
+
+/// Parse S-record text into contiguous `(address, bytes)` segments
+///
+/// All data records must share one address width (S1, S2 or S3).
+pub fn parse_srec(text: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+    let mut segments: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut data_type: Option<char> = None;
+    let mut data_records: u32 = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let rest = line.strip_prefix('S').ok_or(SrecError::MissingStartCode(line_no))?;
+        let mut chars = rest.chars();
+        let record_type = chars.next().ok_or(SrecError::LengthMismatch(line_no))?;
+        let addr_len = address_len(record_type)
+            .ok_or(SrecError::UnsupportedRecord(line_no, record_type))?;
+
+        let bytes = ::hex::decode(chars.as_str()).map_err(|_| SrecError::InvalidDigits(line_no))?;
+
+        // Byte count covers address, data and checksum
+        if bytes.len() < 1 + addr_len + 1 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(SrecError::LengthMismatch(line_no));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = record_checksum(body);
+        if expected != checksum[0] {
+            return Err(SrecError::Checksum(line_no, expected, checksum[0]));
+        }
+
+        let address = body[1..1 + addr_len]
+            .iter()
+            .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        let data = &body[1 + addr_len..];
+
+        match record_type {
+            '0' => {}
+            '1' | '2' | '3' => {
+                match data_type {
+                    Some(t) if t != record_type => {
+                        return Err(SrecError::MixedAddressWidth(line_no, record_type, t));
+                    }
+                    _ => data_type = Some(record_type),
+                }
+                data_records += 1;
+
+                match segments.last_mut() {
+                    Some((start, seg)) if *start + seg.len() as u32 == address => {
+                        seg.extend_from_slice(data);
+                    }
+                    _ => segments.push((address, data.to_vec())),
+                }
+            }
+            '5' | '6' => {
+                if address != data_records {
+                    return Err(SrecError::CountMismatch(line_no, address, data_records));
+                }
+            }
+            _ => break, // S7/S8/S9 terminate the file
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Generate S-record text for `data` located at `base`
+///
+/// The narrowest address width that covers the whole range is used.
+pub fn to_srec(base: u32, data: &[u8]) -> String {
+    let end = base as u64 + data.len() as u64;
+    let (data_type, term_type) = if end <= 0x1_0000 {
+        ('1', '9')
+    } else if end <= 0x100_0000 {
+        ('2', '8')
+    } else {
+        ('3', '7')
+    };
+
+    let mut out = String::new();
+    push_record(&mut out, '0', 0, HEADER);
+
+    let mut count: u32 = 0;
+    for (i, chunk) in data.chunks(BYTES_PER_RECORD).enumerate() {
+        push_record(&mut out, data_type, base + (i * BYTES_PER_RECORD) as u32, chunk);
+        count += 1;
+    }
+
+    if count <= 0xFFFF {
+        push_record(&mut out, '5', count, &[]);
+    } else {
+        push_record(&mut out, '6', count, &[]);
+    }
+
+    push_record(&mut out, term_type, 0, &[]);
+    out
+}
+
+fn push_record(out: &mut String, record_type: char, address: u32, data: &[u8]) {
+    let addr_len = address_len(record_type).unwrap_or(4);
+    let mut body = Vec::with_capacity(1 + addr_len + data.len());
+    body.push((addr_len + data.len() + 1) as u8);
+    body.extend_from_slice(&address.to_be_bytes()[4 - addr_len..]);
+    body.extend_from_slice(data);
+
+    out.push('S');
+    out.push(record_type);
+    out.push_str(&::hex::encode_upper(&body));
+    out.push_str(&format!("{:02X}\n", record_checksum(&body)));
+}
+
+/// One's complement of the byte sum
+fn record_checksum(body: &[u8]) -> u8 {
+    !body.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+}
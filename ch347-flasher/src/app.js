@@ -7,9 +7,15 @@ const { readFile } = window.__TAURI__.fs;
 
 // State
 let isConnected = false;
+let hasConnected = false;  // A connect succeeded, so reconnect has an adapter to reopen
 let chipDetected = false;
+let chipHasUniqueId = false;
 let currentFile = null;
 let isBusy = false;
+let isPaused = false;     // Running operation is held at a chunk boundary
+let resumeOffset = null;  // Offset an interrupted write can continue from
+let settings = null;      // Persisted preferences, loaded at startup
+const deviceProgress = new Map();  // Latest progress per adapter during Program All
 
 // HEX Viewer State
 let fileData = null;
@@ -31,13 +37,39 @@ const elements = {
 
     btnConnect: document.getElementById('btnConnect'),
     btnDisconnect: document.getElementById('btnDisconnect'),
+    btnReconnect: document.getElementById('btnReconnect'),
+    btnI2cScan: document.getElementById('btnI2cScan'),
+    btnProgramAll: document.getElementById('btnProgramAll'),
     btnDetect: document.getElementById('btnDetect'),
+    btnUniqueId: document.getElementById('btnUniqueId'),
+    btnSelfTest: document.getElementById('btnSelfTest'),
+    btnBenchmark: document.getElementById('btnBenchmark'),
+    btnMeasureClock: document.getElementById('btnMeasureClock'),
+    btnDumpSfdp: document.getElementById('btnDumpSfdp'),
+    btnReadJedec: document.getElementById('btnReadJedec'),
+    btnDiagnostics: document.getElementById('btnDiagnostics'),
+    btnSpiConfig: document.getElementById('btnSpiConfig'),
     btnBrowse: document.getElementById('btnBrowse'),
     btnRead: document.getElementById('btnRead'),
     btnWrite: document.getElementById('btnWrite'),
     btnVerify: document.getElementById('btnVerify'),
     btnErase: document.getElementById('btnErase'),
+    btnCancel: document.getElementById('btnCancel'),
+    btnPause: document.getElementById('btnPause'),
+    btnRecover: document.getElementById('btnRecover'),
+    btnExportReport: document.getElementById('btnExportReport'),
+    reportSerial: document.getElementById('reportSerial'),
     verifyAfterWrite: document.getElementById('verifyAfterWrite'),
+    verifyPerPage: document.getElementById('verifyPerPage'),
+    eraseStrategy: document.getElementById('eraseStrategy'),
+    skipBlankErase: document.getElementById('skipBlankErase'),
+    verifyFileOnly: document.getElementById('verifyFileOnly'),
+    verifyCrc: document.getElementById('verifyCrc'),
+    clockSpeed: document.getElementById('clockSpeed'),
+    chipSelect: document.getElementById('chipSelect'),
+    spiMode: document.getElementById('spiMode'),
+    bitOrder: document.getElementById('bitOrder'),
+    spiInterface: document.getElementById('spiInterface'),
 
     // HEX Viewer
     hexContent: document.getElementById('hexContent'),
@@ -60,12 +92,33 @@ async function init() {
     // Set up event listeners
     elements.btnConnect.addEventListener('click', connect);
     elements.btnDisconnect.addEventListener('click', disconnect);
-    elements.btnDetect.addEventListener('click', detectChip);
+    elements.btnReconnect.addEventListener('click', reconnect);
+    elements.btnI2cScan.addEventListener('click', i2cScan);
+    elements.btnProgramAll.addEventListener('click', programAll);
+    elements.btnDetect.addEventListener('click', () => detectChip('detect_chip', { robust: true }));
+    elements.btnUniqueId.addEventListener('click', readUniqueId);
+    elements.btnSelfTest.addEventListener('click', selfTest);
+    elements.btnBenchmark.addEventListener('click', benchmark);
+    elements.btnMeasureClock.addEventListener('click', measureClock);
+    elements.btnDumpSfdp.addEventListener('click', dumpSfdp);
+    elements.btnReadJedec.addEventListener('click', readJedec);
+    elements.btnDiagnostics.addEventListener('click', diagnostics);
+    elements.btnSpiConfig.addEventListener('click', showSpiConfig);
     elements.btnBrowse.addEventListener('click', browseFile);
     elements.btnRead.addEventListener('click', readFlash);
     elements.btnWrite.addEventListener('click', writeFlash);
     elements.btnVerify.addEventListener('click', verifyFlash);
     elements.btnErase.addEventListener('click', eraseChip);
+    elements.btnCancel.addEventListener('click', cancelOperation);
+    elements.btnPause.addEventListener('click', togglePause);
+    elements.btnRecover.addEventListener('click', recoverDevice);
+    elements.btnExportReport.addEventListener('click', exportReport);
+    elements.clockSpeed.addEventListener('change', () => { setClockSpeed(); saveSettings(); });
+    elements.verifyAfterWrite.addEventListener('change', saveSettings);
+    elements.chipSelect.addEventListener('change', setChipSelect);
+    elements.spiMode.addEventListener('change', setSpiMode);
+    elements.bitOrder.addEventListener('change', setBitOrder);
+    elements.spiInterface.addEventListener('change', saveSettings);
 
     // Tab switching
     document.querySelectorAll('.tab-btn').forEach(btn => {
@@ -93,9 +146,68 @@ async function init() {
         updateProgress(event.payload);
     });
 
+    // Listen for adapters being plugged in or removed
+    await listen('device-changed', (event) => {
+        handleDeviceChanged(event.payload);
+    });
+
+    // Listen for backend log records
+    await listen('log', (event) => {
+        logBackend(event.payload);
+    });
+
+    await loadSettings();
+
     log('Ready. Click "Connect" to start.', 'info');
 }
 
+// Restore the saved clock speed and verify option
+async function loadSettings() {
+    try {
+        const result = await invoke('load_settings');
+        if (!result.success) return;
+
+        settings = result.data;
+        const clock = String(settings.clock_khz);
+        if ([...elements.clockSpeed.options].some(o => o.value === clock)) {
+            elements.clockSpeed.value = clock;
+        }
+        elements.verifyAfterWrite.checked = settings.verify;
+        elements.spiInterface.value = settings.interface ?? '';
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// SPI interface typed by the user, or null to use the adapter's default
+function spiInterfaceOverride() {
+    const value = elements.spiInterface.value.trim();
+    return value === '' ? null : parseInt(value);
+}
+
+// Remember the current clock speed and verify option for the next session
+async function saveSettings() {
+    if (!settings) return;
+
+    const next = {
+        ...settings,
+        clock_khz: parseInt(elements.clockSpeed.value),
+        verify: elements.verifyAfterWrite.checked,
+        interface: spiInterfaceOverride(),
+    };
+
+    try {
+        const result = await invoke('save_settings', { settings: next });
+        if (result.success) {
+            settings = next;
+        } else {
+            log(`Failed to save settings: ${result.error}`, 'warning');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
 // Tab switching
 function switchTab(tabName) {
     document.querySelectorAll('.tab-btn').forEach(btn => {
@@ -107,14 +219,38 @@ function switchTab(tabName) {
 }
 
 // Logging
-function log(message, type = 'info') {
+function log(message, type = 'info', time = new Date()) {
     const entry = document.createElement('div');
     entry.className = `log-entry ${type}`;
-    entry.textContent = `[${new Date().toLocaleTimeString()}] ${message}`;
+    entry.textContent = `[${time.toLocaleTimeString()}] ${message}`;
     elements.logContainer.appendChild(entry);
     elements.logContainer.scrollTop = elements.logContainer.scrollHeight;
 }
 
+// Show a { level, timestamp, message } record emitted by the backend
+function logBackend(record) {
+    const types = { ERROR: 'error', WARN: 'warning' };
+    log(record.message, types[record.level] || 'info', new Date(record.timestamp));
+}
+
+// Log a failed command result, reacting to its error code
+function logFailure(action, result) {
+    switch (result.error_code) {
+        case 'Cancelled':
+            log(`${action} cancelled`, 'warning');
+            break;
+        case 'NotConnected':
+            isConnected = false;
+            log(`${action} failed: ${result.error}`, 'error');
+            break;
+        case 'Busy':
+            log(`${action} skipped: another operation is in progress`, 'warning');
+            break;
+        default:
+            log(`${action} failed: ${result.error}`, 'error');
+    }
+}
+
 // Update UI state
 function updateUI() {
     // Connection status
@@ -134,30 +270,140 @@ function updateUI() {
     // Buttons
     elements.btnConnect.disabled = isConnected || isBusy;
     elements.btnDisconnect.disabled = !isConnected || isBusy;
+    elements.btnReconnect.disabled = !hasConnected || isBusy;
+    elements.btnI2cScan.disabled = !isConnected || isBusy;
+    elements.btnProgramAll.disabled = isConnected || isBusy || !currentFile;
     elements.btnDetect.disabled = !isConnected || isBusy;
+    elements.btnDumpSfdp.disabled = !isConnected || isBusy;
+    elements.btnReadJedec.disabled = !isConnected || isBusy;
+    elements.btnDiagnostics.disabled = !isConnected || isBusy;
+    elements.btnSpiConfig.disabled = !isConnected || isBusy;
 
     const opEnabled = isConnected && chipDetected && !isBusy;
     elements.btnRead.disabled = !opEnabled;
     elements.btnWrite.disabled = !opEnabled || !currentFile;
     elements.btnVerify.disabled = !opEnabled || !currentFile;
     elements.btnErase.disabled = !opEnabled;
+    elements.btnUniqueId.hidden = !chipDetected || !chipHasUniqueId;
+    elements.btnUniqueId.disabled = !opEnabled;
+    elements.btnSelfTest.disabled = !opEnabled;
+    elements.btnBenchmark.disabled = !opEnabled;
+    elements.btnMeasureClock.disabled = !opEnabled;
+    elements.btnCancel.disabled = !isBusy;
+    if (!isBusy) isPaused = false;
+    elements.btnPause.disabled = !isBusy;
+    elements.btnRecover.disabled = !isConnected;
+    elements.btnPause.textContent = isPaused ? 'Resume' : 'Pause';
 }
 
 // Update progress bar
 function updateProgress(info) {
+    if (info.device) {
+        updateDeviceProgress(info);
+        return;
+    }
+
     const percent = Math.round(info.percent);
     elements.progressFill.style.width = `${percent}%`;
-    elements.progressOperation.textContent = info.operation;
+    elements.progressOperation.textContent = info.operation + formatRate(info);
     elements.progressPercent.textContent = `${percent}%`;
 }
 
+// During Program All, show the adapter that is furthest behind
+function updateDeviceProgress(info) {
+    deviceProgress.set(info.device, info);
+
+    let slowest = info;
+    for (const p of deviceProgress.values()) {
+        if (p.percent < slowest.percent) slowest = p;
+    }
+
+    const percent = Math.round(slowest.percent);
+    elements.progressFill.style.width = `${percent}%`;
+    elements.progressOperation.textContent = `${deviceProgress.size} adapters - slowest: ${slowest.device} ${slowest.operation}`;
+    elements.progressPercent.textContent = `${percent}%`;
+}
+
+// Format throughput and time remaining, e.g. " - 3.2 MB/s, 00:42 remaining"
+function formatRate(info) {
+    if (info.percent >= 100 || (!info.bytes_per_sec && !info.eta_secs)) {
+        return '';
+    }
+
+    const parts = [];
+    if (info.bytes_per_sec) {
+        parts.push(`${(info.bytes_per_sec / (1024 * 1024)).toFixed(1)} MB/s`);
+    }
+    if (info.eta_secs) {
+        const secs = Math.round(info.eta_secs);
+        const mm = String(Math.floor(secs / 60)).padStart(2, '0');
+        const ss = String(secs % 60).padStart(2, '0');
+        parts.push(`${mm}:${ss} remaining`);
+    }
+
+    return ` - ${parts.join(', ')}`;
+}
+
 // Reset progress
 function resetProgress() {
+    deviceProgress.clear();
     elements.progressFill.style.width = '0%';
     elements.progressOperation.textContent = 'Idle';
     elements.progressPercent.textContent = '0%';
 }
 
+// Fill the device panel from a connect/reconnect result
+function showDeviceInfo(data) {
+    elements.deviceInfo.innerHTML = `
+        <div class="info-row">
+            <span class="info-label">Status:</span>
+            <span class="info-value">Connected</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">VID:PID:</span>
+            <span class="info-value">${data.vid.toString(16).toUpperCase()}:${data.pid.toString(16).toUpperCase()}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Device:</span>
+            <span class="info-value">${data.name || 'CH347'}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Variant:</span>
+            <span class="info-value">${data.variant} (interface ${data.interface})</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Serial:</span>
+            <span class="info-value">${data.serial || '-'}</span>
+        </div>
+    `;
+}
+
+// Fill the chip panel from a detection result
+function showChipInfo(chip) {
+    elements.chipInfo.innerHTML = `
+        <div class="info-row">
+            <span class="info-label">Chip:</span>
+            <span class="info-value">${chip.name}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Manufacturer:</span>
+            <span class="info-value">${chip.manufacturer}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">JEDEC ID:</span>
+            <span class="info-value">${chip.jedec_id}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Signature:</span>
+            <span class="info-value">${chip.signature ?? 'N/A'}</span>
+        </div>
+        <div class="info-row">
+            <span class="info-label">Size:</span>
+            <span class="info-value">${chip.size_str}</span>
+        </div>
+    `;
+}
+
 // Connect to device
 async function connect() {
     log('Connecting to CH347...', 'info');
@@ -165,28 +411,29 @@ async function connect() {
     updateUI();
 
     try {
-        const result = await invoke('connect');
+        const options = {
+            clock_khz: parseInt(elements.clockSpeed.value),
+            mode: parseInt(elements.spiMode.value),
+            interface: spiInterfaceOverride(),
+        };
+        const result = await invoke('connect_with', { options });
 
         if (result.success) {
             isConnected = true;
+            hasConnected = true;
             const data = result.data;
 
-            elements.deviceInfo.innerHTML = `
-                <div class="info-row">
-                    <span class="info-label">Status:</span>
-                    <span class="info-value">Connected</span>
-                </div>
-                <div class="info-row">
-                    <span class="info-label">VID:PID:</span>
-                    <span class="info-value">${data.vid.toString(16).toUpperCase()}:${data.pid.toString(16).toUpperCase()}</span>
-                </div>
-                <div class="info-row">
-                    <span class="info-label">Device:</span>
-                    <span class="info-value">${data.name || 'CH347'}</span>
-                </div>
-            `;
+            showDeviceInfo(data);
 
             log('Connected successfully!', 'success');
+
+            if (elements.bitOrder.value !== 'msb') {
+                await setBitOrder();
+            }
+
+            if (elements.chipSelect.value !== '1') {
+                await setChipSelect();
+            }
         } else {
             log(`Connection failed: ${result.error}`, 'error');
         }
@@ -198,6 +445,142 @@ async function connect() {
     updateUI();
 }
 
+// Apply selected SPI clock speed
+async function setClockSpeed() {
+    if (!isConnected) return;
+
+    try {
+        const speedKhz = parseInt(elements.clockSpeed.value);
+        const result = await invoke('set_clock_speed', { speedKhz });
+
+        if (result.success) {
+            log(`SPI clock set to ${(result.data / 1e6).toFixed(3)} MHz`, 'info');
+        } else {
+            log(`Failed to set clock: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Apply selected SPI mode (CPOL/CPHA)
+async function setSpiMode() {
+    if (!isConnected) return;
+
+    try {
+        const mode = parseInt(elements.spiMode.value);
+        const result = await invoke('set_spi_mode', { mode });
+
+        if (result.success) {
+            log(`SPI mode ${result.data.mode} at ${(result.data.clock_hz / 1e6).toFixed(3)} MHz`, 'info');
+        } else {
+            log(`Failed to set SPI mode: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Apply selected SPI bit order
+async function setBitOrder() {
+    if (!isConnected) return;
+
+    try {
+        const lsbFirst = elements.bitOrder.value === 'lsb';
+        const result = await invoke('set_bit_order', { lsbFirst });
+
+        if (result.success) {
+            log(`SPI bit order ${result.data.lsb_first ? 'LSB' : 'MSB'} first`, 'info');
+        } else {
+            log(`Failed to set bit order: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Show the SPI settings the CH347 reports
+async function showSpiConfig() {
+    try {
+        const result = await invoke('get_spi_config');
+
+        if (result.success) {
+            const c = result.data;
+            log(`SPI: ${(c.clock_hz / 1e6).toFixed(3)} MHz, mode ${c.mode}, ${c.lsb_first ? 'LSB' : 'MSB'} first`, 'info');
+        } else {
+            logFailure('Read SPI settings', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Switch to the flash on the selected chip select line and detect it
+async function setChipSelect() {
+    if (!isConnected) return;
+
+    const line = parseInt(elements.chipSelect.value);
+    log(`Switching to CS${line}...`, 'info');
+
+    if (!await detectChip('set_chip_select', { line })) {
+        // The backend forgets the previous chip when the line changes
+        chipDetected = false;
+        elements.chipInfo.innerHTML = '<p class="placeholder">No chip detected</p>';
+        updateUI();
+    }
+}
+
+// List devices answering on the I2C bus
+async function i2cScan() {
+    try {
+        const result = await invoke('i2c_scan');
+
+        if (result.success) {
+            const found = result.data.map(addr => '0x' + addr.toString(16).toUpperCase().padStart(2, '0'));
+            log(found.length ? `I2C devices: ${found.join(', ')}` : 'No I2C devices found', 'info');
+        } else {
+            log(`I2C scan failed: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Reopen the last adapter after a USB drop, keeping the detected chip
+async function reconnect() {
+    log('Reconnecting...', 'info');
+    isBusy = true;
+    updateUI();
+
+    try {
+        const result = await invoke('reconnect');
+
+        // A changed chip still leaves the adapter connected
+        isConnected = await invoke('is_connected');
+        if (result.success) {
+            showDeviceInfo(result.data.device);
+
+            if (result.data.chip) {
+                chipDetected = true;
+                chipHasUniqueId = result.data.chip.has_unique_id;
+                showChipInfo(result.data.chip);
+                log(`Reconnected, still on ${result.data.chip.summary}`, 'success');
+            } else {
+                log('Reconnected', 'success');
+            }
+        } else {
+            chipDetected = false;
+            elements.chipInfo.innerHTML = '<p class="placeholder">No chip detected</p>';
+            logFailure('Reconnect', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
+    isBusy = false;
+    updateUI();
+}
+
 // Disconnect from device
 async function disconnect() {
     log('Disconnecting...', 'info');
@@ -206,6 +589,7 @@ async function disconnect() {
         await invoke('disconnect');
         isConnected = false;
         chipDetected = false;
+        chipHasUniqueId = false;
 
         elements.deviceInfo.innerHTML = '<p class="placeholder">No device connected</p>';
         elements.chipInfo.innerHTML = '<p class="placeholder">No chip detected</p>';
@@ -218,39 +602,48 @@ async function disconnect() {
     updateUI();
 }
 
+// React to a CH347 being plugged in or removed
+async function handleDeviceChanged(device) {
+    if (device.connected) {
+        log(`${device.variant} plugged in`, 'info');
+        return;
+    }
+
+    log(`${device.variant} removed`, 'warning');
+
+    // The backend drops the connection if the removed adapter was ours
+    if (isConnected && !(await invoke('is_connected'))) {
+        isConnected = false;
+        chipDetected = false;
+        chipHasUniqueId = false;
+
+        elements.deviceInfo.innerHTML = '<p class="placeholder">No device connected</p>';
+        elements.chipInfo.innerHTML = '<p class="placeholder">No chip detected</p>';
+
+        log('Connection lost: adapter was unplugged', 'error');
+        updateUI();
+    }
+}
+
 // Detect flash chip
-async function detectChip() {
+async function detectChip(command = 'detect_chip', args = {}) {
     log('Detecting flash chip...', 'info');
     isBusy = true;
     updateUI();
+    let detected = false;
 
     try {
-        const result = await invoke('detect_chip');
+        const result = await invoke(command, args);
 
         if (result.success && result.data.detected) {
             chipDetected = true;
             const chip = result.data;
+            chipHasUniqueId = chip.has_unique_id;
 
-            elements.chipInfo.innerHTML = `
-                <div class="info-row">
-                    <span class="info-label">Chip:</span>
-                    <span class="info-value">${chip.name}</span>
-                </div>
-                <div class="info-row">
-                    <span class="info-label">Manufacturer:</span>
-                    <span class="info-value">${chip.manufacturer}</span>
-                </div>
-                <div class="info-row">
-                    <span class="info-label">JEDEC ID:</span>
-                    <span class="info-value">${chip.jedec_id}</span>
-                </div>
-                <div class="info-row">
-                    <span class="info-label">Size:</span>
-                    <span class="info-value">${chip.size_str}</span>
-                </div>
-            `;
-
-            log(`Detected: ${chip.manufacturer} ${chip.name} (${chip.size_str})`, 'success');
+            showChipInfo(chip);
+
+            log(`Detected: ${chip.summary}`, 'success');
+            detected = true;
         } else {
             log(`Detection failed: ${result.error || 'Unknown error'}`, 'error');
         }
@@ -258,10 +651,171 @@ async function detectChip() {
         log(`Error: ${e}`, 'error');
     }
 
+    isBusy = false;
+    updateUI();
+    return detected;
+}
+
+// Read flash unique ID
+async function readUniqueId() {
+    try {
+        const result = await invoke('read_unique_id');
+
+        if (result.success) {
+            log(`Unique ID: ${result.data}`, 'success');
+        } else {
+            log(`Unique ID read failed: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Round-trip the last sector to check the whole setup
+async function selfTest() {
+    log('Running self-test on the last sector...', 'info');
+    isBusy = true;
+    updateUI();
+    resetProgress();
+
+    try {
+        const result = await invoke('self_test');
+
+        if (!result.success) {
+            logFailure('Self-test', result);
+        } else if (result.data.passed) {
+            log('Self-test PASSED: erase, program and verify all work', 'success');
+        } else if (result.data.failed_step) {
+            log(`Self-test FAILED at ${result.data.failed_step} step: ${result.data.error}`, 'error');
+        }
+
+        if (result.success && !result.data.restored) {
+            log('Original content of the last sector could not be restored!', 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
     isBusy = false;
     updateUI();
 }
 
+// Measure read/erase/program speed at the current clock
+async function benchmark() {
+    log(`Benchmarking at ${elements.clockSpeed.selectedOptions[0].text}...`, 'info');
+    isBusy = true;
+    updateUI();
+    resetProgress();
+
+    try {
+        const result = await invoke('benchmark');
+
+        if (result.success) {
+            const b = result.data;
+            log(`Read ${b.read_kbps.toFixed(0)} KB/s, sector erase ${b.erase_ms.toFixed(1)} ms, ` +
+                `page program ${b.program_ms.toFixed(2)} ms`, 'success');
+        } else {
+            logFailure('Benchmark', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
+    isBusy = false;
+    updateUI();
+}
+
+// Compare the configured SPI clock with what a timed read achieves
+async function measureClock() {
+    isBusy = true;
+    updateUI();
+
+    try {
+        const result = await invoke('measure_clock');
+
+        if (result.success) {
+            const m = result.data;
+            log(`Effective clock ${(m.effective_hz / 1e6).toFixed(2)} MHz of ${(m.nominal_hz / 1e6).toFixed(2)} MHz ` +
+                `(${(m.efficiency * 100).toFixed(0)}%, ${(m.bytes_per_sec / 1024).toFixed(0)} KB/s)`, 'success');
+        } else {
+            logFailure('Clock measurement', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
+    isBusy = false;
+    updateUI();
+}
+
+// Read the raw JEDEC ID without changing the detected chip
+async function readJedec() {
+    try {
+        const result = await invoke('read_jedec');
+
+        if (result.success) {
+            log(`JEDEC ID: ${result.data}`, 'info');
+        } else {
+            logFailure('Read ID', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Save the raw SFDP tables, e.g. to attach to a chip support request
+async function dumpSfdp() {
+    try {
+        const savePath = await save({
+            filters: [{
+                name: 'Binary Files',
+                extensions: ['bin']
+            }]
+        });
+
+        if (!savePath) return;
+
+        const result = await invoke('dump_sfdp', { path: savePath });
+
+        if (result.success) {
+            const d = result.data;
+            const tables = d.tables.map(t =>
+                `${t.id.toString(16).toUpperCase()} v${t.major} @0x${t.offset.toString(16).toUpperCase()} (${t.length} bytes)`);
+            log(`SFDP saved to ${savePath}: ${d.size} bytes, ${d.header_count} parameter header(s): ${tables.join(', ')}`, 'success');
+        } else {
+            logFailure('SFDP dump', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Gather every identification step into one report for bug reports
+async function diagnostics() {
+    try {
+        const result = await invoke('diagnostics');
+
+        if (!result.success) {
+            logFailure('Diagnostics', result);
+            return;
+        }
+
+        const d = result.data;
+        const hex = v => v.toString(16).toUpperCase().padStart(2, '0');
+        const status = d.status ? `${hex(d.status.sr1)} ${hex(d.status.sr2)} ${hex(d.status.sr3)}` : '-';
+        const size = d.guessed_size ? `${d.guessed_size} bytes (from ${d.size_source})` : '-';
+
+        log(`JEDEC ID: ${d.jedec_id || '-'} (${d.manufacturer || '-'}), signature: ${d.signature || '-'}`, 'info');
+        log(`Status registers: ${status}, SFDP: ${d.sfdp_present ? 'present' : 'absent'}`, 'info');
+        log(`Database match: ${d.database_match || 'none'}, size: ${size}`, 'info');
+        for (const e of d.errors) {
+            log(`Diagnostics ${e.step} step failed: ${e.error}`, 'warning');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
 // Browse for file
 async function browseFile() {
     try {
@@ -270,6 +824,15 @@ async function browseFile() {
             filters: [{
                 name: 'BIOS Files',
                 extensions: ['bin', 'rom', 'fd', 'cap']
+            }, {
+                name: 'Intel HEX',
+                extensions: ['hex', 'ihex', 'ihx']
+            }, {
+                name: 'Motorola S-Record',
+                extensions: ['srec', 's19', 's28', 's37', 'mot']
+            }, {
+                name: 'Compressed',
+                extensions: ['gz', 'zst']
             }, {
                 name: 'All Files',
                 extensions: ['*']
@@ -522,6 +1085,18 @@ async function readFlash() {
             filters: [{
                 name: 'Binary Files',
                 extensions: ['bin']
+            }, {
+                name: 'Intel HEX',
+                extensions: ['hex']
+            }, {
+                name: 'Motorola S-Record',
+                extensions: ['srec']
+            }, {
+                name: 'Compressed (zstd)',
+                extensions: ['zst']
+            }, {
+                name: 'Compressed (gzip)',
+                extensions: ['gz']
             }]
         });
 
@@ -542,7 +1117,7 @@ async function readFlash() {
             currentFile = savePath;
             elements.filePath.value = savePath;
         } else {
-            log(`Read failed: ${result.error}`, 'error');
+            logFailure('Read', result);
         }
     } catch (e) {
         log(`Error: ${e}`, 'error');
@@ -561,6 +1136,7 @@ async function writeFlash() {
 
     const confirm = window.confirm(
         'WARNING: This will erase and overwrite the flash chip!\n\n' +
+        await describeWritePlan() +
         'Make sure you have a backup of the original content.\n\n' +
         'Continue?'
     );
@@ -573,16 +1149,126 @@ async function writeFlash() {
     resetProgress();
 
     try {
-        const verify = elements.verifyAfterWrite.checked;
+        const perPage = elements.verifyPerPage.checked;
+        const verify = perPage || elements.verifyAfterWrite.checked;
         const result = await invoke('write_flash', {
             path: currentFile,
+            verify: verify,
+            verifyMode: perPage ? 'PerPage' : (verify ? 'AfterAll' : 'None'),
+            eraseStrategy: elements.eraseStrategy.value,
+            skipBlankErase: elements.skipBlankErase.checked
+        });
+
+        if (result.success) {
+            log('Write complete!' + (verify ? ' Verification passed.' : ''), 'success');
+        } else {
+            logFailure('Write', result);
+            resumeOffset = result.resume_offset;
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
+    isBusy = false;
+    updateUI();
+    offerResume();
+}
+
+// Write the selected file to every connected adapter at once
+async function programAll() {
+    if (!currentFile) {
+        log('Please select a file first', 'warning');
+        return;
+    }
+
+    const confirm = window.confirm(
+        'WARNING: This will erase and overwrite the flash on EVERY connected CH347!\n\n' +
+        'Continue?'
+    );
+
+    if (!confirm) return;
+
+    log('Programming all adapters...', 'info');
+    isBusy = true;
+    updateUI();
+    resetProgress();
+
+    try {
+        const result = await invoke('program_all', {
+            path: currentFile,
+            verify: elements.verifyAfterWrite.checked || elements.verifyPerPage.checked
+        });
+
+        if (result.success) {
+            for (const d of result.data) {
+                if (d.success) {
+                    log(`${d.device}: programmed`, 'success');
+                } else {
+                    log(`${d.device}: FAILED - ${d.error}`, 'error');
+                }
+            }
+            const ok = result.data.filter(d => d.success).length;
+            log(`Program All: ${ok} of ${result.data.length} adapters succeeded`, ok === result.data.length ? 'success' : 'warning');
+        } else {
+            logFailure('Program All', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+
+    isBusy = false;
+    updateUI();
+}
+
+// Summarize what a write of the current file will erase and program
+async function describeWritePlan() {
+    try {
+        const result = await invoke('plan_write', { path: currentFile, offset: 0 });
+        if (!result.success) return '';
+
+        const p = result.data;
+        const hex = v => '0x' + v.toString(16).toUpperCase().padStart(6, '0');
+        const ranges = p.erase_ranges.map(r => `${hex(r.start)}-${hex(r.start + r.len - 1)}`);
+        return `Erase: ${ranges.join(', ')} (${p.erase_ops} operations, ${p.erase_bytes} bytes)\n` +
+            `Program: ${p.pages} pages, ${p.write_bytes} bytes\n` +
+            `Estimated time: about ${Math.ceil(p.estimated_secs)} s\n\n`;
+    } catch (e) {
+        return '';
+    }
+}
+
+// Offer to continue an interrupted write from where it stopped
+async function offerResume() {
+    if (resumeOffset === null || resumeOffset === undefined || !isConnected) return;
+
+    const offset = resumeOffset;
+    resumeOffset = null;
+
+    const resume = window.confirm(
+        `The write stopped at 0x${offset.toString(16).toUpperCase().padStart(6, '0')}.\n\n` +
+        'Resume writing the same file from there?'
+    );
+
+    if (!resume) return;
+
+    log('Resuming write...', 'info');
+    isBusy = true;
+    updateUI();
+    resetProgress();
+
+    try {
+        const verify = elements.verifyAfterWrite.checked;
+        const result = await invoke('write_flash_resume', {
+            path: currentFile,
+            startOffset: offset,
             verify: verify
         });
 
         if (result.success) {
             log('Write complete!' + (verify ? ' Verification passed.' : ''), 'success');
         } else {
-            log(`Write failed: ${result.error}`, 'error');
+            logFailure('Write', result);
+            resumeOffset = result.resume_offset;
         }
     } catch (e) {
         log(`Error: ${e}`, 'error');
@@ -590,6 +1276,7 @@ async function writeFlash() {
 
     isBusy = false;
     updateUI();
+    offerResume();
 }
 
 // Verify flash against file
@@ -605,16 +1292,23 @@ async function verifyFlash() {
     resetProgress();
 
     try {
-        const result = await invoke('verify_flash', { path: currentFile });
+        const result = await invoke('verify_flash', {
+            path: currentFile,
+            fileOnly: elements.verifyFileOnly.checked,
+            verifyCrc: elements.verifyCrc.checked
+        });
 
         if (result.success) {
-            if (result.data === true) {
+            const v = result.data;
+            if (v.ok) {
                 log('Verification PASSED!', 'success');
             } else {
-                log('Verification FAILED! Content does not match.', 'error');
+                const hex = (n, width) => n.toString(16).toUpperCase().padStart(width, '0');
+                log(`Verification FAILED! Mismatch at 0x${hex(v.mismatch_addr, 8)}: ` +
+                    `expected 0x${hex(v.expected, 2)} got 0x${hex(v.actual, 2)}`, 'error');
             }
         } else {
-            log(`Verify failed: ${result.error}`, 'error');
+            logFailure('Verify', result);
         }
     } catch (e) {
         log(`Error: ${e}`, 'error');
@@ -643,9 +1337,10 @@ async function eraseChip() {
         const result = await invoke('erase_chip');
 
         if (result.success) {
-            log('Erase complete!', 'success');
+            const opcode = result.data.toString(16).toUpperCase();
+            log(`Erase complete! (opcode 0x${opcode})`, 'success');
         } else {
-            log(`Erase failed: ${result.error}`, 'error');
+            logFailure('Erase', result);
         }
     } catch (e) {
         log(`Error: ${e}`, 'error');
@@ -655,5 +1350,75 @@ async function eraseChip() {
     updateUI();
 }
 
+// Cancel the running operation
+async function cancelOperation() {
+    log('Cancelling...', 'warning');
+
+    try {
+        await invoke('cancel_operation');
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Abort anything running and reset the bus and chip
+async function recoverDevice() {
+    log('Recovering...', 'warning');
+
+    try {
+        const result = await invoke('recover');
+
+        if (result.success) {
+            log(`Recovered, JEDEC ID ${result.data}`, 'success');
+        } else {
+            logFailure('Recovery', result);
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Pause the running operation between chunks, or let it carry on
+async function togglePause() {
+    const pause = !isPaused;
+
+    try {
+        await invoke(pause ? 'pause_operation' : 'resume_operation');
+        isPaused = pause;
+        log(pause ? 'Paused (a sector erase already started finishes first)' : 'Resumed', 'info');
+        updateUI();
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
+// Save a diagnostics report for bug reports
+async function exportReport() {
+    try {
+        const savePath = await save({
+            defaultPath: 'ch347-report.json',
+            filters: [{
+                name: 'JSON',
+                extensions: ['json']
+            }]
+        });
+
+        if (!savePath) return;
+
+        const result = await invoke('export_report', {
+            path: savePath,
+            includeSerial: elements.reportSerial.checked
+        });
+
+        if (result.success) {
+            log(`Report saved to: ${savePath}`, 'success');
+        } else {
+            log(`Export failed: ${result.error}`, 'error');
+        }
+    } catch (e) {
+        log(`Error: ${e}`, 'error');
+    }
+}
+
 // Initialize when DOM is ready
 document.addEventListener('DOMContentLoaded', init);